@@ -1,9 +1,23 @@
-use crate::task::{Task, TaskStatus};
+use crate::config::{Config, CONFIG_PATH};
+use crate::eventlog::{self, EVENT_LOG_PATH};
+use crate::quick_add::parse_quick_add;
+use crate::search::{search, search_case_insensitive};
+use crate::task::{
+    normalize_label, parse_estimate, parse_id_generator, parse_priority, parse_summary,
+    parse_timestamp, parse_when, short_id, Filter, IdGenerator, Task, TaskStatus,
+    DEFAULT_MAX_SUMMARY_LENGTH,
+};
 use crate::tasklist;
-use chrono::Local;
-use clap::{ArgAction, Parser, Subcommand};
-use colored::{ColoredString, Colorize};
+use crate::taskstore::{parse_backend, Backend};
+use crate::template::{list_templates, Template, TEMPLATES_DIR};
+use chrono::{DateTime, Local};
+use clap::{ArgAction, Args, Parser, Subcommand, ValueEnum};
+use colored::{Color, ColoredString, Colorize};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
 use std::error::Error;
+use std::fmt::{self, Write as _};
+use std::str::FromStr;
 
 trait ColoredStringExt {
     fn slate_blue(self) -> ColoredString;
@@ -19,26 +33,186 @@ impl ColoredStringExt for String {
 // const DB_PATH: &str = "./data/db.json";
 const DB_PATH: &str = "$HOME/.ztask/taskdb.json";
 
+/// Command line arguments the user passes when invoking the application
 #[derive(Parser, Default, Debug)]
 #[clap(name = "ZTask", author = "Tom Zakrajsek", version, about)]
-
-/// Command line arguments the user passes when invoking the application
 pub struct Arguments {
     #[command(subcommand)]
     command: Option<Command>,
 
-    /// Database file of tasks
-    #[clap(long, default_value = DB_PATH)]
-    db: String,
+    /// Database file of tasks, or $ZTASK_DB if it is set [default: $HOME/.ztask/taskdb.json]
+    #[clap(long)]
+    db: Option<String>,
+
+    /// Configuration file (an empty path uses no configuration) [default: $HOME/.ztask/config.json]
+    #[clap(long)]
+    config: Option<String>,
+
+    /// Directory of task templates
+    #[clap(long, default_value = TEMPLATES_DIR)]
+    templates: String,
+
+    /// Event log of task state transitions (an empty path disables logging) [default: $HOME/.ztask/events.log]
+    #[clap(long)]
+    event_log: Option<String>,
 
     /// Increase logging verbosity
     #[clap(short, long, action=ArgAction::Count)]
     verbose: u8,
 
+    /// Don't print incidental messages, such as tasks waking up or being unblocked
+    #[clap(short, long, action=ArgAction::SetTrue)]
+    quiet: bool,
+
+    /// Maximum number of tasks that may be active at once (0 for unlimited) [default: 1]
+    #[clap(long)]
+    max_active: Option<usize>,
+
+    /// strftime format used when displaying dates [default: %F]
+    #[clap(long)]
+    date_format: Option<String>,
+
+    /// Don't start the next backlog task when showing the active task and
+    /// none is active.  Without this, a bare `ztask` or `ztask show` starts it.
+    #[clap(long, global = true, action=ArgAction::SetTrue)]
+    no_auto_start: bool,
+
+    /// Don't save any changes to the database
+    #[clap(long, action=ArgAction::SetTrue)]
+    no_save: bool,
+
+    /// When an id matches several tasks, list them and ask which one was meant
+    #[clap(long, action=ArgAction::SetTrue)]
+    interactive: bool,
+
+    /// Require task ids to be given in full rather than as a prefix
+    #[clap(long, action=ArgAction::SetTrue)]
+    exact: bool,
+
+    /// Change nothing unless every id given to start, complete or del matches exactly one task
+    #[clap(long, action=ArgAction::SetTrue)]
+    strict: bool,
+
+    /// How to choose ids for new tasks: uuid, or short sequential ids such as "a1" [default: uuid]
+    #[clap(long, value_parser = parse_id_generator)]
+    id_generator: Option<IdGenerator>,
+
+    /// How to store tasks: heap, or vec for very large lists [default: vec above 10000 tasks, else heap]
+    #[clap(long, value_parser = parse_backend)]
+    backend: Option<Backend>,
+
+    /// Which active task comes first and is acted on by default: oldest or newest [default: newest]
+    #[clap(long, value_parser = tasklist::parse_active_order)]
+    active_order: Option<tasklist::ActiveOrder>,
+
+    /// Order to save tasks in: display, or id for a database kept under version control [default: display]
+    #[clap(long, value_parser = tasklist::parse_db_order)]
+    db_order: Option<tasklist::DbOrder>,
+
+    /// Highlight backlog tasks created longer ago than this, such as "3 weeks"
+    #[clap(long, visible_alias = "age-threshold", value_parser = parse_estimate)]
+    stale_after: Option<chrono::Duration>,
+
+    /// Truncate summaries in one-line output to this many characters
+    #[clap(long)]
+    summary_width: Option<usize>,
+
+    /// Follow the summary in one-line output with the first N characters of the details
+    #[clap(long, value_name = "N")]
+    show_details_preview: Option<usize>,
+
+    /// When to use colored output
+    #[clap(long, value_enum, default_value_t = ColorMode::Auto)]
+    color: ColorMode,
+
     #[clap(short='?', action=ArgAction::Help, help="Print help (alias for --help)")]
     help_short: Option<bool>,
 }
 
+/// When to color output
+#[derive(ValueEnum, Clone, Copy, Default, Debug, PartialEq)]
+enum ColorMode {
+    /// Let the terminal decide
+    #[default]
+    Auto,
+    /// Always color output
+    Always,
+    /// Never color output
+    Never,
+}
+
+/// How to group tasks in the list
+#[derive(ValueEnum, Clone, Copy, Default, Debug, PartialEq)]
+enum GroupBy {
+    /// Group tasks by status
+    #[default]
+    Status,
+    /// Group open tasks by category
+    Category,
+}
+
+/// Select a task by age instead of by id
+#[derive(Args, Clone, Copy, Debug, Default)]
+struct AgeSelector {
+    /// Select the oldest task (active, unless --status is given)
+    #[clap(long, conflicts_with_all = ["task_ids", "newest"])]
+    oldest: bool,
+
+    /// Select the newest task (active, unless --status is given)
+    #[clap(long, conflicts_with = "task_ids")]
+    newest: bool,
+}
+
+/// Output format for exported tasks
+#[derive(ValueEnum, Clone, Copy, Default, Debug, PartialEq)]
+enum ExportFormat {
+    /// A pretty-printed JSON array
+    #[default]
+    Json,
+    /// One compact JSON object per line
+    Jsonl,
+    /// An iCalendar file with a to-do for each task that has a due or scheduled time
+    Ical,
+    /// Tab-separated values with a header row and the columns id, status,
+    /// priority, category, created_at, due_at and summary
+    Tsv,
+}
+
+/// Output format for shown tasks
+#[derive(ValueEnum, Clone, Copy, Default, Debug, PartialEq)]
+enum ShowFormat {
+    /// Formatted for reading
+    #[default]
+    Human,
+    /// A JSON object, or an array when several tasks are shown
+    Json,
+    /// A YAML mapping, or a sequence when several tasks are shown
+    Yaml,
+}
+
+/// When to send output through a pager
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum PagerMode {
+    /// Page only when the output doesn't fit in the terminal
+    Auto,
+    /// Page whenever stdout is a terminal, however short the output
+    Always,
+    /// Always print straight to stdout
+    Never,
+}
+
+impl PagerMode {
+    fn from_flags(color: ColorMode, pager: bool, no_pager: bool) -> Self {
+        if color == ColorMode::Never || no_pager {
+            PagerMode::Never
+        } else if pager {
+            PagerMode::Always
+        } else {
+            PagerMode::Auto
+        }
+    }
+}
+
 /// Subcommands for the application
 #[derive(Subcommand, Debug)]
 enum Command {
@@ -47,8 +221,74 @@ enum Command {
         /// Increase logging verbosity
         #[clap(short, long, action=ArgAction::Count)]
         verbose: u8,
+
+        /// Only list tasks owned by this user
+        #[clap(long)]
+        owner: Option<String>,
+
+        /// Only list tasks with this status
+        #[clap(long, value_parser = TaskStatus::from_str)]
+        status: Option<TaskStatus>,
+
+        /// Only list tasks at least this urgent: priority 1-5 or high, medium or low, inclusive
+        #[clap(long, visible_alias = "priority-threshold", value_parser = parse_priority)]
+        max_priority: Option<u8>,
+
+        /// Only list tasks at most this urgent: priority 1-5 or high, medium or low, inclusive
+        #[clap(long, value_parser = parse_priority)]
+        min_priority: Option<u8>,
+
+        /// Print only the full id of each task, one per line
+        #[clap(long, action=ArgAction::SetTrue, conflicts_with_all = ["tree", "group_by", "numbered"])]
+        only_ids: bool,
+
+        /// Always send the list through $PAGER
+        #[clap(long, action=ArgAction::SetTrue, conflicts_with = "no_pager")]
+        pager: bool,
+
+        /// Never send the list through $PAGER
+        #[clap(long, action=ArgAction::SetTrue)]
+        no_pager: bool,
+
+        /// How to group the listed tasks
+        #[clap(long, value_enum, default_value_t = GroupBy::Status)]
+        group_by: GroupBy,
+
+        /// Number the listed tasks, so later commands can refer to them as #1, #2, ...
+        #[clap(short, long, action=ArgAction::SetTrue)]
+        numbered: bool,
+
+        /// Show open tasks as a tree, with tasks blocked on a task indented beneath it
+        #[clap(
+            long,
+            visible_alias = "group-blocked-under-blocker",
+            action=ArgAction::SetTrue,
+            conflicts_with_all = ["group_by", "numbered"]
+        )]
+        tree: bool,
+
+        /// List every open task that has a due date, soonest first, whatever its status
+        #[clap(
+            long,
+            action=ArgAction::SetTrue,
+            conflicts_with_all = ["tree", "group_by", "numbered", "only_ids"]
+        )]
+        due_soon: bool,
+
+        /// List only the active tasks and the open tasks due or scheduled today
+        #[clap(
+            long,
+            action=ArgAction::SetTrue,
+            conflicts_with_all = ["tree", "group_by", "numbered", "only_ids", "due_soon"]
+        )]
+        today: bool,
+
+        /// Expand environment variables, such as $PROJECT, in the summaries shown
+        #[clap(long, action=ArgAction::SetTrue)]
+        expand_env: bool,
     },
-    /// Show specific tasks.  Shows currently active tasks by default.
+    /// Show specific tasks.  Shows currently active tasks by default, starting
+    /// the next backlog task if none is active (unless --no-auto-start is given).
     Show {
         /// Increase logging verbosity
         #[clap(short, long, action=ArgAction::Count)]
@@ -56,6 +296,53 @@ enum Command {
         /// Id(s) of task(s) to show
         #[clap(num_args(0..), action=ArgAction::Append)]
         task_ids: Option<Vec<String>>,
+
+        /// Show every task with this status in detail, when no ids are given
+        #[clap(long, value_parser = TaskStatus::from_str)]
+        status: Option<TaskStatus>,
+
+        /// Output format
+        #[clap(long, value_enum, default_value_t = ShowFormat::Human)]
+        format: ShowFormat,
+
+        /// Show the id, priority, status and summary on one line, with any
+        /// details wrapped below
+        #[clap(long, action=ArgAction::SetTrue, conflicts_with = "verbose")]
+        compact: bool,
+
+        /// Expand environment variables, such as $PROJECT, in the summaries
+        /// and details shown
+        #[clap(long, action=ArgAction::SetTrue)]
+        expand_env: bool,
+
+        #[clap(flatten)]
+        select: AgeSelector,
+    },
+    /// Search task summaries and details for text
+    Search {
+        /// Text to search for
+        #[clap(num_args(1..), required = true)]
+        query: Vec<String>,
+
+        /// Ignore case when matching
+        #[clap(short, long, action=ArgAction::SetTrue)]
+        ignore_case: bool,
+
+        /// Only print the number of matching tasks
+        #[clap(short, long, action=ArgAction::SetTrue)]
+        count_only: bool,
+
+        /// Print only the full id of each matching task, one per line
+        #[clap(long, action=ArgAction::SetTrue, conflicts_with = "count_only")]
+        only_ids: bool,
+
+        /// Only search the summaries, not the details
+        #[clap(short, long, action=ArgAction::SetTrue)]
+        summary_only: bool,
+
+        /// Print at most this many matches, most relevant first
+        #[clap(short, long, conflicts_with_all = ["count_only", "only_ids"])]
+        limit: Option<usize>,
     },
     /// Add one or more new tasks
     Add {
@@ -67,15 +354,50 @@ enum Command {
         #[clap(short, long, action=ArgAction::SetTrue)]
         is_interrupt: bool,
 
+        /// Add the task(s) sleeping for this long, such as "1 week"
+        #[clap(long, value_parser = parse_estimate, conflicts_with = "is_interrupt")]
+        sleep: Option<chrono::Duration>,
+
         /// Invoke editor on for each added task
         #[clap(short, long, action=ArgAction::SetTrue)]
         edit: bool,
+
+        /// Add one task per name, even when every name is a single word
+        #[clap(long, action=ArgAction::SetTrue)]
+        separate: bool,
+
+        /// Owner of the task(s)
+        #[clap(long)]
+        owner: Option<String>,
+
+        /// Priority of the task(s): 1-5, or high, medium or low
+        #[clap(short, long, value_parser = parse_priority)]
+        priority: Option<u8>,
+
+        /// Estimated effort for the task(s), such as "2h 30m"
+        #[clap(long, value_parser = parse_estimate)]
+        estimate: Option<chrono::Duration>,
+
+        /// Details of the task(s)
+        #[clap(long)]
+        details: Option<String>,
+
+        /// File to read the details of the task(s) from
+        #[clap(long, conflicts_with = "details")]
+        details_from: Option<String>,
+
+        /// Use the fields of this saved template as defaults for the task(s)
+        #[clap(long)]
+        template: Option<String>,
     },
     /// Del one or more tasks
     Del {
         /// Id(s) of task(s) to delete
         #[clap(num_args(0..), action=ArgAction::Append)]
         task_ids: Option<Vec<String>>,
+
+        #[clap(flatten)]
+        select: AgeSelector,
     },
     /// Edit one or more tasks
     Edit {
@@ -86,26 +408,102 @@ enum Command {
         /// Indicate that the we should only edit the details (which makes multiline editing easier)
         #[clap(short, long, action=ArgAction::SetTrue)]
         details_only: bool,
+
+        /// Edit all the tasks at once, as a JSON array in a single editor buffer
+        #[clap(
+            short,
+            long,
+            action=ArgAction::SetTrue,
+            conflicts_with_all = ["details_only", "owner", "priority", "estimate", "set"]
+        )]
+        batch: bool,
+
+        /// Set the owner of the task(s) instead of invoking the editor
+        #[clap(long, conflicts_with = "details_only")]
+        owner: Option<String>,
+
+        /// Set the priority (1-5, or high, medium or low) instead of invoking the editor
+        #[clap(short, long, value_parser = parse_priority, conflicts_with = "details_only")]
+        priority: Option<u8>,
+
+        /// Set the estimated effort, such as "2h 30m", instead of invoking the editor
+        #[clap(long, value_parser = parse_estimate, conflicts_with = "details_only")]
+        estimate: Option<chrono::Duration>,
+
+        /// Set a field (priority, summary, category, status, owner, estimate, snooze) instead of invoking the editor
+        #[clap(
+            long,
+            value_name = "KEY=VALUE",
+            action=ArgAction::Append,
+            conflicts_with = "details_only"
+        )]
+        set: Vec<String>,
+    },
+    /// Nudge the priority of one or more tasks up or down
+    #[clap(allow_negative_numbers = true)]
+    Bump {
+        /// Id(s) of task(s) to bump
+        #[clap(num_args(1..), required = true)]
+        task_ids: Vec<String>,
+
+        /// Amount to add to the priority, such as -1 to make the task(s) more urgent
+        delta: i8,
+    },
+    /// Return tasks to the backlog, clearing any wake or scheduled time, blockers and completion
+    Reset {
+        /// Id(s) of task(s) to reset
+        #[clap(num_args(1..), required = true)]
+        task_ids: Vec<String>,
     },
     /// Start work on a task
     Start {
         /// Id(s) of task(s) to start
         #[clap(num_args(0..), action=ArgAction::Append)]
         task_ids: Option<Vec<String>>,
+
+        /// Record the task(s) as started at this time (YYYY-MM-DD [HH:MM]) rather than now
+        #[clap(long, value_parser = parse_timestamp)]
+        at: Option<DateTime<Local>>,
     },
     /// Stop work on a task
     Stop {
         /// Id(s) of task(s) to stop
         #[clap(num_args(0..), action=ArgAction::Append)]
         task_ids: Option<Vec<String>>,
+
+        #[clap(flatten)]
+        select: AgeSelector,
     },
     /// Put one or more tasks to sleep
     Sleep {
         /// Id(s) of task(s) to put to sleep
         #[clap(num_args(0..), action=ArgAction::Append)]
         task_ids: Option<Vec<String>>,
-        #[clap(short, long)]
-        duration: String,
+
+        #[clap(flatten)]
+        select: AgeSelector,
+
+        /// How long to sleep for, such as "3d" or "2h 30m"
+        #[clap(short, long, value_parser = parse_estimate, required_unless_present = "until_task")]
+        duration: Option<chrono::Duration>,
+
+        /// Wake at this hour (0-23) of the day the sleep ends on
+        #[clap(long, value_parser = clap::value_parser!(u32).range(0..24))]
+        wake_at_hour: Option<u32>,
+
+        /// Sleep until this task is completed rather than for a duration.
+        /// This blocks the task(s) on it, but like sleep, and unlike block,
+        /// it applies to the active task when no ids are given.
+        #[clap(long, conflicts_with_all = ["duration", "wake_at_hour"])]
+        until_task: Option<String>,
+    },
+    /// Schedule a task to become active at a later time
+    Schedule {
+        /// Id of task to schedule
+        task_id: String,
+        /// When to start the task: YYYY-MM-DD [HH:MM], or a duration such as "3 days"
+        #[clap(value_parser = parse_when)]
+        when: DateTime<Local>,
     },
     /// Block a task on one or more other tasks
     Block {
@@ -118,18 +516,335 @@ enum Command {
         /// Id(s) of task(s) to complete
         #[clap(num_args(0..), action=ArgAction::Append)]
         task_ids: Option<Vec<String>>,
+
+        /// Without ids, complete this many active tasks, oldest first
+        #[clap(short = 'n', long, conflicts_with = "task_ids")]
+        count: Option<usize>,
+
+        /// Then start the next backlog task
+        #[clap(long, action=ArgAction::SetTrue)]
+        then_next: bool,
+
+        /// Remove the task(s) once completed, rather than keeping them as completed
+        #[clap(long, action=ArgAction::SetTrue, conflicts_with = "at")]
+        delete: bool,
+
+        /// Record the task(s) as completed at this time (YYYY-MM-DD [HH:MM]) rather than now
+        #[clap(long, value_parser = parse_timestamp)]
+        at: Option<DateTime<Local>>,
+
+        #[clap(flatten)]
+        select: AgeSelector,
+    },
+    /// Show the event log of task state transitions
+    History {
+        /// Id of the task to show history for (all tasks by default)
+        task_id: Option<String>,
+    },
+    /// Show task counts, recent throughput and the estimated remaining work
+    Stats {
+        /// Count the tasks completed within this long before now, such as "30d"
+        #[clap(long, value_parser = parse_estimate, default_value = "7d")]
+        completed_since: chrono::Duration,
+    },
+    /// Write all tasks to stdout as JSON (or iCalendar or TSV), in sorted order
+    Export {
+        /// Output format
+        #[clap(long, value_enum, default_value_t = ExportFormat::Json)]
+        format: ExportFormat,
+
+        /// Only export tasks changed after this time (RFC 3339 or YYYY-MM-DD [HH:MM])
+        #[clap(long, value_parser = parse_timestamp)]
+        since: Option<DateTime<Local>>,
+
+        /// Only export this field of each task, such as "id" (json and jsonl only)
+        #[clap(long = "field", value_name = "NAME", action=ArgAction::Append)]
+        fields: Vec<String>,
+    },
+    /// Spread backlog priorities evenly across 1-5, keeping their order
+    Rebalance,
+    /// Move every task in one category to another
+    RenameCategory {
+        /// Category to rename
+        from: String,
+        /// New name of the category
+        to: String,
+    },
+    /// Replace a tag with another on every task that has it
+    #[clap(visible_alias = "retag")]
+    RenameTag {
+        /// Tag to rename
+        from: String,
+        /// New name of the tag
+        to: String,
+    },
+    /// Replace the database with a backup, saving the current state as the new backup
+    Restore {
+        /// Backup file to restore from (defaults to the database path plus ".bak")
+        path: Option<String>,
+    },
+    /// List the saved task templates, or save a task as a template
+    Template {
+        /// Save a template with this name
+        #[clap(long, requires = "from")]
+        save: Option<String>,
+
+        /// Id of the task to make the template from
+        #[clap(long, requires = "save")]
+        from: Option<String>,
+    },
+    /// Stop active tasks that were started longer ago than the idle time
+    Sweep {
+        /// How long a task may stay active, such as "8h" or "2 days"
+        #[clap(long, value_parser = parse_estimate)]
+        idle: chrono::Duration,
+    },
+    /// Merge open tasks that have the same summary into the oldest of them
+    Dedupe {
+        /// Merge every group of duplicates without asking
+        #[clap(short, long, action=ArgAction::SetTrue)]
+        yes: bool,
     },
 }
 
+use std::io::{BufRead, IsTerminal, Write};
 use std::path::Path;
+use std::process::Stdio;
 use std::vec;
+use terminal_size::{terminal_size, Height, Width};
+
+/// Default strftime format for displaying dates
+const DATE_FORMAT: &str = "%F";
+
+/// Settings that control how tasks are rendered
+#[derive(Debug)]
+pub struct DisplayOptions {
+    /// strftime format used for dates
+    pub date_format: String,
+    /// Maximum number of characters of the summary shown in one-line output
+    pub summary_width: Option<usize>,
+    /// Colors for task summaries, by category
+    pub category_colors: BTreeMap<String, Color>,
+    /// Age after which backlog tasks are highlighted as stale
+    pub stale_after: Option<chrono::Duration>,
+    /// Column widths that line one-line output up with the terminal, when
+    /// its width is known
+    pub columns: Option<Columns>,
+    /// Number of characters of the details shown after the summary in
+    /// one-line output
+    pub details_preview: Option<usize>,
+    /// Expand environment variables in the summaries and details shown.
+    /// The stored tasks are left as they are.
+    pub expand_env: bool,
+    /// Looks up the value of an environment variable when expanding
+    pub env_var: fn(&str) -> Option<String>,
+}
+
+/// Widths of the columns of one-line output
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Columns {
+    pub id: usize,
+    pub status: usize,
+    pub date: usize,
+    /// Room left for the summary on the line
+    pub summary: usize,
+}
+
+/// Narrowest the summary column gets, however narrow the terminal is
+const MIN_SUMMARY_WIDTH: usize = 20;
+
+/// Compute column widths that fit one-line output for the tasks into the
+/// given terminal width.  Id and status are as wide as the widest of them,
+/// and the summary gets whatever room is left.
+fn compute_columns<'a>(
+    tasks: impl Iterator<Item = &'a Task>,
+    width: usize,
+    display: &DisplayOptions,
+) -> Columns {
+    let (id, status) = tasks.fold((0, 0), |(id, status), task| {
+        (
+            id.max(short_id(&task.id).chars().count()),
+            status.max(task.status.to_string().len()),
+        )
+    });
+    let date = display.format_date(&Local::now()).chars().count();
+    // Two space indent and gaps around id, priority, status and date
+    let used = 2 + id + 2 + 1 + 2 + status + 2 + date + 2;
+    Columns {
+        id,
+        status,
+        date,
+        summary: width.saturating_sub(used).max(MIN_SUMMARY_WIDTH),
+    }
+}
+
+impl DisplayOptions {
+    /// Format a date using the configured date format
+    fn format_date(&self, date: &DateTime<Local>) -> String {
+        date.format(&self.date_format).to_string()
+    }
+
+    /// Truncate a summary to the configured width for one-line output,
+    /// marking the cut with an ellipsis
+    fn format_summary(&self, summary: &str) -> String {
+        let summary = self.expand(summary);
+        let column_width = self.columns.map(|columns| columns.summary);
+        let width = match (self.summary_width, column_width) {
+            (Some(width), Some(column_width)) => Some(width.min(column_width)),
+            (width, column_width) => width.or(column_width),
+        };
+        match width {
+            Some(width) if summary.chars().count() > width => {
+                let mut truncated: String = summary.chars().take(width.saturating_sub(1)).collect();
+                truncated.push('…');
+                truncated
+            }
+            _ => summary.to_string(),
+        }
+    }
+
+    /// Expand environment variables in text, if expansion is turned on.
+    /// Undefined variables are left as they are.
+    fn expand<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        if !self.expand_env {
+            return Cow::Borrowed(text);
+        }
+        shellexpand::env_with_context_no_errors(text, self.env_var)
+    }
+
+    /// Color text with its category's configured color, if it has one
+    fn tint(&self, category: &str, text: ColoredString) -> ColoredString {
+        match self.category_colors.get(category) {
+            Some(color) => text.color(*color),
+            None => text,
+        }
+    }
+
+    /// Return true if the task has sat in the backlog for longer than the
+    /// stale threshold
+    fn is_stale(&self, task: &Task, now: &DateTime<Local>) -> bool {
+        task.status == TaskStatus::Backlog
+            && self
+                .stale_after
+                .is_some_and(|threshold| *now - task.created_at > threshold)
+    }
+
+    /// The start of the details, on a single line, for one-line output.
+    /// None if previews are off or the task has no details.
+    fn details_preview(&self, details: &str) -> Option<String> {
+        let length = self.details_preview?;
+        let preview: String = self
+            .expand(details)
+            .trim()
+            .chars()
+            .map(|c| if c == '\n' || c == '\r' { ' ' } else { c })
+            .take(length)
+            .collect();
+        Some(preview).filter(|preview| !preview.is_empty())
+    }
+
+    /// Pad an id to the width of the id column, if columns are aligned
+    fn pad_id(&self, id: &str) -> String {
+        let width = self.columns.map_or(0, |columns| columns.id);
+        format!("{:width$}", short_id(id))
+    }
+
+    /// Pad a status to the width of the status column, if columns are aligned
+    fn pad_status(&self, status: &TaskStatus) -> String {
+        let width = self.columns.map_or(0, |columns| columns.status);
+        format!("{:width$}", status.to_string())
+    }
+
+    /// Format a date and time of day using the configured date format
+    fn format_datetime(&self, date: &DateTime<Local>) -> String {
+        date.format(&format!("{} %T", self.date_format)).to_string()
+    }
+}
+
+impl Default for DisplayOptions {
+    fn default() -> Self {
+        DisplayOptions {
+            date_format: DATE_FORMAT.to_string(),
+            summary_width: None,
+            category_colors: BTreeMap::new(),
+            stale_after: None,
+            columns: None,
+            details_preview: None,
+            expand_env: false,
+            env_var: read_env_var,
+        }
+    }
+}
+
+/// The value of an environment variable, or None if it isn't set
+fn read_env_var(name: &str) -> Option<String> {
+    std::env::var(name).ok()
+}
+
+/// Check that a strftime format string contains only valid specifiers, so
+/// that formatting a date with it can't panic.
+fn validate_date_format(format: &str) -> Result<(), String> {
+    use chrono::format::{Item, StrftimeItems};
+    if StrftimeItems::new(format).any(|item| matches!(item, Item::Error)) {
+        return Err(format!("Invalid date format '{}'", format));
+    }
+    Ok(())
+}
+
+/// Parse the configured category colors.  Categories are stored normalized,
+/// so the configured names are normalized the same way to match them.
+fn parse_category_colors(
+    colors: &BTreeMap<String, String>,
+) -> Result<BTreeMap<String, Color>, String> {
+    colors
+        .iter()
+        .map(|(category, color)| Ok((normalize_label(category), parse_color(color)?)))
+        .collect()
+}
+
+/// Parse a color given either by name, such as "red" or "bright blue", or
+/// as an RGB hex string such as "#ff8800".
+fn parse_color(color: &str) -> Result<Color, String> {
+    let invalid = || format!("Invalid color '{}'", color);
+    match color.strip_prefix('#') {
+        Some(hex) if hex.len() == 6 => {
+            let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| invalid());
+            Ok(Color::TrueColor {
+                r: channel(0)?,
+                g: channel(2)?,
+                b: channel(4)?,
+            })
+        }
+        Some(_) => Err(invalid()),
+        None => color.parse().map_err(|_| invalid()),
+    }
+}
 
 fn create_path(file_path: &str) -> std::io::Result<()> {
     // Create a Path from the provided file_path
     let path = Path::new(file_path);
+    let misconfigured =
+        |message: String| std::io::Error::new(std::io::ErrorKind::InvalidInput, message);
+
+    if path.is_dir() {
+        return Err(misconfigured(format!(
+            "{} is a directory, not a database file",
+            path.display()
+        )));
+    }
 
     // Create the directory if it doesn't exist
     if let Some(parent) = path.parent() {
+        // Anything in the way that isn't a directory would make
+        // create_dir_all fail with an unhelpful error
+        if let Some(blocker) = parent.ancestors().find(|dir| dir.exists()) {
+            if !blocker.is_dir() {
+                return Err(misconfigured(format!(
+                    "{} exists but is not a directory",
+                    blocker.display()
+                )));
+            }
+        }
         if !parent.exists() {
             std::fs::create_dir_all(parent)?;
         }
@@ -138,16 +853,147 @@ fn create_path(file_path: &str) -> std::io::Result<()> {
     Ok(())
 }
 
+/// Choose the database path: --db, then $ZTASK_DB, then the default
+fn resolve_db_path(db: Option<String>, env_db: Option<String>) -> String {
+    db.or(env_db.filter(|path| !path.is_empty()))
+        .unwrap_or(DB_PATH.to_string())
+}
+
+/// Turn colored output on or off.  Setting NO_COLOR (https://no-color.org)
+/// to any value turns it off, unless --color always is given.
+fn apply_color_mode(color: ColorMode, no_color: bool) {
+    match color {
+        ColorMode::Always => colored::control::set_override(true),
+        ColorMode::Never => colored::control::set_override(false),
+        ColorMode::Auto if no_color => colored::control::set_override(false),
+        ColorMode::Auto => (),
+    }
+}
+
 pub fn run(arg_overrides: Option<Arguments>) -> Result<(), Box<dyn Error>> {
     let args = arg_overrides.unwrap_or(Arguments::parse());
-    let db_path = shellexpand::env(&args.db)?;
+    apply_color_mode(args.color, std::env::var_os("NO_COLOR").is_some());
+    // Don't read the real configuration when running tests
+    let default_config = if cfg!(test) { "" } else { CONFIG_PATH };
+    let config = match args.config.as_deref().unwrap_or(default_config) {
+        "" => Config::default(),
+        path => Config::load(&shellexpand::env(path)?)?,
+    };
+    let mut display = DisplayOptions {
+        date_format: args
+            .date_format
+            .or(config.date_format)
+            .unwrap_or(DATE_FORMAT.to_string()),
+        summary_width: args.summary_width,
+        category_colors: parse_category_colors(&config.category_colors)?,
+        stale_after: match args.stale_after {
+            Some(threshold) => Some(threshold),
+            None => config
+                .stale_after
+                .as_deref()
+                .map(parse_estimate)
+                .transpose()?,
+        },
+        columns: None,
+        details_preview: args.show_details_preview,
+        expand_env: false,
+        env_var: read_env_var,
+    };
+    validate_date_format(&display.date_format)?;
+    let db_path = resolve_db_path(args.db, std::env::var("ZTASK_DB").ok());
+    let db_path = shellexpand::env(&db_path)?;
     create_path(&db_path)?;
-    let mut task_list = tasklist::TaskList::new(db_path.to_string());
+    // Declared before the task list so it is released after the list is saved
+    let _lock = tasklist::DbLock::acquire(&db_path)?;
+    let mut task_list = tasklist::TaskList::open(db_path.to_string(), args.quiet)?;
+    task_list.exact = args.exact;
+    task_list.no_save = args.no_save;
+    let backend = args
+        .backend
+        .unwrap_or(Backend::for_size(task_list.num_tasks()));
+    task_list.set_backend(backend);
+    // Don't write to the real event log when running tests
+    let default_event_log = if cfg!(test) { "" } else { EVENT_LOG_PATH };
+    let event_log = args
+        .event_log
+        .or(config.event_log)
+        .unwrap_or(default_event_log.to_string());
+    if !event_log.is_empty() {
+        task_list.event_log = Some(shellexpand::env(&event_log)?.to_string());
+    }
+    if config.wake_at_hour.is_some_and(|hour| hour > 23) {
+        return Err("wake_at_hour in the config must be from 0 to 23".into());
+    }
+    task_list.active_order = args
+        .active_order
+        .or(config.active_order)
+        .unwrap_or_default();
+    task_list.db_order = args.db_order.or(config.db_order).unwrap_or_default();
+    task_list.id_generator = args
+        .id_generator
+        .or(config.id_generator)
+        .unwrap_or_default();
+    task_list.max_active = args
+        .max_active
+        .or(config.max_active)
+        .unwrap_or(tasklist::DEFAULT_MAX_ACTIVE);
+    // Loading only starts scheduled tasks up to the default limit, so start
+    // any more that the configured limit has room for
+    let started = task_list.activate_scheduled_tasks();
+    if started > 0 {
+        task_list.note(format!("Started {} scheduled task(s)", started));
+    }
+    task_list.max_summary_length = config
+        .max_summary_length
+        .unwrap_or(DEFAULT_MAX_SUMMARY_LENGTH);
+    // Line columns up when writing to a terminal of known width
+    if std::io::stdout().is_terminal() {
+        if let Some(width) = terminal_columns() {
+            display.columns = Some(compute_columns(task_list.tasks.iter(), width, &display));
+        }
+    }
 
     if let Some(subcmd) = args.command {
         match subcmd {
-            Command::List { verbose } => {
-                match process_list(&mut task_list, std::cmp::max(args.verbose, verbose), true) {
+            Command::List {
+                verbose,
+                owner,
+                status,
+                max_priority,
+                min_priority,
+                only_ids,
+                pager,
+                no_pager,
+                group_by,
+                numbered,
+                tree,
+                due_soon,
+                today,
+                expand_env,
+            } => {
+                display.expand_env = expand_env;
+                match process_list(
+                    &mut task_list,
+                    std::cmp::max(args.verbose, verbose),
+                    true,
+                    &ListOptions {
+                        filter: Filter {
+                            owner,
+                            status,
+                            max_priority,
+                            min_priority,
+                            ..Default::default()
+                        },
+                        group_by,
+                        numbered,
+                        tree,
+                        only_ids,
+                        due_soon,
+                        today,
+                    },
+                    &display,
+                    PagerMode::from_flags(args.color, pager, no_pager),
+                ) {
                     Ok(c) => {
                         if args.verbose > 0 {
                             println!("{} task(s) found", c)
@@ -156,14 +1002,67 @@ pub fn run(arg_overrides: Option<Arguments>) -> Result<(), Box<dyn Error>> {
                     Err(e) => eprintln!("error in processing : {}", e),
                 }
             }
-            Command::Show { task_ids, verbose } => match process_show(
-                &mut task_list,
-                std::cmp::max(args.verbose, verbose),
-                task_ids.unwrap_or_default(),
+            Command::Show {
+                task_ids,
+                verbose,
+                status,
+                format,
+                compact,
+                expand_env,
+                select,
+            } => {
+                display.expand_env = expand_env;
+                let task_ids = choose_ids(
+                    &task_list,
+                    select_task_ids(&task_list, task_ids, select, status.clone())?,
+                    args.interactive,
+                )?;
+                if format != ShowFormat::Human {
+                    print!(
+                        "{}",
+                        serialize_shown_tasks(&task_list, task_ids, status, format)?
+                    );
+                } else {
+                    match process_show(
+                        &mut task_list,
+                        std::cmp::max(args.verbose, verbose),
+                        task_ids,
+                        status,
+                        !args.no_auto_start,
+                        compact,
+                        &display,
+                    ) {
+                        Ok(c) => {
+                            if args.verbose > 0 {
+                                println!("{} task(s) updated", c)
+                            }
+                        }
+                        Err(e) => eprintln!("error in processing : {}", e),
+                    }
+                }
+            }
+            Command::Search {
+                query,
+                ignore_case,
+                count_only,
+                only_ids,
+                summary_only,
+                limit,
+            } => match process_search(
+                &task_list,
+                &query.join(" "),
+                &SearchOptions {
+                    ignore_case,
+                    include_details: !summary_only,
+                    count_only,
+                    only_ids,
+                    limit,
+                },
+                &display,
             ) {
                 Ok(c) => {
-                    if args.verbose > 0 {
-                        println!("{} task(s) updated", c)
+                    if args.verbose > 0 && !count_only && !only_ids {
+                        println!("{} task(s) found", c)
                     }
                 }
                 Err(e) => eprintln!("error in processing : {}", e),
@@ -171,28 +1070,72 @@ pub fn run(arg_overrides: Option<Arguments>) -> Result<(), Box<dyn Error>> {
             Command::Add {
                 task_names,
                 is_interrupt,
+                sleep,
                 edit,
-            } => match process_add(&mut task_list, task_names.unwrap_or_default(), is_interrupt) {
-                Ok(ids) => {
-                    if args.verbose > 0 {
-                        println!("created task(s) {:?}", ids);
-                    }
-                    if edit {
-                        // Invoke editor on each new task
-                        match process_edit(&mut task_list, ids, false) {
-                            Ok(c) => {
-                                if args.verbose > 0 {
-                                    println!("edited {} task(s)", c);
+                separate,
+                owner,
+                priority,
+                estimate,
+                details,
+                details_from,
+                template,
+            } => {
+                let template = match template {
+                    Some(name) => Template::load(&shellexpand::env(&args.templates)?, &name)?,
+                    None => Template::default(),
+                };
+                match process_add(
+                    &mut task_list,
+                    task_names.unwrap_or_default(),
+                    &AddOptions {
+                        is_interrupt,
+                        sleep,
+                        separate,
+                        owner: owner.or(template.owner).or_else(default_owner),
+                        priority: priority.or(template.priority),
+                        estimate: estimate.or(template.estimate),
+                        details: match details_from {
+                            Some(path) => Some(std::fs::read_to_string(&path).map_err(|e| {
+                                format!("failed to read details from '{}': {}", path, e)
+                            })?),
+                            None => details.or(template.details),
+                        },
+                        category: template.category,
+                        tags: template.tags,
+                        default_summary: template.summary,
+                        quick_add: config.quick_add.unwrap_or(true),
+                    },
+                    &display,
+                ) {
+                    Ok(ids) => {
+                        if args.verbose > 0 {
+                            println!("created task(s) {:?}", ids);
+                        }
+                        if edit {
+                            // Invoke editor on each new task
+                            match process_edit(&mut task_list, ids, false) {
+                                Ok(c) => {
+                                    if args.verbose > 0 {
+                                        println!("edited {} task(s)", c);
+                                    }
                                 }
+                                Err(e) => eprintln!("error in processing : {}", e),
                             }
-                            Err(e) => eprintln!("error in processing : {}", e),
                         }
                     }
+                    Err(e) => return Err(e),
                 }
-                Err(e) => eprintln!("error in processing : {}", e),
-            },
-            Command::Start { task_ids } => {
-                match process_start(&mut task_list, task_ids.unwrap_or_default()) {
+            }
+            Command::Start { task_ids, at } => {
+                let task_ids = choose_ids(
+                    &task_list,
+                    task_list.resolve_ordinals(task_ids.unwrap_or_default()),
+                    args.interactive,
+                )?;
+                if args.strict {
+                    check_ids_resolve(&task_list, &task_ids)?;
+                }
+                match process_start(&mut task_list, task_ids, at.unwrap_or_else(Local::now)) {
                     Ok(c) => {
                         if args.verbose > 0 {
                             println!("{} task(s) started", c)
@@ -201,8 +1144,13 @@ pub fn run(arg_overrides: Option<Arguments>) -> Result<(), Box<dyn Error>> {
                     Err(e) => eprintln!("error in processing : {}", e),
                 }
             }
-            Command::Stop { task_ids } => {
-                match process_stop(&mut task_list, task_ids.unwrap_or_default()) {
+            Command::Stop { task_ids, select } => {
+                let task_ids = choose_ids(
+                    &task_list,
+                    select_task_ids(&task_list, task_ids, select, None)?,
+                    args.interactive,
+                )?;
+                match process_stop(&mut task_list, task_ids) {
                     Ok(c) => {
                         if args.verbose > 0 {
                             println!("{} task(s) stopped", c)
@@ -211,8 +1159,38 @@ pub fn run(arg_overrides: Option<Arguments>) -> Result<(), Box<dyn Error>> {
                     Err(e) => eprintln!("error in processing : {}", e),
                 }
             }
-            Command::Sleep { task_ids, duration } => {
-                match process_sleep(&mut task_list, task_ids.unwrap_or_default(), duration) {
+            Command::Sleep {
+                task_ids,
+                duration,
+                wake_at_hour,
+                until_task,
+                select,
+            } => {
+                let task_ids = choose_ids(
+                    &task_list,
+                    select_task_ids(&task_list, task_ids, select, None)?,
+                    args.interactive,
+                )?;
+                let result = match until_task {
+                    Some(blocker_id) => {
+                        let blocker_id = choose_ids(
+                            &task_list,
+                            task_list.resolve_ordinals(vec![blocker_id]),
+                            args.interactive,
+                        )?
+                        .remove(0);
+                        process_sleep_until_task(&mut task_list, task_ids, blocker_id)
+                    }
+                    // clap requires a duration when there's no --until-task
+                    None => process_sleep(
+                        &mut task_list,
+                        task_ids,
+                        duration.unwrap_or_else(chrono::Duration::zero),
+                        wake_at_hour.or(config.wake_at_hour),
+                        &display,
+                    ),
+                };
+                match result {
                     Ok(c) => {
                         if args.verbose > 0 {
                             println!("{} task(s) suspended", c)
@@ -221,8 +1199,32 @@ pub fn run(arg_overrides: Option<Arguments>) -> Result<(), Box<dyn Error>> {
                     Err(e) => eprintln!("error in processing : {}", e),
                 }
             }
-            Command::Del { task_ids } => {
-                match process_del(&mut task_list, task_ids.unwrap_or_default()) {
+            Command::Schedule { task_id, when } => {
+                let task_id = choose_ids(
+                    &task_list,
+                    task_list.resolve_ordinals(vec![task_id]),
+                    args.interactive,
+                )?
+                .remove(0);
+                match process_schedule(&mut task_list, task_id, when, &display) {
+                    Ok(c) => {
+                        if args.verbose > 0 {
+                            println!("{} task(s) scheduled", c)
+                        }
+                    }
+                    Err(e) => eprintln!("error in processing : {}", e),
+                }
+            }
+            Command::Del { task_ids, select } => {
+                let task_ids = choose_ids(
+                    &task_list,
+                    select_task_ids(&task_list, task_ids, select, None)?,
+                    args.interactive,
+                )?;
+                if args.strict {
+                    check_ids_resolve(&task_list, &task_ids)?;
+                }
+                match process_del(&mut task_list, task_ids) {
                     Ok(c) => {
                         if args.verbose > 0 {
                             println!("{} task(s) removed", c)
@@ -234,16 +1236,34 @@ pub fn run(arg_overrides: Option<Arguments>) -> Result<(), Box<dyn Error>> {
             Command::Edit {
                 task_ids,
                 details_only,
-            } => match process_edit(&mut task_list, task_ids.unwrap_or_default(), details_only) {
-                Ok(c) => {
-                    if args.verbose > 0 {
-                        println!("{} task(s) updated", c)
-                    }
+                batch,
+                owner,
+                priority,
+                estimate,
+                mut set,
+            } => {
+                if let Some(owner) = owner {
+                    set.push(format!("owner={}", owner));
                 }
-                Err(e) => eprintln!("error in processing : {}", e),
-            },
-            Command::Block { task_ids } => {
-                match process_block_on(&mut task_list, task_ids.unwrap_or_default()) {
+                if let Some(priority) = priority {
+                    set.push(format!("priority={}", priority));
+                }
+                if let Some(estimate) = estimate {
+                    set.push(format!("estimate={}s", estimate.num_seconds()));
+                }
+                let task_ids = choose_ids(
+                    &task_list,
+                    task_list.resolve_ordinals(task_ids.unwrap_or_default()),
+                    args.interactive,
+                )?;
+                let result = if !set.is_empty() {
+                    process_set_fields(&mut task_list, task_ids, set)
+                } else if batch {
+                    task_list.edit_tasks_batch(task_ids).map_err(Into::into)
+                } else {
+                    process_edit(&mut task_list, task_ids, details_only)
+                };
+                match result {
                     Ok(c) => {
                         if args.verbose > 0 {
                             println!("{} task(s) updated", c)
@@ -252,8 +1272,13 @@ pub fn run(arg_overrides: Option<Arguments>) -> Result<(), Box<dyn Error>> {
                     Err(e) => eprintln!("error in processing : {}", e),
                 }
             }
-            Command::Complete { task_ids } => {
-                match process_complete(&mut task_list, task_ids.unwrap_or_default()) {
+            Command::Bump { task_ids, delta } => {
+                let task_ids = choose_ids(
+                    &task_list,
+                    task_list.resolve_ordinals(task_ids),
+                    args.interactive,
+                )?;
+                match process_bump(&mut task_list, task_ids, delta) {
                     Ok(c) => {
                         if args.verbose > 0 {
                             println!("{} task(s) updated", c)
@@ -262,122 +1287,607 @@ pub fn run(arg_overrides: Option<Arguments>) -> Result<(), Box<dyn Error>> {
                     Err(e) => eprintln!("error in processing : {}", e),
                 }
             }
-        }
-    } else {
-        // No subcommand, so just list the active task
-        match process_show(&mut task_list, args.verbose, vec![]) {
-            Ok(_) => (),
-            Err(e) => eprintln!("error in processing : {}", e),
-        }
-    }
-
-    Ok(())
-}
-
-fn process_show(
-    task_list: &mut tasklist::TaskList,
-    verbosity: u8,
-    task_ids: Vec<String>,
-) -> Result<usize, Box<dyn Error>> {
-    let mut processed_task_count = 0;
-    if task_ids.is_empty() {
-        let mut tasks = task_list.tasks.clone();
-        tasks.retain(|task| task.status == TaskStatus::Active);
-
-        if tasks.is_empty() {
-            // Activate the next backlog task
-            process_start(task_list, vec![])?;
-
-            // Check to see if there are any active tasks now
-            tasks.clone_from(&task_list.tasks);
-            tasks.retain(|task| task.status == TaskStatus::Active);
-            if tasks.is_empty() {
-                return Ok(0);
-            }
-        }
-
-        let mut tasks = tasks.into_sorted_vec();
-        let task = tasks.remove(0);
-        if verbosity > 0 {
-            print_task_detailed(&task);
-        } else {
-            print_task_oneline(&task, true);
-        }
-        processed_task_count = 1;
-    } else {
-        // Edit selected tasks
-        for id in task_ids {
-            if let Some(task) = task_list.copy_task(id.clone()) {
-                if verbosity > 0 {
-                    print_task_detailed(&task);
-                } else {
-                    print_task_oneline(&task, true);
+            Command::Reset { task_ids } => {
+                let task_ids = choose_ids(
+                    &task_list,
+                    task_list.resolve_ordinals(task_ids),
+                    args.interactive,
+                )?;
+                match process_reset(&mut task_list, task_ids) {
+                    Ok(c) => {
+                        if args.verbose > 0 {
+                            println!("{} task(s) reset", c)
+                        }
+                    }
+                    Err(e) => eprintln!("error in processing : {}", e),
                 }
-            } else {
-                println!("task {} not found", id);
             }
-        }
-    }
-    Ok(processed_task_count)
+            Command::Block { task_ids } => {
+                let task_ids = choose_ids(
+                    &task_list,
+                    task_list.resolve_ordinals(task_ids.unwrap_or_default()),
+                    args.interactive,
+                )?;
+                match process_block_on(&mut task_list, task_ids) {
+                    Ok(c) => {
+                        if args.verbose > 0 {
+                            println!("{} task(s) updated", c)
+                        }
+                    }
+                    Err(e) => eprintln!("error in processing : {}", e),
+                }
+            }
+            Command::Complete {
+                task_ids,
+                count,
+                then_next,
+                delete,
+                at,
+                select,
+            } => {
+                let task_ids = choose_ids(
+                    &task_list,
+                    select_task_ids(&task_list, task_ids, select, None)?,
+                    args.interactive,
+                )?;
+                if args.strict {
+                    check_ids_resolve(&task_list, &task_ids)?;
+                }
+                match process_complete(
+                    &mut task_list,
+                    task_ids,
+                    count.unwrap_or(1),
+                    at.unwrap_or_else(Local::now),
+                    delete,
+                ) {
+                    Ok(c) => {
+                        if args.verbose > 0 {
+                            println!("{} task(s) updated", c)
+                        }
+                    }
+                    Err(e) => eprintln!("error in processing : {}", e),
+                }
+                if then_next {
+                    match process_start_next(&mut task_list, &display) {
+                        Ok(c) => {
+                            if args.verbose > 0 {
+                                println!("{} task(s) started", c)
+                            }
+                        }
+                        Err(e) => eprintln!("error in processing : {}", e),
+                    }
+                }
+            }
+            Command::History { task_id } => {
+                if let Err(e) = process_history(&task_list, task_id, &display) {
+                    eprintln!("error in processing : {}", e)
+                }
+            }
+            Command::Stats { completed_since } => {
+                let mut out = String::new();
+                match print_stats(&mut out, &task_list, completed_since, &Local::now()) {
+                    Ok(()) => print!("{}", out),
+                    Err(e) => eprintln!("error in processing : {}", e),
+                }
+            }
+            Command::Export {
+                format,
+                since,
+                fields,
+            } => match export_tasks(
+                &task_list,
+                &Filter {
+                    updated_after: since,
+                    ..Default::default()
+                },
+                format,
+                &fields,
+            ) {
+                Ok(exported) => print!("{}", exported),
+                Err(e) => eprintln!("error in processing : {}", e),
+            },
+            Command::Rebalance => match process_rebalance(&mut task_list) {
+                Ok(c) => println!("{} task(s) changed priority", c),
+                Err(e) => eprintln!("error in processing : {}", e),
+            },
+            Command::RenameCategory { from, to } => {
+                println!(
+                    "{} task(s) changed category",
+                    task_list.rename_category(&from, &to)
+                )
+            }
+            Command::RenameTag { from, to } => {
+                println!("{} task(s) retagged", task_list.rename_tag(&from, &to))
+            }
+            Command::Restore { path } => match process_restore(&mut task_list, path) {
+                Ok(c) => println!("{} task(s) restored", c),
+                Err(e) => eprintln!("error in processing : {}", e),
+            },
+            Command::Template { save, from } => {
+                let templates_dir = shellexpand::env(&args.templates)?;
+                match (save, from) {
+                    (Some(name), Some(id)) => {
+                        let id = choose_ids(&task_list, vec![id], args.interactive)?.remove(0);
+                        process_save_template(&task_list, &templates_dir, &name, &id)?;
+                        println!("saved template '{}'", name);
+                    }
+                    _ => {
+                        for name in list_templates(&templates_dir)? {
+                            println!("{}", name);
+                        }
+                    }
+                }
+            }
+            Command::Sweep { idle } => {
+                match process_sweep(&mut task_list, idle, &Local::now(), &display) {
+                    Ok(c) => println!("{} idle task(s) stopped", c),
+                    Err(e) => eprintln!("error in processing : {}", e),
+                }
+            }
+            Command::Dedupe { yes } => match process_dedupe(
+                &mut task_list,
+                yes,
+                &mut std::io::stdin().lock(),
+                &mut std::io::stdout(),
+            ) {
+                Ok(c) => println!("{} duplicate task(s) merged", c),
+                Err(e) => eprintln!("error in processing : {}", e),
+            },
+        }
+    } else {
+        // No subcommand, so just list the active task
+        match process_default(&mut task_list, args.verbose, !args.no_auto_start, &display) {
+            Ok(_) => (),
+            Err(e) => eprintln!("error in processing : {}", e),
+        }
+    }
+
+    Ok(())
+}
+
+/// Show the active task, as run does when given no subcommand.  An empty
+/// database would otherwise print nothing at all, so say how to add a task.
+fn process_default(
+    task_list: &mut tasklist::TaskList,
+    verbosity: u8,
+    auto_start: bool,
+    display: &DisplayOptions,
+) -> Result<usize, Box<dyn Error>> {
+    let count = process_show(
+        task_list,
+        verbosity,
+        vec![],
+        None,
+        auto_start,
+        false,
+        display,
+    )?;
+    if count == 0 && task_list.tasks.is_empty() {
+        task_list.note("no tasks to show; add one with 'ztask add <summary>'".to_string());
+    }
+    Ok(count)
+}
+
+fn process_show(
+    task_list: &mut tasklist::TaskList,
+    verbosity: u8,
+    task_ids: Vec<String>,
+    status: Option<TaskStatus>,
+    auto_start: bool,
+    compact: bool,
+    display: &DisplayOptions,
+) -> Result<usize, Box<dyn Error>> {
+    let mut out = String::new();
+    let mut processed_task_count = 0;
+    let print_task = |out: &mut String, task: &Task, detailed: bool| {
+        if compact {
+            print_task_compact(out, task, display)
+        } else if detailed {
+            print_task_detailed(out, task, display)
+        } else {
+            print_task_oneline(out, task, true, display)
+        }
+    };
+    if let (true, Some(status)) = (task_ids.is_empty(), status) {
+        // Show every task with the requested status
+        for task in section_tasks(task_list, &Filter::default().with_status(status)) {
+            print_task(&mut out, &task, true)?;
+            processed_task_count += 1;
+        }
+    } else if task_ids.is_empty() {
+        let active = Filter::default().with_status(TaskStatus::Active);
+        let mut tasks = task_list.filtered(&active);
+
+        if tasks.is_empty() && !auto_start {
+            return Ok(0);
+        }
+        if tasks.is_empty() {
+            // Activate the next backlog task
+            process_start(task_list, vec![], Local::now())?;
+
+            // Check to see if there are any active tasks now
+            tasks = task_list.filtered(&active);
+            if tasks.is_empty() {
+                return Ok(0);
+            }
+        }
+
+        let task = tasks.remove(0);
+        print_task(&mut out, &task, verbosity > 0)?;
+        processed_task_count = 1;
+    } else {
+        // Edit selected tasks
+        for id in task_ids {
+            if let Some(task) = task_list.copy_task(id.clone()) {
+                print_task(&mut out, &task, verbosity > 0)?;
+            } else {
+                writeln!(out, "task {} not found", id)?;
+            }
+        }
+    }
+    print!("{}", out);
+    Ok(processed_task_count)
+}
+
+/// Options selecting which tasks the list command shows, and how
+struct ListOptions {
+    filter: Filter,
+    group_by: GroupBy,
+    numbered: bool,
+    tree: bool,
+    /// Print only task ids, for feeding into other commands
+    only_ids: bool,
+    /// List open tasks by due date instead of by status
+    due_soon: bool,
+    /// List only active tasks and tasks due or scheduled today
+    today: bool,
 }
 
 fn process_list(
     task_list: &mut tasklist::TaskList,
     verbosity: u8,
     show_all: bool,
+    options: &ListOptions,
+    display: &DisplayOptions,
+    pager: PagerMode,
 ) -> Result<usize, Box<dyn Error>> {
-    if show_all {
-        print_categorized_task_list(task_list, verbosity);
+    let filter = &options.filter;
+    let mut out = String::new();
+    let listed_count = if show_all && options.only_ids {
+        // Ids are meant for pipelines, so they are never paged
+        let ids = listing_order(task_list, filter);
+        let id_count = ids.len();
+        print_ids(&mut out, ids)?;
+        print!("{}", out);
+        return Ok(id_count);
+    } else if show_all && options.due_soon {
+        print_due_soon(&mut out, task_list, filter, &Local::now(), display)?
+    } else if show_all && options.today {
+        print_today(&mut out, task_list, filter, &Local::now(), display)?
+    } else if show_all && options.tree {
+        print_task_tree(&mut out, task_list, filter, display)?
+    } else if show_all && options.group_by == GroupBy::Category {
+        print_task_list_by_category(&mut out, task_list, filter, display)?
+    } else if show_all {
+        let ordinals = if options.numbered {
+            let ordinals = listing_order(task_list, filter);
+            if let Err(e) = task_list.save_ordinals(&ordinals) {
+                eprintln!("Failed to save task numbers: {}", e);
+            }
+            ordinals
+        } else {
+            vec![]
+        };
+        print_categorized_task_list(&mut out, task_list, verbosity, filter, &ordinals, display)?
     } else {
-        let mut tasks = task_list.tasks.clone();
-        tasks.retain(|task| task.status == TaskStatus::Active);
+        let mut tasks = task_list.filtered(&filter.with_status(TaskStatus::Active));
 
         if tasks.is_empty() {
             return Ok(0);
         }
 
-        let mut tasks = tasks.into_sorted_vec();
         let task = tasks.remove(0);
 
         if verbosity > 0 {
-            print_task_detailed(&task);
+            print_task_detailed(&mut out, &task, display)?;
         } else {
-            print_task_oneline(&task, true);
+            print_task_oneline(&mut out, &task, true, display)?;
+        }
+        1
+    };
+    emit_output(&out, pager)?;
+    Ok(listed_count)
+}
+
+/// Print rendered output, sending it through $PAGER (default "less -R") when
+/// requested and stdout is a terminal.
+fn emit_output(output: &str, pager: PagerMode) -> Result<(), Box<dyn Error>> {
+    let use_pager = std::io::stdout().is_terminal()
+        && match pager {
+            PagerMode::Always => true,
+            PagerMode::Never => false,
+            PagerMode::Auto => terminal_rows().is_some_and(|rows| output.lines().count() > rows),
+        };
+    if use_pager {
+        let pager = std::env::var("PAGER").unwrap_or_else(|_| "less -R".to_string());
+        if page_output(output, &pager).is_ok() {
+            return Ok(());
         }
     }
-    Ok(task_list.tasks.len())
+    print!("{}", output);
+    Ok(())
+}
+
+/// Run the pager command with the output on its stdin and wait for it to
+/// exit.  Only failing to start the pager is an error.  Once it is running,
+/// the output is the pager's to show, and a failed write, such as a broken
+/// pipe after the user quits the pager early, must not print it again.
+fn page_output(output: &str, pager: &str) -> std::io::Result<()> {
+    let mut words = pager.split_whitespace();
+    let program = words.next().unwrap_or("less");
+    let mut child = std::process::Command::new(program)
+        .args(words)
+        .stdin(Stdio::piped())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = stdin.write_all(output.as_bytes());
+    }
+    let _ = child.wait();
+    Ok(())
+}
+
+/// Number of columns in the terminal, if it can be determined.  $COLUMNS
+/// takes precedence over the size the terminal reports.
+fn terminal_columns() -> Option<usize> {
+    if let Some(columns) = std::env::var("COLUMNS").ok().and_then(|c| c.parse().ok()) {
+        return Some(columns);
+    }
+    let (Width(columns), _) = terminal_size()?;
+    Some(columns as usize)
 }
 
-/// Print all tasks
-fn print_categorized_task_list(task_list: &tasklist::TaskList, verbosity: u8) {
-    show_list("Active Tasks", TaskStatus::Active, task_list, verbosity);
-    show_list("Backlog Tasks", TaskStatus::Backlog, task_list, verbosity);
-    show_list("Blocked Tasks", TaskStatus::Blocked, task_list, verbosity);
-    show_list("Sleeping Tasks", TaskStatus::Sleeping, task_list, verbosity);
-    show_list(
-        "Completed Tasks",
-        TaskStatus::Completed,
+/// Number of rows in the terminal, if it can be determined.  $LINES takes
+/// precedence over the size the terminal reports.
+fn terminal_rows() -> Option<usize> {
+    if let Some(rows) = std::env::var("LINES").ok().and_then(|l| l.parse().ok()) {
+        return Some(rows);
+    }
+    let (_, Height(rows)) = terminal_size()?;
+    Some(rows as usize)
+}
+
+/// Serialize the tasks that show would display: the given tasks, else those
+/// with the given status, else the active tasks.  A single id gives a single
+/// task, and anything else gives a list of them.
+fn serialize_shown_tasks(
+    task_list: &tasklist::TaskList,
+    task_ids: Vec<String>,
+    status: Option<TaskStatus>,
+    format: ShowFormat,
+) -> Result<String, Box<dyn Error>> {
+    let single = task_ids.len() == 1;
+    let tasks = if !task_ids.is_empty() {
+        // A lookup failure is returned rather than printed, so it goes to
+        // stderr and never mixes into the JSON or YAML on stdout
+        let mut tasks = vec![];
+        for id in task_ids {
+            tasks.push(task_list.find_unique(&id)?.clone());
+        }
+        tasks
+    } else {
+        let status = status.unwrap_or(TaskStatus::Active);
+        section_tasks(task_list, &Filter::default().with_status(status))
+    };
+    Ok(match (format, single) {
+        (ShowFormat::Json, true) => serde_json::to_string_pretty(&tasks[0])? + "\n",
+        (ShowFormat::Json, false) => serde_json::to_string_pretty(&tasks)? + "\n",
+        (_, true) => serde_yaml::to_string(&tasks[0])?,
+        (_, false) => serde_yaml::to_string(&tasks)?,
+    })
+}
+
+/// Return the tasks whose summary, or details if include_details is set,
+/// contain the query, in sorted order
+fn find_matching_tasks(
+    task_list: &tasklist::TaskList,
+    query: &str,
+    ignore_case: bool,
+    include_details: bool,
+) -> Vec<Task> {
+    task_list.tasks.sorted_matching(|task| {
+        let text = match include_details {
+            true => format!("{}\n{}", task.summary, task.details),
+            false => task.summary.clone(),
+        };
+        let matches = if ignore_case {
+            search_case_insensitive(query, &text)
+        } else {
+            search(query, &text)
+        };
+        !matches.is_empty()
+    })
+}
+
+/// Options controlling how the search command matches and prints tasks
+#[derive(Default)]
+struct SearchOptions {
+    ignore_case: bool,
+    /// Search the details as well as the summaries
+    include_details: bool,
+    /// Only print the number of matching tasks
+    count_only: bool,
+    /// Print only the full id of each matching task
+    only_ids: bool,
+    /// Print at most this many matches
+    limit: Option<usize>,
+}
+
+fn process_search(
+    task_list: &tasklist::TaskList,
+    query: &str,
+    options: &SearchOptions,
+    display: &DisplayOptions,
+) -> Result<usize, Box<dyn Error>> {
+    let tasks = find_matching_tasks(
         task_list,
-        verbosity,
+        query,
+        options.ignore_case,
+        options.include_details,
     );
+    if options.count_only {
+        println!("{}", tasks.len());
+    } else if options.only_ids {
+        let mut out = String::new();
+        print_ids(&mut out, tasks.iter().map(|task| task.id.clone()))?;
+        print!("{}", out);
+    } else {
+        let mut out = String::new();
+        print_matches(&mut out, &tasks, options.limit, display)?;
+        print!("{}", out);
+    }
+    Ok(tasks.len())
+}
+
+/// Print the matching tasks, stopping after limit tasks if one is given and
+/// noting how many more matched
+fn print_matches(
+    out: &mut String,
+    tasks: &[Task],
+    limit: Option<usize>,
+    display: &DisplayOptions,
+) -> fmt::Result {
+    let shown = limit.unwrap_or(tasks.len()).min(tasks.len());
+    for task in &tasks[..shown] {
+        print_task_oneline(out, task, true, display)?;
+    }
+    if shown < tasks.len() {
+        writeln!(out, "… {} more matches", tasks.len() - shown)?;
+    }
+    Ok(())
+}
+
+/// Print each id in full on a line of its own, without color
+fn print_ids(out: &mut String, ids: impl IntoIterator<Item = String>) -> fmt::Result {
+    for id in ids {
+        writeln!(out, "{}", id)?;
+    }
+    Ok(())
+}
+
+/// Default owner for new tasks, taken from $USER when ZTASK_TRACK_OWNER=1
+fn default_owner() -> Option<String> {
+    match std::env::var("ZTASK_TRACK_OWNER") {
+        Ok(track) if track == "1" => std::env::var("USER").ok(),
+        _ => None,
+    }
+}
+
+/// Sections of the categorized task list, in the order they are printed
+const LIST_SECTIONS: [(&str, TaskStatus); 6] = [
+    ("Active Tasks", TaskStatus::Active),
+    ("Backlog Tasks", TaskStatus::Backlog),
+    ("Blocked Tasks", TaskStatus::Blocked),
+    ("Sleeping Tasks", TaskStatus::Sleeping),
+    ("Scheduled Tasks", TaskStatus::Scheduled),
+    ("Completed Tasks", TaskStatus::Completed),
+];
+
+/// Return the tasks matching the filter, in the order they are listed
+fn section_tasks(task_list: &tasklist::TaskList, filter: &Filter) -> Vec<Task> {
+    let mut tasks = task_list.filtered(filter);
+    match filter.status {
+        Some(TaskStatus::Sleeping) => sort_by_wake_time(&mut tasks),
+        Some(TaskStatus::Scheduled) => {
+            tasks.sort_by_key(|task| (task.scheduled_at.is_none(), task.scheduled_at))
+        }
+        Some(TaskStatus::Completed) => sort_by_completion_time(&mut tasks),
+        _ => (),
+    }
+    tasks
+}
+
+/// Return the ids of all tasks in the order the categorized list shows them
+fn listing_order(task_list: &tasklist::TaskList, filter: &Filter) -> Vec<String> {
+    LIST_SECTIONS
+        .iter()
+        .filter(|(_, status)| filter.status.as_ref().is_none_or(|s| s == status))
+        .flat_map(|(_, status)| section_tasks(task_list, &filter.with_status(status.clone())))
+        .map(|task| task.id)
+        .collect()
+}
+
+/// Print all tasks, headed by the task currently being worked on, if any.
+/// Tasks whose id appears in `ordinals` are prefixed with their position in
+/// it, counting from 1.  Returns the number of tasks listed.
+fn print_categorized_task_list(
+    out: &mut String,
+    task_list: &tasklist::TaskList,
+    verbosity: u8,
+    filter: &Filter,
+    ordinals: &[String],
+    display: &DisplayOptions,
+) -> Result<usize, fmt::Error> {
+    if task_list.tasks.is_empty() {
+        writeln!(out, "No tasks. Add one with 'ztask add <summary>'.")?;
+        return Ok(0);
+    }
+    if filter
+        .status
+        .as_ref()
+        .is_none_or(|s| s == &TaskStatus::Active)
+    {
+        if let Some(task) = task_list
+            .filtered(&filter.with_status(TaskStatus::Active))
+            .first()
+        {
+            let since = task.last_started_at.unwrap_or(task.created_at);
+            writeln!(
+                out,
+                "{} {} ({})\n",
+                "▶ Currently working on:".bright_green().bold(),
+                display.format_summary(&task.summary),
+                describe_age(&since, &Local::now())
+            )?;
+        }
+    }
+    let mut listed_count = 0;
+    for (heading, status) in &LIST_SECTIONS {
+        if filter.status.as_ref().is_some_and(|s| s != status) {
+            continue;
+        }
+        listed_count += show_list(
+            out, heading, status, task_list, verbosity, filter, ordinals, display,
+        )?;
+    }
 
+    #[allow(clippy::too_many_arguments)]
     fn show_list(
+        out: &mut String,
         heading: &str,
-        status: TaskStatus,
+        status: &TaskStatus,
         task_list: &tasklist::TaskList,
         _verbosity: u8,
-    ) {
-        let mut tasks = task_list.tasks.clone();
-        tasks.retain(|task| task.status == status);
-        let mut tasks = tasks.into_sorted_vec();
+        filter: &Filter,
+        ordinals: &[String],
+        display: &DisplayOptions,
+    ) -> Result<usize, fmt::Error> {
+        let mut tasks = section_tasks(task_list, &filter.with_status(status.clone()));
+        let listed_count = tasks.len();
+        let write_ordinal = |out: &mut String, task: &Task| -> fmt::Result {
+            match ordinals.iter().position(|id| id == &task.id) {
+                Some(index) => write!(out, "{:>3}", index + 1),
+                None => Ok(()),
+            }
+        };
 
         if !tasks.is_empty() {
-            println!("{}:", heading.bright_white().underline());
+            writeln!(out, "{}:", heading.bright_white().underline())?;
 
-            if status == TaskStatus::Active {
+            if status == &TaskStatus::Active {
                 // Print the first active task normally
                 let task = tasks.remove(0);
-                print_task_oneline(&task, false);
+                write_ordinal(out, &task)?;
+                print_task_oneline(out, &task, false, display)?;
             }
         }
         let fn_format = match status {
@@ -385,28 +1895,319 @@ fn print_categorized_task_list(task_list: &tasklist::TaskList, verbosity: u8) {
             TaskStatus::Backlog => |s: &str| s.white(),
             TaskStatus::Blocked => |s: &str| s.bright_black(),
             TaskStatus::Sleeping => |s: &str| s.bright_black(),
+            TaskStatus::Scheduled => |s: &str| s.cyan(),
             TaskStatus::Completed => |s: &str| s.bright_black().strikethrough(),
         };
 
         if !tasks.is_empty() {
+            let now = Local::now();
             for task in tasks {
-                print_task_oneline_with_format_override(&task, fn_format);
-                // print_task_oneline(&task, true);
+                write_ordinal(out, &task)?;
+                let fn_format = if display.is_stale(&task, &now) {
+                    |s: &str| s.yellow().dimmed()
+                } else {
+                    fn_format
+                };
+                print_task_oneline_with_format_override(out, &task, fn_format, display)?;
+                // print_task_oneline(out, &task, true, display)?;
             }
         }
+        Ok(listed_count)
+    }
+
+    Ok(listed_count)
+}
+
+/// Print open (not completed) tasks grouped under a heading for each
+/// category.  Returns the number of tasks listed.
+fn print_task_list_by_category(
+    out: &mut String,
+    task_list: &tasklist::TaskList,
+    filter: &Filter,
+    display: &DisplayOptions,
+) -> Result<usize, fmt::Error> {
+    let mut categories: BTreeMap<String, Vec<Task>> = BTreeMap::new();
+    for task in task_list.filtered(filter) {
+        if task.status != TaskStatus::Completed {
+            categories
+                .entry(normalize_label(&task.category))
+                .or_default()
+                .push(task);
+        }
+    }
+
+    let mut listed_count = 0;
+    for (category, tasks) in categories {
+        writeln!(out, "{}:", category.bright_white().underline())?;
+        for task in tasks {
+            print_task_oneline(out, &task, true, display)?;
+            listed_count += 1;
+        }
+    }
+    Ok(listed_count)
+}
+
+/// Print the open tasks that have a due date, soonest first, each with how
+/// long until it is due or how overdue it is.  Returns the number of tasks
+/// listed.
+fn print_due_soon(
+    out: &mut String,
+    task_list: &tasklist::TaskList,
+    filter: &Filter,
+    now: &DateTime<Local>,
+    display: &DisplayOptions,
+) -> Result<usize, fmt::Error> {
+    let mut tasks: Vec<Task> = task_list
+        .filtered(filter)
+        .into_iter()
+        .filter(|task| task.status != TaskStatus::Completed && task.due_at.is_some())
+        .collect();
+    if tasks.is_empty() {
+        writeln!(out, "No open tasks have a due date.")?;
+        return Ok(0);
+    }
+    tasks.sort_by(|a, b| a.due_at.cmp(&b.due_at).then_with(|| a.id.cmp(&b.id)));
+    for task in &tasks {
+        // Only tasks with a due date were kept, so unwrap is safe
+        let due_at = task.due_at.unwrap();
+        let due = describe_wake_at(&due_at, now, display);
+        let due = if due_at <= *now {
+            due.bright_red()
+        } else {
+            due.white()
+        };
+        writeln!(
+            out,
+            "  {}  {}  {}  {}  {}",
+            short_id(&task.id),
+            task.priority.to_string().bright_black(),
+            format!("{:9}", task.status).bright_black(),
+            due,
+            display.tint(
+                &task.category,
+                display.format_summary(&task.summary).white()
+            )
+        )?;
+    }
+    Ok(tasks.len())
+}
+
+/// Print the active tasks and the open tasks due or scheduled on the local
+/// date of now, in listing order.  Returns the number of tasks listed.
+fn print_today(
+    out: &mut String,
+    task_list: &tasklist::TaskList,
+    filter: &Filter,
+    now: &DateTime<Local>,
+    display: &DisplayOptions,
+) -> Result<usize, fmt::Error> {
+    let today = now.date_naive();
+    let on_today = |at: Option<DateTime<Local>>| at.is_some_and(|at| at.date_naive() == today);
+    let tasks: Vec<Task> = task_list
+        .filtered(filter)
+        .into_iter()
+        .filter(|task| {
+            task.status == TaskStatus::Active
+                || (task.status != TaskStatus::Completed
+                    && (on_today(task.due_at) || on_today(task.scheduled_at)))
+        })
+        .collect();
+    if tasks.is_empty() {
+        writeln!(out, "Nothing is active, due or scheduled today.")?;
+        return Ok(0);
+    }
+    for task in &tasks {
+        print_task_oneline(out, task, true, display)?;
+    }
+    Ok(tasks.len())
+}
+
+/// Print open tasks as a tree.  Each task is followed by the tasks blocked
+/// on it, indented one level deeper, so a task blocked on several others
+/// appears under each of them.  Returns the number of distinct tasks listed.
+fn print_task_tree(
+    out: &mut String,
+    task_list: &tasklist::TaskList,
+    filter: &Filter,
+    display: &DisplayOptions,
+) -> Result<usize, fmt::Error> {
+    let tasks: Vec<Task> = task_list
+        .filtered(filter)
+        .into_iter()
+        .filter(|task| task.status != TaskStatus::Completed)
+        .collect();
+    let open_ids: BTreeSet<&str> = tasks.iter().map(|task| task.id.as_str()).collect();
+    let mut dependents: BTreeMap<&str, Vec<&Task>> = BTreeMap::new();
+    for task in &tasks {
+        for blocker in &task.blocked_by {
+            dependents.entry(blocker.as_str()).or_default().push(task);
+        }
+    }
+
+    fn print_subtree<'a>(
+        out: &mut String,
+        task: &'a Task,
+        depth: usize,
+        dependents: &BTreeMap<&str, Vec<&'a Task>>,
+        ancestors: &mut Vec<&'a str>,
+        printed: &mut BTreeSet<&'a str>,
+        display: &DisplayOptions,
+    ) -> fmt::Result {
+        write!(out, "{:indent$}", "", indent = depth * 2)?;
+        if ancestors.contains(&task.id.as_str()) {
+            // Blocked on itself through a cycle, so stop here
+            writeln!(out, "  {} (cycle)", short_id(&task.id))?;
+            return Ok(());
+        }
+        print_task_oneline(out, task, true, display)?;
+        printed.insert(&task.id);
+        ancestors.push(&task.id);
+        for dependent in dependents.get(task.id.as_str()).into_iter().flatten() {
+            print_subtree(
+                out,
+                dependent,
+                depth + 1,
+                dependents,
+                ancestors,
+                printed,
+                display,
+            )?;
+        }
+        ancestors.pop();
+        Ok(())
+    }
+
+    // Start from tasks not blocked on any open task, then pick up anything
+    // only reachable through a cycle
+    let (roots, rest): (Vec<&Task>, Vec<&Task>) = tasks.iter().partition(|task| {
+        !task
+            .blocked_by
+            .iter()
+            .any(|id| open_ids.contains(id.as_str()))
+    });
+    let mut printed = BTreeSet::new();
+    for task in roots.into_iter().chain(rest) {
+        if !printed.contains(task.id.as_str()) {
+            print_subtree(
+                out,
+                task,
+                0,
+                &dependents,
+                &mut vec![],
+                &mut printed,
+                display,
+            )?;
+        }
+    }
+    Ok(printed.len())
+}
+
+/// Sort tasks so the one waking soonest comes first.  Tasks without a wake
+/// time go last, keeping their existing relative order.
+fn sort_by_wake_time(tasks: &mut [Task]) {
+    tasks.sort_by_key(|task| (task.wake_at.is_none(), task.wake_at));
+}
+
+/// Sort tasks so the most recently completed comes first.  Tasks completed
+/// before completion times were recorded fall back to their creation time.
+fn sort_by_completion_time(tasks: &mut [Task]) {
+    tasks.sort_by_key(|task| std::cmp::Reverse(task.completed_at.unwrap_or(task.created_at)));
+}
+
+/// Beyond this many days, wake times are shown as a date and a rough
+/// number of months rather than an exact countdown
+const WAKE_COUNTDOWN_MAX_DAYS: i64 = 90;
+
+/// Render a number of seconds as a compact duration such as "2d 3h 15m"
+fn humanize_duration(total_seconds: i64) -> String {
+    let total_seconds = total_seconds.abs();
+    let days = total_seconds / (60 * 60 * 24);
+    let hours = (total_seconds / (60 * 60)) % 24;
+    let minutes = (total_seconds / 60) % 60;
+    let seconds = total_seconds % 60;
+
+    let mut duration_fragments: Vec<String> = vec![];
+    if days > 0 {
+        duration_fragments.push(format!("{}d", days));
+    }
+    if hours > 0 {
+        duration_fragments.push(format!("{}h", hours));
+    }
+    if minutes > 0 {
+        duration_fragments.push(format!("{}m", minutes));
+    }
+    if seconds > 0 {
+        duration_fragments.push(format!("{}s", seconds));
+    }
+    duration_fragments.join(" ")
+}
+
+/// Describe how long ago a time was, to the minute
+fn describe_age(since: &DateTime<Local>, now: &DateTime<Local>) -> String {
+    match (*now - *since).num_minutes() {
+        minutes if minutes < 1 => "just now".to_string(),
+        minutes => humanize_duration(minutes * 60),
+    }
+}
+
+/// Describe when a sleeping task wakes, relative to now.  Far off (or long
+/// overdue) wake times show just the date and a rough count of months.
+fn describe_wake_at(
+    wake_at: &DateTime<Local>,
+    now: &DateTime<Local>,
+    display: &DisplayOptions,
+) -> String {
+    let total_seconds = (*wake_at - *now).num_seconds();
+    let days = total_seconds.abs() / (60 * 60 * 24);
+    let overdue = if total_seconds <= 0 {
+        "overdue by "
+    } else {
+        ""
+    };
+
+    if days > WAKE_COUNTDOWN_MAX_DAYS {
+        let months = days / 30;
+        if total_seconds <= 0 {
+            format!(
+                "{} (overdue by {} months)",
+                display.format_date(wake_at),
+                months
+            )
+        } else {
+            format!("{} (in {} months)", display.format_date(wake_at), months)
+        }
+    } else {
+        format!(
+            "{} ({}{})",
+            display.format_datetime(wake_at),
+            overdue,
+            humanize_duration(total_seconds)
+        )
     }
 }
 
 // fn red(s: &str) -> ColoredString { s.red() }
 
-fn print_task_oneline_with_format_override(task: &Task, set_color: fn(&str) -> ColoredString) {
-    let id = set_color(&task.id[..9]);
+fn print_task_oneline_with_format_override(
+    out: &mut String,
+    task: &Task,
+    set_color: fn(&str) -> ColoredString,
+    display: &DisplayOptions,
+) -> fmt::Result {
+    let id = set_color(&display.pad_id(&task.id));
     let priority = set_color(&task.priority.to_string());
 
-    print!("  {}  {}", id, priority);
-    print!("  {}", set_color(&task.created_at.format("%F").to_string()));
+    write!(out, "  {}  {}", id, priority)?;
+    write!(
+        out,
+        "  {}",
+        set_color(&display.format_date(&task.created_at))
+    )?;
 
-    let summary = set_color(&task.summary.to_string());
+    let summary = display.tint(
+        &task.category,
+        set_color(&display.format_summary(&task.summary)),
+    );
     let blocked = if task.blocked_by.is_empty() {
         set_color("")
     } else {
@@ -414,91 +2215,62 @@ fn print_task_oneline_with_format_override(task: &Task, set_color: fn(&str) -> C
             "[{}]",
             task.blocked_by
                 .iter()
-                .map(|s| &s[..9])
+                .map(|s| short_id(s))
                 .collect::<Vec<_>>()
                 .join(", ")
         ))
     };
-    let wake_at = if task.wake_at.is_none() {
-        set_color("")
-    } else {
-        let wake_at = task.wake_at.unwrap();
-        let time_delta = wake_at - Local::now();
-        let mut total_seconds = time_delta.num_seconds();
-        let mut duration_string = wake_at.format("%F %T (").to_string(); //String::new();
-
-        let _expired = if total_seconds <= 0 {
-            total_seconds = total_seconds.abs();
-            duration_string.push_str("overdue by ");
-            true
-        } else {
-            false
-        };
-
-        let days = total_seconds / (60 * 60 * 24);
-        let hours = (total_seconds / (60 * 60)) % 24;
-        let minutes = (total_seconds / 60) % 60;
-        let seconds = total_seconds % 60;
-
-        let mut duration_fragments: Vec<String> = vec![];
-
-        if days > 0 {
-            // duration_string.push_str(&format!("{}d ", days));
-            duration_fragments.push(format!("{}d", days));
-        }
-        if hours > 0 {
-            // duration_string.push_str(&format!("{}h ", hours));
-            duration_fragments.push(format!("{}h", hours));
-        }
-        if minutes > 0 {
-            // duration_string.push_str(&format!("{}m ", minutes));
-            duration_fragments.push(format!("{}m", minutes));
-        }
-        if seconds > 0 {
-            // duration_string.push_str(&format!("{}s ", seconds));
-            duration_fragments.push(format!("{}s", seconds));
-        }
-        // let s = duration_fragments.join(" ");
-        duration_string.push_str(&duration_fragments.join(" "));
-        duration_string.push(')');
-        set_color(&duration_string)
+    // Sleeping tasks show when they wake, scheduled tasks when they start
+    let wake_at = match task.wake_at.or(task.scheduled_at) {
+        Some(wake_at) => set_color(&describe_wake_at(&wake_at, &Local::now(), display)),
+        None => set_color(""),
     };
 
-    print!("  {}", summary);
+    write!(out, "  {}", summary)?;
     if !task.blocked_by.is_empty() {
-        print!("  {}", blocked);
+        write!(out, "  {}", blocked)?;
     }
-    if task.wake_at.is_some() {
-        print!("  {}", wake_at);
+    if task.wake_at.is_some() || task.scheduled_at.is_some() {
+        write!(out, "  {}", wake_at)?;
     }
-    println!();
+    if let Some(preview) = display.details_preview(&task.details) {
+        write!(out, "  {}", set_color(&preview))?;
+    }
+    writeln!(out)
 }
 
-fn print_task_oneline(task: &Task, show_status: bool) {
+fn print_task_oneline(
+    out: &mut String,
+    task: &Task,
+    show_status: bool,
+    display: &DisplayOptions,
+) -> fmt::Result {
     let show_date = true;
     // See specifiers at https://docs.rs/chrono/latest/chrono/format/strftime/index.html
     // "%F@%T%.3f" example: 2024-02-15@22:38:39.439
 
-    let id = &task.id[..9];
+    let id = display.pad_id(&task.id);
     let id = match task.status {
         TaskStatus::Active => id.bright_green(),
         TaskStatus::Backlog => id.white(),
         TaskStatus::Blocked => id.bright_red(),
         TaskStatus::Sleeping => id.bright_black(),
+        TaskStatus::Scheduled => id.cyan(),
         TaskStatus::Completed => id.bright_black(),
     };
     let priority = task.priority.to_string().bright_black();
 
-    print!("  {}", id);
-    print!("  {}", priority);
+    write!(out, "  {}", id)?;
+    write!(out, "  {}", priority)?;
     if show_status {
-        print!("  {}", task.status.to_string().bright_black());
+        write!(out, "  {}", display.pad_status(&task.status).bright_black())?;
     }
     if show_date {
-        print!(
+        write!(
+            out,
             "  {}",
-            task.created_at.format("%F").to_string().bright_black()
-        );
+            display.format_date(&task.created_at).bright_black()
+        )?;
     }
 
     // let summary = task.summary.to_string().bright_black();
@@ -509,150 +2281,818 @@ fn print_task_oneline(task: &Task, show_status: bool) {
             "[{}]",
             task.blocked_by
                 .iter()
-                .map(|s| &s[..9])
+                .map(|s| short_id(s))
                 .collect::<Vec<_>>()
                 .join(", ")
         )
         .bright_red()
     };
 
-    print!("  {}  {}", task.summary.to_string().white(), blocked);
-    println!();
+    write!(
+        out,
+        "  {}  {}",
+        display.tint(
+            &task.category,
+            display.format_summary(&task.summary).white()
+        ),
+        blocked
+    )?;
+    if let Some(preview) = display.details_preview(&task.details) {
+        write!(out, "  {}", preview.bright_black())?;
+    }
+    writeln!(out)
 }
 
-pub fn print_task_detailed(task: &Task) {
+pub fn print_task_detailed(out: &mut String, task: &Task, display: &DisplayOptions) -> fmt::Result {
     let blocked = if task.blocked_by.is_empty() {
         "".to_string().slate_blue()
     } else {
         task.blocked_by
             .iter()
-            .map(|s| &s[..9])
+            .map(|s| short_id(s))
             .collect::<Vec<_>>()
             .join(", ")
             .slate_blue()
     };
 
     let width = 11;
-    println!(
+    writeln!(
+        out,
         "  {:width$} {}",
         "summary:".bright_white(),
-        task.summary.to_string().bright_black()
-    );
-    println!(
+        display.expand(&task.summary).bright_black()
+    )?;
+    writeln!(
+        out,
         "  {:width$} {}",
         "id:".bright_white(),
-        &task.id[0..9].to_string().bright_black()
-    );
-    println!(
+        short_id(&task.id).bright_black()
+    )?;
+    writeln!(
+        out,
         "  {:width$} {}",
         "priority:".bright_white(),
         task.priority.to_string().bright_black()
-    );
-    println!(
+    )?;
+    writeln!(
+        out,
         "  {:width$} {}",
         "status:".bright_white(),
         task.status.to_string().bright_black()
-    );
-    println!(
+    )?;
+    writeln!(
+        out,
         "  {:width$} {}",
         "created:".bright_white(),
-        task.created_at.format("%F %T").to_string().bright_black()
-    );
+        display.format_datetime(&task.created_at).bright_black()
+    )?;
     if task.status == TaskStatus::Blocked {
-        println!("  {:width$} {}", "blocked by:".bright_white(), blocked);
+        writeln!(out, "  {:width$} {}", "blocked by:".bright_white(), blocked)?;
     }
-    if !task.details.is_empty() {
-        // let details = str::replace(&task.details, "!", "?");
-        let details = task.details.replace('\n', &format!("\n  {:width$} ", ""));
-        println!(
+    if !task.tags.is_empty() {
+        let tags: Vec<&str> = task.tags.iter().map(String::as_str).collect();
+        writeln!(
+            out,
             "  {:width$} {}",
-            "details:".bright_white(),
-            details.bright_black()
-        );
+            "tags:".bright_white(),
+            tags.join(", ").bright_black()
+        )?;
     }
-}
-
-fn process_block_on(
-    task_list: &mut tasklist::TaskList,
-    task_ids: Vec<String>,
-) -> Result<usize, Box<dyn Error>> {
-    let mut blocker_count = 0;
-    if task_ids.is_empty() {
-        // TODO: Should this prompt for which to block on?
-        println!("block_on arg list is empty, which is not currently allowed");
-    } else {
-        // Edit selected tasks
-        let blockee = task_ids.first().unwrap();
-        let mut task_ids = task_ids.clone();
-        task_ids.remove(0);
-        for id in task_ids.clone() {
-            blocker_count += task_list.block_task_on(blockee, &id);
+    if let Some(due_at) = &task.due_at {
+        writeln!(
+            out,
+            "  {:width$} {}",
+            "due:".bright_white(),
+            display.format_datetime(due_at).bright_black()
+        )?;
+    }
+    if task.completed_at.is_some() {
+        print_task_timeline(out, task, display)?;
+    } else if let Some(last_started_at) = &task.last_started_at {
+        writeln!(
+            out,
+            "  {:width$} {}",
+            "started:".bright_white(),
+            display.format_datetime(last_started_at).bright_black()
+        )?;
+    }
+    if let Some(scheduled_at) = &task.scheduled_at {
+        writeln!(
+            out,
+            "  {:width$} {}",
+            "scheduled:".bright_white(),
+            display.format_datetime(scheduled_at).bright_black()
+        )?;
+    }
+    if let Some(estimate) = &task.estimate {
+        writeln!(
+            out,
+            "  {:width$} {}",
+            "estimate:".bright_white(),
+            humanize_duration(estimate.num_seconds()).bright_black()
+        )?;
+    }
+    if let Some(owner) = &task.owner {
+        writeln!(
+            out,
+            "  {:width$} {}",
+            "owner:".bright_white(),
+            owner.to_string().bright_black()
+        )?;
+    }
+    if let Some(rule) = &task.snooze_rule {
+        writeln!(
+            out,
+            "  {:width$} {}",
+            "snooze:".bright_white(),
+            rule.bright_black()
+        )?;
+    }
+    // Each line of the details is written on its own, aligned under the
+    // first, and trailing blank lines are dropped rather than padded
+    for (index, line) in display.expand(&task.details).trim_end().lines().enumerate() {
+        let heading = if index == 0 { "details:" } else { "" };
+        let line = line.trim_end();
+        if line.is_empty() {
+            writeln!(out, "  {}", heading.bright_white())?;
+        } else {
+            writeln!(
+                out,
+                "  {:width$} {}",
+                heading.bright_white(),
+                line.bright_black()
+            )?;
         }
     }
-    Ok(blocker_count)
+    Ok(())
 }
 
-fn process_complete(
+/// Width that details are wrapped to in compact output
+const COMPACT_WRAP_WIDTH: usize = 76;
+
+/// Print the id, priority, status and full summary of a task on one line,
+/// followed by its details, if any, indented and wrapped
+fn print_task_compact(out: &mut String, task: &Task, display: &DisplayOptions) -> fmt::Result {
+    writeln!(
+        out,
+        "  {}  {}  {}  {}",
+        short_id(&task.id).bright_white(),
+        task.priority.to_string().bright_black(),
+        task.status.to_string().bright_black(),
+        display.tint(&task.category, display.expand(&task.summary).white())
+    )?;
+    for line in display.expand(&task.details).trim_end().lines() {
+        for wrapped in wrap_words(line.trim_end(), COMPACT_WRAP_WIDTH) {
+            if wrapped.is_empty() {
+                writeln!(out)?;
+            } else {
+                writeln!(out, "    {}", wrapped.bright_black())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Break a line of text into lines of at most width characters, at spaces.
+/// A word longer than the width gets a line of its own.
+fn wrap_words(text: &str, width: usize) -> Vec<String> {
+    let mut lines = vec![String::new()];
+    for word in text.split_whitespace() {
+        let line = lines.last_mut().expect("lines is never empty");
+        if line.is_empty() {
+            line.push_str(word);
+        } else if line.chars().count() + 1 + word.chars().count() <= width {
+            line.push(' ');
+            line.push_str(word);
+        } else {
+            lines.push(word.to_string());
+        }
+    }
+    lines
+}
+
+/// Print the lifecycle of a task as a timeline: when it was created, last
+/// started and completed, each with the time elapsed since the step before.
+fn print_task_timeline(out: &mut String, task: &Task, display: &DisplayOptions) -> fmt::Result {
+    let width = 11;
+    let mut steps = vec![("created", task.created_at)];
+    steps.extend(task.last_started_at.map(|at| ("started", at)));
+    steps.extend(task.completed_at.map(|at| ("completed", at)));
+    // Backdated timestamps can be out of order, so show them as they happened
+    steps.sort_by_key(|(_, at)| *at);
+
+    let mut previous: Option<DateTime<Local>> = None;
+    for (index, (step, at)) in steps.iter().enumerate() {
+        let heading = if index == 0 { "timeline:" } else { "" };
+        let elapsed = match previous {
+            Some(previous) => match humanize_duration((*at - previous).num_seconds()) {
+                elapsed if elapsed.is_empty() => "  (+0s)".to_string(),
+                elapsed => format!("  (+{})", elapsed),
+            },
+            None => String::new(),
+        };
+        writeln!(
+            out,
+            "  {:width$} {}  {}{}",
+            heading.bright_white(),
+            display.format_datetime(at).bright_black(),
+            step,
+            elapsed.bright_black()
+        )?;
+        previous = Some(*at);
+    }
+    Ok(())
+}
+
+/// Render the tasks matching the filter in sorted order in the given export format
+fn export_tasks(
+    task_list: &tasklist::TaskList,
+    filter: &Filter,
+    format: ExportFormat,
+    fields: &[String],
+) -> Result<String, Box<dyn Error>> {
+    let tasks = task_list.filtered(filter);
+    let mut exported = String::new();
+    if !fields.is_empty() {
+        let projected = project_fields(&tasks, fields)?;
+        match format {
+            ExportFormat::Json => {
+                exported.push_str(&serde_json::to_string_pretty(&projected)?);
+                exported.push('\n');
+            }
+            ExportFormat::Jsonl => {
+                for task in &projected {
+                    exported.push_str(&serde_json::to_string(task)?);
+                    exported.push('\n');
+                }
+            }
+            _ => return Err("--field can only be used with the json and jsonl formats".into()),
+        }
+        return Ok(exported);
+    }
+    match format {
+        ExportFormat::Json => {
+            exported.push_str(&serde_json::to_string_pretty(&tasks)?);
+            exported.push('\n');
+        }
+        ExportFormat::Jsonl => {
+            for task in &tasks {
+                exported.push_str(&serde_json::to_string(task)?);
+                exported.push('\n');
+            }
+        }
+        ExportFormat::Ical => exported = export_ical(&tasks),
+        ExportFormat::Tsv => exported = export_tsv(&tasks),
+    }
+    Ok(exported)
+}
+
+/// Serialize each task as a JSON object holding only the given fields
+fn project_fields(
+    tasks: &[Task],
+    fields: &[String],
+) -> Result<Vec<serde_json::Value>, Box<dyn Error>> {
+    let known = serde_json::to_value(Task::builder().build())?;
+    if let Some(unknown) = fields
+        .iter()
+        .find(|field| known.get(field.as_str()).is_none())
+    {
+        return Err(format!("Unknown field '{}'", unknown).into());
+    }
+    tasks
+        .iter()
+        .map(|task| {
+            let mut value = serde_json::to_value(task)?;
+            if let Some(object) = value.as_object_mut() {
+                object.retain(|key, _| fields.contains(key));
+            }
+            Ok(value)
+        })
+        .collect()
+}
+
+/// Render the tasks that have a due or scheduled time as an iCalendar
+/// VCALENDAR, one VTODO per task.  Lines end in CRLF, as RFC 5545 requires.
+fn export_ical(tasks: &[Task]) -> String {
+    let timestamp = |at: &DateTime<Local>| at.naive_utc().format("%Y%m%dT%H%M%SZ").to_string();
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//ztask//ztask//EN".to_string(),
+    ];
+    for task in tasks {
+        let Some(due) = task.due_at.or(task.scheduled_at) else {
+            continue;
+        };
+        let status = match task.status {
+            TaskStatus::Active => "IN-PROCESS",
+            TaskStatus::Completed => "COMPLETED",
+            _ => "NEEDS-ACTION",
+        };
+        lines.extend([
+            "BEGIN:VTODO".to_string(),
+            format!("UID:{}", task.id),
+            format!("DTSTAMP:{}", timestamp(&task.last_updated())),
+            format!("SUMMARY:{}", escape_ical_text(&task.summary)),
+            format!("DUE:{}", timestamp(&due)),
+            format!("STATUS:{}", status),
+            "END:VTODO".to_string(),
+        ]);
+    }
+    lines.push("END:VCALENDAR".to_string());
+    lines.iter().map(|line| fold_ical_line(line)).collect()
+}
+
+/// Longest an iCalendar content line may be, in octets, before the CRLF
+const ICAL_LINE_LIMIT: usize = 75;
+
+/// Fold an iCalendar content line into lines of at most 75 octets, each
+/// continuation starting with a space, and end it with CRLF (RFC 5545 3.1).
+/// Lines are only broken between characters, never inside one.
+fn fold_ical_line(line: &str) -> String {
+    let mut folded = String::new();
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > ICAL_LINE_LIMIT {
+            folded.push_str("\r\n ");
+            length = 1;
+        }
+        folded.push(c);
+        length += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
+}
+
+/// Escape text for an iCalendar TEXT value.  Any kind of line break becomes
+/// an escaped newline.
+fn escape_ical_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\n")
+        .replace('\r', "\n")
+        .replace('\n', "\\n")
+}
+
+/// Columns of the TSV export, in order
+const TSV_COLUMNS: [&str; 7] = [
+    "id",
+    "status",
+    "priority",
+    "category",
+    "created_at",
+    "due_at",
+    "summary",
+];
+
+/// Render the tasks as tab-separated values, one task per line after a
+/// header row.  Times are RFC 3339 and a missing due time is left empty.
+fn export_tsv(tasks: &[Task]) -> String {
+    let mut exported = TSV_COLUMNS.join("\t");
+    exported.push('\n');
+    for task in tasks {
+        let fields = [
+            escape_tsv_field(&task.id),
+            task.status.to_string(),
+            task.priority.to_string(),
+            escape_tsv_field(&task.category),
+            task.created_at.to_rfc3339(),
+            task.due_at.map(|due| due.to_rfc3339()).unwrap_or_default(),
+            escape_tsv_field(&task.summary),
+        ];
+        exported.push_str(&fields.join("\t"));
+        exported.push('\n');
+    }
+    exported
+}
+
+/// Escape backslashes, tabs and line breaks in a TSV field, so that each
+/// task stays on one line with the expected number of columns
+fn escape_tsv_field(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+/// Print task counts by status, the number of tasks completed within the
+/// window before now, and the estimated remaining work
+fn print_stats(
+    out: &mut String,
+    task_list: &tasklist::TaskList,
+    window: chrono::Duration,
+    now: &DateTime<Local>,
+) -> fmt::Result {
+    for status in [
+        TaskStatus::Active,
+        TaskStatus::Backlog,
+        TaskStatus::Blocked,
+        TaskStatus::Sleeping,
+        TaskStatus::Scheduled,
+        TaskStatus::Completed,
+    ] {
+        let count = task_list
+            .tasks
+            .iter()
+            .filter(|task| task.status == status)
+            .count();
+        writeln!(out, "  {:10} {}", format!("{}:", status), count)?;
+    }
+
+    let completed = task_list
+        .tasks
+        .iter()
+        .filter(|task| task.completed_at.is_some_and(|at| *now - at <= window))
+        .count();
+    writeln!(
+        out,
+        "  completed in last {}: {}",
+        humanize_duration(window.num_seconds()),
+        completed
+    )?;
+
+    let (total, unestimated) = task_list.remaining_estimate();
+    let total = match humanize_duration(total.num_seconds()) {
+        remaining if remaining.is_empty() => "0m".to_string(),
+        remaining => remaining,
+    };
+    writeln!(out, "  estimated remaining work: {}", total)?;
+    if unestimated > 0 {
+        writeln!(out, "  {} open task(s) have no estimate", unestimated)?;
+    }
+    Ok(())
+}
+
+fn process_history(
+    task_list: &tasklist::TaskList,
+    task_id: Option<String>,
+    display: &DisplayOptions,
+) -> Result<usize, Box<dyn Error>> {
+    let path = match &task_list.event_log {
+        Some(path) => path,
+        None => return Err("Event logging is disabled".into()),
+    };
+    let mut events = eventlog::read(path)?;
+    if let Some(task_id) = task_id {
+        events.retain(|event| event.task_id.starts_with(&task_id));
+    }
+
+    let mut out = String::new();
+    for event in &events {
+        let status = |status: &Option<TaskStatus>| match status {
+            Some(status) => status.to_string(),
+            None => "-".to_string(),
+        };
+        writeln!(
+            out,
+            "  {}  {:8}  {}  {} -> {}",
+            display.format_datetime(&event.ts).bright_black(),
+            event.op,
+            short_id(&event.task_id),
+            status(&event.from_status),
+            status(&event.to_status)
+        )?;
+    }
+    print!("{}", out);
+    Ok(events.len())
+}
+
+fn process_bump(
     task_list: &mut tasklist::TaskList,
     task_ids: Vec<String>,
+    delta: i8,
 ) -> Result<usize, Box<dyn Error>> {
-    let mut completed_count = 0;
-    if task_ids.is_empty() {
-        let mut tasks = task_list.tasks.clone();
-        tasks.retain(|task| task.status == TaskStatus::Active);
+    let mut updated_count = 0;
+    for id in task_ids {
+        updated_count += task_list.bump_priority(id, delta);
+    }
+    Ok(updated_count)
+}
 
-        if tasks.is_empty() {
-            return Ok(0);
+/// Reset each task and print what was cleared from it
+fn process_reset(
+    task_list: &mut tasklist::TaskList,
+    task_ids: Vec<String>,
+) -> Result<usize, Box<dyn Error>> {
+    let mut reset_count = 0;
+    for id in task_ids {
+        let Some(cleared) = task_list.reset_task(id.clone()) else {
+            continue;
+        };
+        match cleared.is_empty() {
+            true => println!("{}: nothing to reset", short_id(&id)),
+            false => {
+                println!("{}: cleared {}", short_id(&id), cleared.join(", "));
+                reset_count += 1;
+            }
         }
+    }
+    Ok(reset_count)
+}
 
-        let mut tasks = tasks.into_sorted_vec();
-        let task = tasks.remove(0);
-        task_list.complete_task(task.id);
-        completed_count = 1;
+fn process_rebalance(task_list: &mut tasklist::TaskList) -> Result<usize, Box<dyn Error>> {
+    Ok(task_list.rebalance_priorities())
+}
+
+fn process_restore(
+    task_list: &mut tasklist::TaskList,
+    path: Option<String>,
+) -> Result<usize, Box<dyn Error>> {
+    let path = match path {
+        Some(path) => shellexpand::env(&path)?.to_string(),
+        None => task_list.backup_path(),
+    };
+    Ok(task_list.restore_from(&path)?)
+}
+
+/// Save the fields of the task with the given id as a named template
+fn process_save_template(
+    task_list: &tasklist::TaskList,
+    templates_dir: &str,
+    name: &str,
+    id: &str,
+) -> Result<(), Box<dyn Error>> {
+    let task = task_list.find_unique(id)?;
+    Template::from_task(task).save(templates_dir, name)?;
+    Ok(())
+}
+
+/// Merge each group of duplicate tasks, asking first for each group unless
+/// `yes` is given.
+fn process_dedupe(
+    task_list: &mut tasklist::TaskList,
+    yes: bool,
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> Result<usize, Box<dyn Error>> {
+    let mut merged_count = 0;
+    for group in task_list.find_duplicate_groups() {
+        for (index, id) in group.iter().enumerate() {
+            if let Some(task) = task_list.matching(id).first() {
+                let role = if index == 0 { "keep " } else { "merge" };
+                writeln!(output, "  {}  {}  {}", role, short_id(id), task.summary)?;
+            }
+        }
+        if !yes {
+            write!(output, "Merge these {} tasks? [y/N] ", group.len())?;
+            output.flush()?;
+            let mut answer = String::new();
+            input.read_line(&mut answer)?;
+            if !matches!(answer.trim().to_lowercase().as_str(), "y" | "yes") {
+                continue;
+            }
+        }
+        merged_count += task_list.merge_tasks(&group);
+    }
+    Ok(merged_count)
+}
+
+fn process_block_on(
+    task_list: &mut tasklist::TaskList,
+    task_ids: Vec<String>,
+) -> Result<usize, Box<dyn Error>> {
+    // clap requires two ids, but don't rely on it here
+    let Some((blockee, blockers)) = task_ids.split_first().filter(|(_, b)| !b.is_empty()) else {
+        return Err(format!(
+            "block needs the id of the task to block and at least one blocker id, got {} id(s)",
+            task_ids.len()
+        )
+        .into());
+    };
+    let mut blocker_count = 0;
+    for id in blockers {
+        blocker_count += task_list.block_task_on(blockee, id);
+    }
+    Ok(blocker_count)
+}
+
+fn process_complete(
+    task_list: &mut tasklist::TaskList,
+    task_ids: Vec<String>,
+    count: usize,
+    at: DateTime<Local>,
+    delete: bool,
+) -> Result<usize, Box<dyn Error>> {
+    if at > Local::now() {
+        return Err("tasks can't be completed in the future".into());
+    }
+    let task_ids = if task_ids.is_empty() {
+        // Complete the first `count` active tasks in sorted order
+        let tasks = task_list.filtered(&Filter::default().with_status(TaskStatus::Active));
+        let tasks: Vec<Task> = tasks.into_iter().take(count).collect();
+        if count > 1 {
+            for task in &tasks {
+                println!("Completed {}  {}", short_id(&task.id), task.summary);
+            }
+        }
+        tasks.into_iter().map(|task| task.id).collect()
     } else {
-        // Complete selected tasks
-        for id in task_ids {
-            completed_count += task_list.complete_task(id);
+        task_ids
+    };
+    let mut completed_count = 0;
+    let mut removed_count = 0;
+    for id in task_ids {
+        let completed = task_list.complete_task_at(id.clone(), at);
+        completed_count += completed;
+        // Completing first records the completion in the event log.  Tasks
+        // blocked on it are unblocked the next time the list is loaded.
+        if delete && completed > 0 {
+            removed_count += task_list.remove_task(id);
         }
     }
+    if delete {
+        println!(
+            "removed {}, completed {}",
+            removed_count,
+            completed_count - removed_count
+        );
+    }
     Ok(completed_count)
 }
 
 fn process_start(
     task_list: &mut tasklist::TaskList,
     task_ids: Vec<String>,
+    at: DateTime<Local>,
 ) -> Result<usize, Box<dyn Error>> {
     let mut completed_count = 0;
     if task_ids.is_empty() {
-        let count_active = task_list
-            .tasks
-            .iter()
-            .filter(|task| task.status == TaskStatus::Active)
-            .count();
-
-        if count_active == 0 {
-            let mut tasks = task_list.tasks.clone();
-            tasks.retain(|task| task.status == TaskStatus::Backlog);
+        if task_list.can_start_another() {
+            let mut tasks = task_list
+                .tasks
+                .sorted_matching(|task| task.status == TaskStatus::Backlog);
 
             if tasks.is_empty() {
                 return Ok(0);
             }
 
-            let mut tasks = tasks.into_sorted_vec();
             let task = tasks.remove(0);
-            task_list.start_task(task.id);
-            completed_count = 1;
+            completed_count = task_list.start_task_at(task.id, at);
         } else {
-            println!("Can't activate default backlog task when there are active tasks");
-            println!("Clear your active tasks or use the start command with a task id");
+            print!("{}", describe_start_deadlock(task_list));
         }
     } else {
-        task_list.start_task(task_ids.first().unwrap().clone());
-        completed_count = 1;
+        // Start selected tasks
+        for id in task_ids {
+            completed_count += task_list.start_task_at(id, at);
+        }
     }
     Ok(completed_count)
 }
 
+/// Check that every id matches exactly one task, so a batch command can
+/// refuse to run at all rather than skip the ids that don't
+fn check_ids_resolve(task_list: &tasklist::TaskList, task_ids: &[String]) -> Result<(), String> {
+    let unresolved: Vec<String> = task_ids
+        .iter()
+        .filter_map(|id| match task_list.matching(id).len() {
+            1 => None,
+            count => Some(format!("'{}' matches {}", id, count)),
+        })
+        .collect();
+    if unresolved.is_empty() {
+        return Ok(());
+    }
+    Err(format!(
+        "Nothing changed, as not every id matches one task: {}",
+        unresolved.join(", ")
+    ))
+}
+
+/// Return the ids with any that match several tasks replaced by the task
+/// the user picks, when interactive.  Otherwise the ids are returned as is,
+/// and commands report the ambiguity themselves.
+fn choose_ids(
+    task_list: &tasklist::TaskList,
+    task_ids: Vec<String>,
+    interactive: bool,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    if !interactive {
+        return Ok(task_ids);
+    }
+    Ok(prompt_for_ambiguous_ids(
+        task_list,
+        task_ids,
+        &mut std::io::stdin().lock(),
+        &mut std::io::stdout(),
+    )?)
+}
+
+/// For each id that matches several tasks, list the candidates and read
+/// the number of the one to use from the input.  Ids that match at most
+/// one task, or whose choice isn't valid, are kept unchanged.
+fn prompt_for_ambiguous_ids(
+    task_list: &tasklist::TaskList,
+    task_ids: Vec<String>,
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> std::io::Result<Vec<String>> {
+    let mut chosen = Vec::new();
+    for id in task_ids {
+        let candidates = task_list.matching(&id);
+        if candidates.len() < 2 {
+            chosen.push(id);
+            continue;
+        }
+        writeln!(output, "Id '{}' matches {} tasks:", id, candidates.len())?;
+        for (index, task) in candidates.iter().enumerate() {
+            writeln!(
+                output,
+                "  {:>3}  {}  {}",
+                index + 1,
+                short_id(&task.id),
+                task.summary
+            )?;
+        }
+        write!(output, "Which task? [1-{}] ", candidates.len())?;
+        output.flush()?;
+        let mut answer = String::new();
+        input.read_line(&mut answer)?;
+        match answer
+            .trim()
+            .parse::<usize>()
+            .ok()
+            .and_then(|n| candidates.get(n.checked_sub(1)?))
+        {
+            Some(task) => chosen.push(task.id.clone()),
+            None => {
+                writeln!(output, "No task chosen for '{}'", id)?;
+                chosen.push(id);
+            }
+        }
+    }
+    Ok(chosen)
+}
+
+/// Explain why the next backlog task can't be started, naming the active
+/// tasks and spelling out the commands that would make room for it.
+fn describe_start_deadlock(task_list: &tasklist::TaskList) -> String {
+    let mut message = task_list.describe_active_limit();
+    let active = task_list.filtered(&Filter::default().with_status(TaskStatus::Active));
+    let next = task_list
+        .filtered(&Filter::default().with_status(TaskStatus::Backlog))
+        .into_iter()
+        .next();
+    if let (Some(active), Some(next)) = (active.first(), next) {
+        message.push_str(&format!(
+            "Stop an active task first with 'ztask stop {}', then 'ztask start {}',\n\
+             or allow more active tasks with 'ztask --max-active {} start {}'\n",
+            short_id(&active.id),
+            short_id(&next.id),
+            task_list.max_active + 1,
+            short_id(&next.id)
+        ));
+    }
+    message
+}
+
+/// Return the ids a command should act on: the given ids with any #n
+/// references resolved, or the task picked by --oldest/--newest from those
+/// with the given status (active by default).
+fn select_task_ids(
+    task_list: &tasklist::TaskList,
+    task_ids: Option<Vec<String>>,
+    select: AgeSelector,
+    status: Option<TaskStatus>,
+) -> Result<Vec<String>, String> {
+    if !select.oldest && !select.newest {
+        return Ok(task_list.resolve_ordinals(task_ids.unwrap_or_default()));
+    }
+    let status = status.unwrap_or(TaskStatus::Active);
+    let tasks = task_list.filtered(&Filter::default().with_status(status.clone()));
+    let task = if select.oldest {
+        tasks.into_iter().min_by_key(|task| task.created_at)
+    } else {
+        tasks.into_iter().max_by_key(|task| task.created_at)
+    };
+    match task {
+        Some(task) => Ok(vec![task.id]),
+        None => Err(format!("No {} tasks to select from", status)),
+    }
+}
+
+/// Start the next backlog task, as the start command does without ids, and
+/// show the task that was started
+fn process_start_next(
+    task_list: &mut tasklist::TaskList,
+    display: &DisplayOptions,
+) -> Result<usize, Box<dyn Error>> {
+    let next = task_list
+        .filtered(&Filter::default().with_status(TaskStatus::Backlog))
+        .into_iter()
+        .next();
+    let started_count = process_start(task_list, vec![], Local::now())?;
+    if let (1, Some(next)) = (started_count, next) {
+        if let Some(task) = task_list.copy_task(next.id) {
+            let mut out = String::new();
+            print_task_oneline(&mut out, &task, true, display)?;
+            print!("{}", out);
+        }
+    }
+    Ok(started_count)
+}
+
 fn process_stop(
     task_list: &mut tasklist::TaskList,
     task_ids: Vec<String>,
@@ -666,52 +3106,155 @@ fn process_stop(
             .count();
 
         if count_active != 0 {
-            let mut tasks = task_list.tasks.clone();
-            tasks.retain(|task| task.status == TaskStatus::Active);
+            let mut tasks = task_list.filtered(&Filter::default().with_status(TaskStatus::Active));
 
             // if tasks.is_empty() { return Ok(0) }
 
-            let mut tasks = tasks.into_sorted_vec();
             let task = tasks.remove(0);
-            task_list.suspend_task(task.id, "0".to_string());
-            completed_count = 1;
+            completed_count = task_list.stop_task(task.id);
         } else {
             println!("There's no default active task to stop");
         }
     } else {
-        task_list.suspend_task(task_ids.first().unwrap().clone(), "0".to_string());
-        completed_count = 1;
+        // Stop selected tasks
+        for id in task_ids {
+            completed_count += task_list.stop_task(id);
+        }
     }
     Ok(completed_count)
 }
 
+/// Move the active tasks that have been active for longer than `idle`,
+/// counting from when they were last started (or created, if they never
+/// were), back to the backlog, and print each one moved
+fn process_sweep(
+    task_list: &mut tasklist::TaskList,
+    idle: chrono::Duration,
+    now: &DateTime<Local>,
+    display: &DisplayOptions,
+) -> Result<usize, Box<dyn Error>> {
+    let stale: Vec<Task> = task_list
+        .filtered(&Filter::default().with_status(TaskStatus::Active))
+        .into_iter()
+        .filter(|task| *now - task.last_started_at.unwrap_or(task.created_at) > idle)
+        .collect();
+    let mut stopped_count = 0;
+    for task in stale {
+        stopped_count += task_list.sweep_task(task.id.clone());
+        // Show the task as it is now, back in the backlog
+        if let Some(task) = task_list.copy_task(task.id) {
+            let mut out = String::new();
+            print_task_oneline(&mut out, &task, false, display)?;
+            print!("{}", out);
+        }
+    }
+    Ok(stopped_count)
+}
+
 fn process_sleep(
     task_list: &mut tasklist::TaskList,
     task_ids: Vec<String>,
-    duration: String,
+    duration: chrono::Duration,
+    wake_at_hour: Option<u32>,
+    display: &DisplayOptions,
 ) -> Result<usize, Box<dyn Error>> {
     let mut suspended_count = 0;
     if task_ids.is_empty() {
-        let mut tasks = task_list.tasks.clone();
-        tasks.retain(|task| task.status == TaskStatus::Active);
+        let mut tasks = task_list.filtered(&Filter::default().with_status(TaskStatus::Active));
 
         if tasks.is_empty() {
             return Ok(0);
         }
 
-        let mut tasks = tasks.into_sorted_vec();
         let task = tasks.remove(0);
-        task_list.suspend_task(task.id, duration.clone());
-        suspended_count = 1;
+        suspended_count = task_list.suspend_task(task.id.clone(), duration, wake_at_hour);
+        // Show which task went to sleep, and until when
+        if let Some(task) = task_list.copy_task(task.id) {
+            let mut out = String::new();
+            print_task_oneline_with_format_override(
+                &mut out,
+                &task,
+                |s| s.bright_black(),
+                display,
+            )?;
+            print!("{}", out);
+        }
     } else {
         // Put selected tasks to sleep
         for id in task_ids {
-            suspended_count += task_list.suspend_task(id, duration.clone());
+            suspended_count += task_list.suspend_task(id, duration, wake_at_hour);
+        }
+    }
+    Ok(suspended_count)
+}
+
+/// Put tasks to sleep until the blocker is completed, by blocking them on it
+/// so unblock_tasks returns them to the backlog.  Without ids, the first
+/// active task is put to sleep.
+fn process_sleep_until_task(
+    task_list: &mut tasklist::TaskList,
+    task_ids: Vec<String>,
+    blocker_id: String,
+) -> Result<usize, Box<dyn Error>> {
+    let task_ids = if task_ids.is_empty() {
+        match task_list
+            .filtered(&Filter::default().with_status(TaskStatus::Active))
+            .first()
+        {
+            Some(task) => vec![task.id.clone()],
+            None => return Ok(0),
         }
+    } else {
+        task_ids
+    };
+    let mut suspended_count = 0;
+    for id in task_ids {
+        suspended_count += task_list.block_task_on(&id, &blocker_id);
     }
     Ok(suspended_count)
 }
 
+fn process_schedule(
+    task_list: &mut tasklist::TaskList,
+    task_id: String,
+    when: DateTime<Local>,
+    display: &DisplayOptions,
+) -> Result<usize, Box<dyn Error>> {
+    let scheduled_count = task_list.schedule_task(task_id, when);
+    if scheduled_count > 0 {
+        println!("Scheduled to start {}", display.format_datetime(&when));
+    }
+    Ok(scheduled_count)
+}
+
+fn process_set_fields(
+    task_list: &mut tasklist::TaskList,
+    task_ids: Vec<String>,
+    assignments: Vec<String>,
+) -> Result<usize, Box<dyn Error>> {
+    let mut fields: Vec<(String, String)> = vec![];
+    for assignment in assignments {
+        match assignment.split_once('=') {
+            Some((key, value)) => fields.push((key.trim().to_string(), value.to_string())),
+            None => return Err(format!("Expected KEY=VALUE, got '{}'", assignment).into()),
+        }
+    }
+
+    let task_ids = if task_ids.is_empty() {
+        let mut tasks = task_list.filtered(&Filter::default().with_status(TaskStatus::Active));
+
+        if tasks.is_empty() {
+            return Ok(0);
+        }
+
+        vec![tasks.remove(0).id]
+    } else {
+        task_ids
+    };
+
+    Ok(task_list.update_fields(&task_ids, &fields)?)
+}
+
 fn process_edit(
     task_list: &mut tasklist::TaskList,
     task_ids: Vec<String>,
@@ -719,14 +3262,12 @@ fn process_edit(
 ) -> Result<usize, Box<dyn Error>> {
     let mut edit_count = 0;
     if task_ids.is_empty() {
-        let mut tasks = task_list.tasks.clone();
-        tasks.retain(|task| task.status == TaskStatus::Active);
+        let mut tasks = task_list.filtered(&Filter::default().with_status(TaskStatus::Active));
 
         if tasks.is_empty() {
             return Ok(0);
         }
 
-        let mut tasks = tasks.into_sorted_vec();
         let task = tasks.remove(0);
         if details_only {
             task_list.edit_task_details(task.id);
@@ -752,75 +3293,136 @@ fn process_del(
     task_list: &mut tasklist::TaskList,
     task_ids: Vec<String>,
 ) -> Result<usize, Box<dyn Error>> {
-    let prior_task_count = task_list.tasks.len();
+    let mut removed_count = 0;
     if task_ids.is_empty() {
         // Remove last task
-        task_list.tasks.pop();
+        if task_list.tasks.pop().is_some() {
+            task_list.dirty = true;
+            removed_count += 1;
+        }
     } else {
-        // Remove selected tasks
+        // Remove selected tasks, counting only those actually removed
         for id in task_ids {
-            task_list.remove_task(id);
+            removed_count += task_list.remove_task(id);
         }
     }
-    Ok(prior_task_count - task_list.tasks.len())
+    Ok(removed_count)
+}
+
+/// Field values applied to every task created by the add command
+#[derive(Default)]
+struct AddOptions {
+    is_interrupt: bool,
+    /// Add the task(s) sleeping for this long
+    sleep: Option<chrono::Duration>,
+    /// Add one task per name, rather than joining single words into one
+    separate: bool,
+    owner: Option<String>,
+    priority: Option<u8>,
+    estimate: Option<chrono::Duration>,
+    details: Option<String>,
+    /// Category of the task(s), instead of "quick"
+    category: Option<String>,
+    tags: BTreeSet<String>,
+    /// Summary used when no names are given, instead of "New task #N"
+    default_summary: Option<String>,
+    /// Parse +tag, @category, p: and due: tokens out of the names
+    quick_add: bool,
 }
 
 fn process_add(
     task_list: &mut tasklist::TaskList,
     new_task_names: Vec<String>,
-    is_interrupt: bool,
+    options: &AddOptions,
+    display: &DisplayOptions,
 ) -> Result<Vec<String>, Box<dyn Error>> {
-    let mut created_task_ids: Vec<String> = Vec::new();
-    if new_task_names.is_empty() {
+    let names = if new_task_names.is_empty() {
         // Create default task with default name
-        let default_task_name = format!("New task #{count}", count = task_list.num_tasks() + 1);
-        let new_task = Task::new(default_task_name, "quick".to_string(), is_interrupt);
-        created_task_ids.push(new_task.id.clone());
-        print_task_oneline(&new_task, true);
-        task_list.add_task(new_task);
+        vec![options
+            .default_summary
+            .clone()
+            .unwrap_or_else(|| format!("New task #{count}", count = task_list.num_tasks() + 1))]
+    } else if !options.separate
+        && new_task_names.len() > 1
+        && !new_task_names.iter().any(|name| name.contains(' '))
+    {
+        // All task names are single word, so consider this as a single task
+        let name = new_task_names.join(" ");
+        task_list.note(format!(
+            "Merged {} arguments into one task '{}'; use --separate for individual tasks",
+            new_task_names.len(),
+            name
+        ));
+        vec![name]
     } else {
-        // Create new tasks with provided names
-        if new_task_names.len() > 1 {
-            // If they are all single word, consider this as a single task
-            let count_multi_word = new_task_names
-                .iter()
-                .filter(|name| name.contains(' '))
-                .count();
-            if count_multi_word == 0 {
-                // All task names are single word
-                // Create single task with those task names
-                let name = new_task_names.join(" ");
-                let new_task = Task::new(name, "quick".to_string(), is_interrupt);
-                created_task_ids.push(new_task.id.clone());
-                print_task_oneline(&new_task, true);
-                task_list.add_task(new_task);
-            } else {
-                // Some task names are multi-word
-                // Create multiple tasks with those task names
-                for name in new_task_names {
-                    let new_task = Task::new(name, "quick".to_string(), is_interrupt);
-                    created_task_ids.push(new_task.id.clone());
-                    print_task_oneline(&new_task, true);
-                    task_list.add_task(new_task);
-                }
-            }
+        // Create one task for each provided name
+        new_task_names
+    };
+
+    // Check every summary before adding anything, so a bad one adds nothing
+    let mut parsed = Vec::new();
+    for name in names {
+        let (name, meta) = if options.quick_add {
+            parse_quick_add(&name)
         } else {
-            // Create single task with that task name
-            let new_task = Task::new(new_task_names[0].clone(), "quick".to_string(), is_interrupt);
-            created_task_ids.push(new_task.id.clone());
-            print_task_oneline(&new_task, true);
-            task_list.add_task(new_task);
+            (name, Default::default())
+        };
+        parsed.push((parse_summary(&name, task_list.max_summary_length)?, meta));
+    }
+
+    let mut created_task_ids: Vec<String> = Vec::new();
+    for (name, meta) in parsed {
+        // Metadata given in the name is specific to this task, so it wins
+        let mut builder = task_list
+            .task_builder()
+            .summary(name)
+            .category(
+                meta.category
+                    .or(options.category.clone())
+                    .unwrap_or("quick".to_string()),
+            )
+            .status(match options.is_interrupt {
+                true => TaskStatus::Active,
+                false => TaskStatus::Backlog,
+            });
+        if let Some(sleep) = options.sleep {
+            builder = builder.sleep_until(Local::now() + sleep);
+        }
+        if let Some(priority) = meta.priority.or(options.priority) {
+            builder = builder.priority(priority);
         }
+        if let Some(due_at) = meta.due_at {
+            builder = builder.due(due_at);
+        }
+        let mut new_task = builder.build();
+        if let Some(details) = &options.details {
+            new_task.details.clone_from(details);
+        }
+        new_task.owner.clone_from(&options.owner);
+        new_task.estimate = options.estimate;
+        new_task.tags.clone_from(&options.tags);
+        new_task.tags.extend(meta.tags);
+        created_task_ids.push(new_task.id.clone());
+        let mut out = String::new();
+        print_task_oneline(&mut out, &new_task, true, display)?;
+        print!("{}", out);
+        task_list.add_task(new_task);
     }
-    // return number of tasks added
+    // return ids of tasks added
     Ok(created_task_ids)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use chrono::TimeZone;
+    use tasklist::tests::__backdate_tasks;
     use tasklist::tests::__create_temp_db;
     use tasklist::tests::__destroy_temp_db;
+    use tasklist::tests::__update_field;
+
+    /// Held by tests that override coloring, which is global
+    static COLOR_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
 
     #[test]
     #[should_panic]
@@ -830,6 +3432,56 @@ mod tests {
         }
     }
 
+    #[test]
+    fn verify_db_path_precedence() {
+        let flag = Some("flag.json".to_string());
+        let env = Some("env.json".to_string());
+        assert_eq!(resolve_db_path(flag.clone(), env.clone()), "flag.json");
+        assert_eq!(resolve_db_path(None, env), "env.json");
+        assert_eq!(resolve_db_path(None, Some("".to_string())), DB_PATH);
+        assert_eq!(resolve_db_path(None, None), DB_PATH);
+    }
+
+    #[test]
+    fn verify_misconfigured_db_path() {
+        let dir = "data/temp/misconfigured-db";
+        std::fs::create_dir_all(dir).unwrap();
+        let not_a_dir = format!("{dir}/ztask");
+        std::fs::write(&not_a_dir, "").unwrap();
+
+        let error = create_path(&format!("{not_a_dir}/db.json")).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!("{not_a_dir} exists but is not a directory")
+        );
+        let error = create_path(&format!("{not_a_dir}/nested/db.json")).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!("{not_a_dir} exists but is not a directory")
+        );
+
+        let error = create_path(dir).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!("{dir} is a directory, not a database file")
+        );
+        let args: Arguments = Arguments::parse_from(["ztask", "--db", dir, "list"]);
+        assert!(run(Some(args)).is_err());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn verify_corrupt_db_is_left_alone() {
+        let db = __create_temp_db(0);
+        std::fs::write(&db, "[,]").unwrap();
+        let args: Arguments = Arguments::parse_from(["ztask", "--db", &db, "add", "x"]);
+        let error = run(Some(args)).unwrap_err();
+        assert!(error.to_string().contains("line 1, column 2"), "{}", error);
+        assert_eq!(std::fs::read(&db).unwrap(), b"[,]");
+        __destroy_temp_db(db);
+    }
+
     // Tests for "list""
 
     #[test]
@@ -841,6 +3493,34 @@ mod tests {
         __destroy_temp_db(db);
     }
 
+    #[test]
+    fn verify_list_does_not_rewrite_db() {
+        let db = __create_temp_db(3);
+        let contents = std::fs::read_to_string(&db).unwrap();
+        let modified = std::fs::metadata(&db).unwrap().modified().unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+
+        let args: Arguments = Arguments::parse_from(["ztask", "--db", &db, "list"]);
+        run(Some(args)).unwrap();
+
+        assert_eq!(std::fs::read_to_string(&db).unwrap(), contents);
+        assert_eq!(
+            std::fs::metadata(&db).unwrap().modified().unwrap(),
+            modified
+        );
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_no_save() {
+        let db = __create_temp_db(1);
+        let args: Arguments =
+            Arguments::parse_from(["ztask", "--db", &db, "--no-save", "add", "dry run"]);
+        run(Some(args)).unwrap();
+        assert_eq!(tasklist::TaskList::new(db.clone()).num_tasks(), 1);
+        __destroy_temp_db(db);
+    }
+
     // Tests for "add"
 
     #[test]
@@ -895,61 +3575,2661 @@ mod tests {
         __destroy_temp_db(db);
     }
 
-    // Tests for "del"
+    #[test]
+    fn verify_add_trims_summary() {
+        let db = __create_temp_db(0);
+        let args: Arguments = Arguments::parse_from(["ztask", "--db", &db, "add", ""]);
+        assert!(run(Some(args)).is_err());
+        let args: Arguments = Arguments::parse_from(["ztask", "--db", &db, "add", "ok", "   "]);
+        assert!(run(Some(args)).is_err());
+        assert_eq!(tasklist::TaskList::new(db.clone()).num_tasks(), 0);
+
+        let args: Arguments = Arguments::parse_from(["ztask", "--db", &db, "add", "  hi  "]);
+        run(Some(args)).unwrap();
+        let mut task_list = tasklist::TaskList::new(db.clone());
+        let id = task_list.tasks.peek().unwrap().id.clone();
+        assert_eq!(task_list.copy_task(id.clone()).unwrap().summary, "hi");
+
+        // Renaming through --set is held to the same rules
+        assert!(__update_field(&mut task_list, id.clone(), "summary", " ").is_err());
+        assert_eq!(
+            __update_field(&mut task_list, id.clone(), "summary", " bye "),
+            Ok(1)
+        );
+        assert_eq!(task_list.copy_task(id).unwrap().summary, "bye");
+        drop(task_list);
+        __destroy_temp_db(db);
+    }
 
     #[test]
-    fn verify_delete_default() {
+    fn verify_add_rejects_long_summary() {
         let db = __create_temp_db(0);
-        let args: Arguments = Arguments::parse_from(["ztask", "--db", &db, "-v", "del"]);
-        println!("args: {:?}", args);
+        let paragraph = "word ".repeat(DEFAULT_MAX_SUMMARY_LENGTH);
+        let args: Arguments = Arguments::parse_from(["ztask", "--db", &db, "add", &paragraph]);
+        let error = run(Some(args)).unwrap_err().to_string();
+        assert!(error.contains("Put the rest in the details instead"));
+        assert_eq!(tasklist::TaskList::new(db.clone()).num_tasks(), 0);
+
+        let mut task_list = tasklist::TaskList::new(db.clone());
+        task_list.max_summary_length = 10;
+        let id = task_list.add_task(Task::builder().summary("short").build());
+        assert!(__update_field(&mut task_list, id.clone(), "summary", "rather longer").is_err());
+        assert_eq!(
+            __update_field(&mut task_list, id, "summary", "still ok"),
+            Ok(1)
+        );
+        drop(task_list);
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_add_short_ids() {
+        let db = __create_temp_db(0);
+        let args: Arguments = Arguments::parse_from([
+            "ztask",
+            "--db",
+            &db,
+            "--id-generator",
+            "short",
+            "add",
+            "first task",
+            "second task",
+            "third task",
+        ]);
+        run(Some(args)).unwrap();
+        let args: Arguments = Arguments::parse_from(["ztask", "--db", &db, "add", "a uuid task"]);
+        run(Some(args)).unwrap();
+
+        let task_list = tasklist::TaskList::new(db.clone());
+        let ids: BTreeSet<String> = task_list.tasks.iter().map(|t| t.id.clone()).collect();
+        assert_eq!(ids.len(), 4);
+        assert!(["a1", "a2", "a3"].iter().all(|id| ids.contains(*id)));
+        assert!(ids.iter().any(|id| id.len() == 32));
+        drop(task_list);
+
+        let args: Arguments = Arguments::parse_from(["ztask", "--db", &db, "list"]);
         run(Some(args)).unwrap();
         __destroy_temp_db(db);
     }
 
     #[test]
-    fn verify_delete_single() {
-        let db = __create_temp_db(2);
+    fn verify_add_argument_parsing() {
+        let parse = |argv: &[&str]| match Arguments::parse_from(argv).command {
+            Some(Command::Add {
+                task_names,
+                edit,
+                separate,
+                ..
+            }) => (task_names.unwrap_or_default(), edit, separate),
+            _ => panic!("not an add command"),
+        };
+        // Bare flags never take a value, so names may come before or after them
+        assert_eq!(
+            parse(&["ztask", "add", "--edit", "my task"]),
+            (vec!["my task".to_string()], true, false)
+        );
+        assert_eq!(
+            parse(&["ztask", "add", "my task", "--edit"]),
+            (vec!["my task".to_string()], true, false)
+        );
+        // Names given on either side of an option with a value are all kept
+        assert_eq!(
+            parse(&["ztask", "add", "buy", "--owner", "bob", "milk"]).0,
+            ["buy", "milk"]
+        );
+        assert_eq!(
+            parse(&["ztask", "add", "--separate", "eggs", "milk"]),
+            (vec!["eggs".to_string(), "milk".to_string()], false, true)
+        );
+    }
+
+    #[test]
+    fn verify_add_separate() {
+        let db = __create_temp_db(0);
+        let mut task_list = tasklist::TaskList::new(db.clone());
+        let names = vec!["eggs".to_string(), "milk".to_string()];
+        let options = AddOptions::default();
+        let ids = process_add(
+            &mut task_list,
+            names.clone(),
+            &options,
+            &DisplayOptions::default(),
+        );
+        assert_eq!(ids.unwrap().len(), 1);
+        assert_eq!(
+            task_list.notes,
+            ["Merged 2 arguments into one task 'eggs milk'; use --separate for individual tasks"]
+        );
+        let options = AddOptions {
+            separate: true,
+            ..Default::default()
+        };
+        let ids = process_add(&mut task_list, names, &options, &DisplayOptions::default());
+        assert_eq!(ids.unwrap().len(), 2);
+        assert_eq!(task_list.notes.len(), 1);
+        drop(task_list);
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_add_sleeping() {
+        let db = __create_temp_db(0);
+        let before = Local::now();
+        let args: Arguments = Arguments::parse_from([
+            "ztask",
+            "--db",
+            &db,
+            "add",
+            "remind me",
+            "--sleep",
+            "1 week",
+        ]);
+        run(Some(args)).unwrap();
         let task_list = tasklist::TaskList::new(db.clone());
-        let mut iter = task_list.tasks.iter().skip(1);
-        let id = iter.next().unwrap().id.clone();
-        let args: Arguments = Arguments::parse_from(["ztask", "--db", &db, "-v", "del", &id]);
+        let task = task_list.tasks.peek().unwrap();
+        assert_eq!(task.status, TaskStatus::Sleeping);
+        let wake_at = task.wake_at.unwrap();
+        assert!(wake_at >= before + chrono::Duration::weeks(1));
+        assert!(wake_at <= Local::now() + chrono::Duration::weeks(1));
         drop(task_list);
-        println!("args: {:?}", args);
+
+        assert!(Arguments::try_parse_from(["ztask", "add", "x", "--sleep", "1d", "-i"]).is_err());
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_add_with_owner() {
+        let db = __create_temp_db(0);
+        let args: Arguments = Arguments::parse_from([
+            "ztask",
+            "--db",
+            &db,
+            "add",
+            "owned task",
+            "--owner",
+            "alice",
+        ]);
         run(Some(args)).unwrap();
+        let task_list = tasklist::TaskList::new(db.clone());
+        let task = task_list.tasks.peek().unwrap();
+        assert_eq!(task.owner, Some("alice".to_string()));
+        drop(task_list);
         __destroy_temp_db(db);
     }
 
     #[test]
-    fn verify_delete_nonexisting() {
+    fn verify_add_from_template() {
         let db = __create_temp_db(0);
-        let id = "invalid";
-        let args: Arguments = Arguments::parse_from(["ztask", "--db", &db, "-v", "del", id]);
-        println!("args: {:?}", args);
+        let templates = format!("{}.templates", db);
+        let args: Arguments = Arguments::parse_from([
+            "ztask",
+            "--db",
+            &db,
+            "add",
+            "Deploy checklist",
+            "--priority",
+            "2",
+            "--details",
+            "run the smoke tests",
+        ]);
+        run(Some(args)).unwrap();
+        let id = tasklist::TaskList::new(db.clone())
+            .tasks
+            .peek()
+            .unwrap()
+            .id
+            .clone();
+        let args: Arguments = Arguments::parse_from([
+            "ztask",
+            "--db",
+            &db,
+            "--templates",
+            &templates,
+            "template",
+            "--save",
+            "deploy",
+            "--from",
+            &id,
+        ]);
+        run(Some(args)).unwrap();
+
+        // The template fills in the fields, and the given summary overrides its own
+        let args: Arguments = Arguments::parse_from([
+            "ztask",
+            "--db",
+            &db,
+            "--templates",
+            &templates,
+            "add",
+            "--template",
+            "deploy",
+            "Deploy v2",
+        ]);
         run(Some(args)).unwrap();
+        let task_list = tasklist::TaskList::new(db.clone());
+        let task = task_list
+            .tasks
+            .iter()
+            .find(|t| t.summary == "Deploy v2")
+            .unwrap();
+        assert_eq!(task.priority, 2);
+        assert_eq!(task.details, "run the smoke tests");
+        drop(task_list);
+
+        let args: Arguments = Arguments::parse_from([
+            "ztask",
+            "--db",
+            &db,
+            "--templates",
+            &templates,
+            "add",
+            "--template",
+            "missing",
+        ]);
+        assert!(run(Some(args)).is_err());
+        std::fs::remove_dir_all(&templates).unwrap();
         __destroy_temp_db(db);
     }
 
-    // Tests for "edit"
+    #[test]
+    fn verify_add_with_details() {
+        let db = __create_temp_db(0);
+        let details = "first line\n  second line\n";
+        let args: Arguments = Arguments::parse_from([
+            "ztask",
+            "--db",
+            &db,
+            "add",
+            "detailed task",
+            "--details",
+            details,
+        ]);
+        run(Some(args)).unwrap();
+        let task_list = tasklist::TaskList::new(db.clone());
+        assert_eq!(task_list.tasks.peek().unwrap().details, details);
+        drop(task_list);
+        __destroy_temp_db(db);
+
+        let db = __create_temp_db(0);
+        let details_file = format!("{}.details", db);
+        std::fs::write(&details_file, details).unwrap();
+        let args: Arguments = Arguments::parse_from([
+            "ztask",
+            "--db",
+            &db,
+            "add",
+            "detailed task",
+            "--details-from",
+            &details_file,
+        ]);
+        run(Some(args)).unwrap();
+        let task_list = tasklist::TaskList::new(db.clone());
+        assert_eq!(task_list.tasks.peek().unwrap().details, details);
+        drop(task_list);
+        std::fs::remove_file(details_file).unwrap();
+        __destroy_temp_db(db);
+    }
 
     #[test]
-    fn verify_edit_default() {
+    fn verify_add_quick_add_tokens() {
         let db = __create_temp_db(0);
-        let args: Arguments = Arguments::parse_from(["ztask", "--db", &db, "-v", "edit"]);
-        println!("args: {:?}", args);
+        let args: Arguments = Arguments::parse_from([
+            "ztask",
+            "--db",
+            &db,
+            "add",
+            "fix login +urgent @work p:1 due:3d",
+        ]);
         run(Some(args)).unwrap();
+        let task_list = tasklist::TaskList::new(db.clone());
+        let task = task_list.tasks.peek().unwrap();
+        assert_eq!(task.summary, "fix login");
+        assert!(task.tags.contains("urgent"));
+        assert_eq!(task.category, "work");
+        assert_eq!(task.priority, 1);
+        assert!(task.due_at.is_some());
+        drop(task_list);
         __destroy_temp_db(db);
     }
 
     #[test]
-    fn verify_edit_single() {
+    fn verify_categories_group_ignoring_case() {
+        let db = __create_temp_db(0);
+        for name in ["report @Work", "slides @work"] {
+            let args: Arguments = Arguments::parse_from(["ztask", "--db", &db, "add", name]);
+            run(Some(args)).unwrap();
+        }
+        let mut task_list = tasklist::TaskList::new(db.clone());
+        assert!(task_list.tasks.iter().all(|task| task.category == "work"));
+
+        // Tasks stored before normalization still group together
+        task_list.add_task(Task::new("budget".to_string(), "WORK".to_string(), false));
+        let mut out = String::new();
+        print_task_list_by_category(
+            &mut out,
+            &task_list,
+            &Filter::default(),
+            &DisplayOptions::default(),
+        )
+        .unwrap();
+        let headings: Vec<&str> = out.lines().filter(|line| line.ends_with(':')).collect();
+        assert_eq!(headings.len(), 1);
+        assert!(out.lines().next().unwrap().contains("work"));
+        drop(task_list);
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_vec_backend() {
+        let db = __create_temp_db(3);
+        let first = tasklist::TaskList::new(db.clone()).tasks.sorted()[0]
+            .id
+            .clone();
+        let args: Arguments =
+            Arguments::parse_from(["ztask", "--db", &db, "--backend", "vec", "start"]);
+        run(Some(args)).unwrap();
+        let mut task_list = tasklist::TaskList::new(db.clone());
+        assert_eq!(task_list.num_tasks(), 3);
+        assert_eq!(
+            task_list.copy_task(first).unwrap().status,
+            TaskStatus::Active
+        );
+        drop(task_list);
+        __destroy_temp_db(db);
+        assert!(Arguments::try_parse_from(["ztask", "--backend", "btree", "list"]).is_err());
+    }
+
+    #[test]
+    fn verify_list_with_owner() {
+        let db = __create_temp_db(0);
+        let mut task_list = tasklist::TaskList::new(db.clone());
+        for owner in [Some("alice"), Some("bob"), None] {
+            let mut task = Task::builder()
+                .summary(format!("owned by {}", owner.unwrap_or("nobody")))
+                .build();
+            task.owner = owner.map(str::to_string);
+            task_list.add_task(task);
+        }
+        let filter = Filter {
+            owner: Some("bob".to_string()),
+            ..Default::default()
+        };
+        let mut out = String::new();
+        print_categorized_task_list(
+            &mut out,
+            &task_list,
+            0,
+            &filter,
+            &[],
+            &DisplayOptions::default(),
+        )
+        .unwrap();
+        assert!(out.contains("owned by bob"));
+        assert!(!out.contains("owned by alice") && !out.contains("owned by nobody"));
+        drop(task_list);
+
+        let args: Arguments =
+            Arguments::parse_from(["ztask", "--db", &db, "list", "--owner", "bob"]);
+        run(Some(args)).unwrap();
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_list_max_priority() {
+        let db = __create_temp_db(0);
+        let mut task_list = tasklist::TaskList::new(db.clone());
+        for priority in 1..=5 {
+            task_list.add_task(
+                Task::builder()
+                    .summary(format!("priority {priority}"))
+                    .priority(priority)
+                    .build(),
+            );
+        }
+        let filter = Filter {
+            max_priority: Some(2),
+            ..Default::default()
+        };
+        let mut out = String::new();
+        print_categorized_task_list(
+            &mut out,
+            &task_list,
+            0,
+            &filter,
+            &[],
+            &DisplayOptions::default(),
+        )
+        .unwrap();
+        assert!(out.contains("priority 1") && out.contains("priority 2"));
+        assert!((3..=5).all(|priority| !out.contains(&format!("priority {priority}"))));
+        drop(task_list);
+
+        let args: Arguments =
+            Arguments::parse_from(["ztask", "--db", &db, "list", "--max-priority", "high"]);
+        run(Some(args)).unwrap();
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_add_with_priority_word() {
+        let db = __create_temp_db(0);
+        let args: Arguments =
+            Arguments::parse_from(["ztask", "--db", &db, "add", "urgent thing", "-p", "high"]);
+        run(Some(args)).unwrap();
+        let task_list = tasklist::TaskList::new(db.clone());
+        assert_eq!(task_list.tasks.peek().unwrap().priority, 1);
+        drop(task_list);
+        __destroy_temp_db(db);
+
+        let result = Arguments::try_parse_from(["ztask", "add", "thing", "-p", "urgent"]);
+        assert!(result.is_err());
+        let result = Arguments::try_parse_from(["ztask", "edit", "abc", "-d", "-p", "high"]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn verify_edit_set_fields() {
         let db = __create_temp_db(2);
         let task_list = tasklist::TaskList::new(db.clone());
-        let mut iter = task_list.tasks.iter().skip(1);
-        let id = iter.next().unwrap().id.clone();
-        let args: Arguments = Arguments::parse_from(["ztask", "--db", &db, "-v", "edit", &id]);
+        let id = task_list.tasks.peek().unwrap().id.clone();
         drop(task_list);
-        println!("args: {:?}", args);
+        let args: Arguments = Arguments::parse_from([
+            "ztask",
+            "--db",
+            &db,
+            "edit",
+            &id,
+            "--set",
+            "priority=1",
+            "--set",
+            "status=backlog",
+        ]);
         run(Some(args)).unwrap();
+        let mut task_list = tasklist::TaskList::new(db.clone());
+        let task = task_list.copy_task(id).unwrap();
+        assert_eq!(task.priority, 1);
+        assert_eq!(task.status, TaskStatus::Backlog);
+        drop(task_list);
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_set_fields_rejects_unknown_key() {
+        let db = __create_temp_db(1);
+        let mut task_list = tasklist::TaskList::new(db.clone());
+        let id = task_list.tasks.peek().unwrap().id.clone();
+        let result = process_set_fields(
+            &mut task_list,
+            vec![id.clone()],
+            vec!["colour=red".to_string()],
+        );
+        assert!(result.is_err());
+        let result = process_set_fields(&mut task_list, vec![id], vec!["priority".to_string()]);
+        assert!(result.is_err());
+        drop(task_list);
         __destroy_temp_db(db);
     }
+
+    #[test]
+    fn verify_set_conflicts_with_details_only() {
+        assert!(Arguments::try_parse_from(["ztask", "edit", "--set", "priority=1", "-d"]).is_err());
+    }
+
+    #[test]
+    fn verify_sleeping_sorted_by_wake_time() {
+        let now = Local::now();
+        let mut tasks: Vec<Task> = [Some(3), None, Some(1), Some(2)]
+            .iter()
+            .map(|hours| {
+                let mut task = Task::new(format!("{:?}", hours), "quick".to_string(), false);
+                task.status = TaskStatus::Sleeping;
+                task.wake_at = hours.map(|h| now + chrono::Duration::hours(h));
+                task
+            })
+            .collect();
+
+        sort_by_wake_time(&mut tasks);
+        let order: Vec<&str> = tasks.iter().map(|task| task.summary.as_str()).collect();
+        assert_eq!(order, ["Some(1)", "Some(2)", "Some(3)", "None"]);
+    }
+
+    #[test]
+    fn verify_completed_sorted_by_completion_time() {
+        let db = __create_temp_db(3);
+        let mut task_list = tasklist::TaskList::new(db.clone());
+        let ids: Vec<String> = task_list
+            .tasks
+            .sorted()
+            .into_iter()
+            .map(|task| task.id)
+            .collect();
+        __backdate_tasks(
+            &mut task_list,
+            Local.with_ymd_and_hms(2024, 1, 1, 9, 0, 0).unwrap(),
+        );
+        // Complete the newest task first and the oldest task last
+        let finished = Local.with_ymd_and_hms(2024, 6, 1, 9, 0, 0).unwrap();
+        for (hours, id) in ids.iter().rev().enumerate() {
+            let at = finished + chrono::Duration::hours(hours as i64);
+            assert_eq!(task_list.complete_task_at(id.clone(), at), 1);
+        }
+
+        let completed = section_tasks(
+            &task_list,
+            &Filter::default().with_status(TaskStatus::Completed),
+        );
+        let order: Vec<String> = completed.into_iter().map(|task| task.id).collect();
+        assert_eq!(order, ids);
+        drop(task_list);
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_date_format() {
+        let date = Local.with_ymd_and_hms(2024, 6, 1, 17, 5, 0).unwrap();
+        let display = DisplayOptions::default();
+        assert_eq!(display.format_date(&date), "2024-06-01");
+
+        let display = DisplayOptions {
+            date_format: "%d/%m/%Y".to_string(),
+            ..Default::default()
+        };
+        assert!(validate_date_format(&display.date_format).is_ok());
+        assert_eq!(display.format_date(&date), "01/06/2024");
+        assert_eq!(display.format_datetime(&date), "01/06/2024 17:05:00");
+    }
+
+    #[test]
+    fn verify_invalid_date_format() {
+        assert!(validate_date_format("%Q").is_err());
+        let db = __create_temp_db(1);
+        let args: Arguments =
+            Arguments::parse_from(["ztask", "--db", &db, "--date-format", "%Q", "list"]);
+        assert!(run(Some(args)).is_err());
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_pager_mode() {
+        assert_eq!(
+            PagerMode::from_flags(ColorMode::Auto, false, false),
+            PagerMode::Auto
+        );
+        assert_eq!(
+            PagerMode::from_flags(ColorMode::Auto, true, false),
+            PagerMode::Always
+        );
+        assert_eq!(
+            PagerMode::from_flags(ColorMode::Auto, false, true),
+            PagerMode::Never
+        );
+        assert_eq!(
+            PagerMode::from_flags(ColorMode::Never, true, false),
+            PagerMode::Never
+        );
+    }
+
+    #[test]
+    fn verify_list_with_pager() {
+        // stdout isn't a terminal under test, so this prints directly
+        let db = __create_temp_db(3);
+        let args: Arguments = Arguments::parse_from(["ztask", "--db", &db, "list", "--pager"]);
+        run(Some(args)).unwrap();
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_list_by_category() {
+        let db = __create_temp_db(0);
+        let mut task_list = tasklist::TaskList::new(db.clone());
+        for (summary, category) in [
+            ("fix sink", "home"),
+            ("file report", "work"),
+            ("call mom", "family"),
+            ("mow lawn", "home"),
+        ] {
+            task_list.add_task(Task::new(summary.to_string(), category.to_string(), false));
+        }
+        let mut done = Task::new("old chore".to_string(), "home".to_string(), false);
+        done.status = TaskStatus::Completed;
+        task_list.add_task(done);
+
+        let mut out = String::new();
+        print_task_list_by_category(
+            &mut out,
+            &task_list,
+            &Filter::default(),
+            &DisplayOptions::default(),
+        )
+        .unwrap();
+        let family = out.find("family").unwrap();
+        let home = out.find("home").unwrap();
+        let work = out.find("work").unwrap();
+        assert!(family < home && home < work);
+        assert!(out.find("fix sink").unwrap() > home);
+        assert!(out.find("mow lawn").unwrap() < work);
+        assert!(!out.contains("old chore"));
+
+        drop(task_list);
+        let args: Arguments =
+            Arguments::parse_from(["ztask", "--db", &db, "list", "--group-by", "category"]);
+        run(Some(args)).unwrap();
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_categorized_list_output() {
+        let db = __create_temp_db(2);
+        let task_list = tasklist::TaskList::new(db.clone());
+        let mut out = String::new();
+        print_categorized_task_list(
+            &mut out,
+            &task_list,
+            0,
+            &Filter::default(),
+            &[],
+            &DisplayOptions::default(),
+        )
+        .unwrap();
+        assert!(out.contains("Active Tasks"));
+        assert!(out.contains("test task 0"));
+        assert!(out.contains("test task 1"));
+        drop(task_list);
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_list_shows_current_task() {
+        let db = __create_temp_db(0);
+        let mut task_list = tasklist::TaskList::new(db.clone());
+        task_list.add_task(Task::new("waiting".to_string(), "quick".to_string(), false));
+        let render = |task_list: &tasklist::TaskList| {
+            let mut out = String::new();
+            print_categorized_task_list(
+                &mut out,
+                task_list,
+                0,
+                &Filter::default(),
+                &[],
+                &DisplayOptions::default(),
+            )
+            .unwrap();
+            out
+        };
+        // Without an active task there's no header
+        assert!(!render(&task_list).contains("Currently working on"));
+
+        let mut task = Task::new("writing".to_string(), "quick".to_string(), true);
+        task.last_started_at = Some(Local::now() - chrono::Duration::minutes(90));
+        task_list.add_task(task);
+        let out = render(&task_list);
+        assert!(out.starts_with("▶ Currently working on: writing (1h 30m)\n"));
+        assert!(out.find("Active Tasks").unwrap() > out.find("Currently").unwrap());
+        drop(task_list);
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_humanize_duration() {
+        assert_eq!(humanize_duration(0), "");
+        assert_eq!(humanize_duration(59), "59s");
+        assert_eq!(humanize_duration(-3600), "1h");
+        assert_eq!(
+            humanize_duration(2 * 86400 + 3 * 3600 + 15 * 60),
+            "2d 3h 15m"
+        );
+    }
+
+    #[test]
+    fn verify_describe_wake_at() {
+        let display = DisplayOptions::default();
+        let now = Local.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap();
+
+        let soon = now + chrono::Duration::hours(26);
+        assert_eq!(
+            describe_wake_at(&soon, &now, &display),
+            "2024-06-02 14:00:00 (1d 2h)"
+        );
+        let late = now - chrono::Duration::minutes(5);
+        assert_eq!(
+            describe_wake_at(&late, &now, &display),
+            "2024-06-01 11:55:00 (overdue by 5m)"
+        );
+
+        let far = now + chrono::Duration::days(400);
+        assert_eq!(
+            describe_wake_at(&far, &now, &display),
+            "2025-07-06 (in 13 months)"
+        );
+        let long_overdue = now - chrono::Duration::days(400);
+        assert_eq!(
+            describe_wake_at(&long_overdue, &now, &display),
+            "2023-04-28 (overdue by 13 months)"
+        );
+    }
+
+    // Tests for "show"
+
+    #[test]
+    fn verify_show_no_auto_start() {
+        let db = __create_temp_db(0);
+        let mut task_list = tasklist::TaskList::new(db.clone());
+        task_list.add_task(Task::new("waiting".to_string(), "quick".to_string(), false));
+        drop(task_list);
+
+        let args: Arguments =
+            Arguments::parse_from(["ztask", "--db", &db, "show", "--no-auto-start"]);
+        run(Some(args)).unwrap();
+        let args: Arguments = Arguments::parse_from(["ztask", "--db", &db, "--no-auto-start"]);
+        run(Some(args)).unwrap();
+        let task_list = tasklist::TaskList::new(db.clone());
+        assert_eq!(task_list.num_active(), 0);
+        drop(task_list);
+
+        let args: Arguments = Arguments::parse_from(["ztask", "--db", &db, "show"]);
+        run(Some(args)).unwrap();
+        let task_list = tasklist::TaskList::new(db.clone());
+        assert_eq!(task_list.num_active(), 1);
+        drop(task_list);
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_bare_invocation_on_empty_db() {
+        let db = __create_temp_db(0);
+        let mut task_list = tasklist::TaskList::new(db.clone());
+        let display = DisplayOptions::default();
+        assert_eq!(
+            process_default(&mut task_list, 0, true, &display).unwrap(),
+            0
+        );
+        assert_eq!(
+            task_list.notes,
+            ["no tasks to show; add one with 'ztask add <summary>'"]
+        );
+
+        // Only an empty database gets the hint
+        task_list.add_task(Task::new("waiting".to_string(), "quick".to_string(), false));
+        task_list.notes.clear();
+        assert_eq!(
+            process_default(&mut task_list, 0, false, &display).unwrap(),
+            0
+        );
+        assert!(task_list.notes.is_empty());
+        drop(task_list);
+
+        let args: Arguments = Arguments::parse_from(["ztask", "--db", &db]);
+        run(Some(args)).unwrap();
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_show_status() {
+        let db = __create_temp_db(3);
+        let task_list = tasklist::TaskList::new(db.clone());
+        let ids: Vec<String> = task_list.tasks.iter().map(|t| t.id.clone()).collect();
+        drop(task_list);
+
+        let args: Arguments = Arguments::parse_from([
+            "ztask",
+            "--db",
+            &db,
+            "sleep",
+            "--duration",
+            "1d",
+            &ids[0],
+            &ids[1],
+        ]);
+        run(Some(args)).unwrap();
+        let args: Arguments =
+            Arguments::parse_from(["ztask", "--db", &db, "show", "--status", "sleeping"]);
+        run(Some(args)).unwrap();
+
+        let mut task_list = tasklist::TaskList::new(db.clone());
+        let display = DisplayOptions::default();
+        let count = process_show(
+            &mut task_list,
+            0,
+            vec![],
+            Some(TaskStatus::Sleeping),
+            false,
+            false,
+            &display,
+        )
+        .unwrap();
+        assert_eq!(count, 2);
+        drop(task_list);
+
+        assert!(
+            Arguments::try_parse_from(["ztask", "--db", &db, "show", "--status", "done"]).is_err()
+        );
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_list_tree() {
+        let db = __create_temp_db(0);
+        let mut task_list = tasklist::TaskList::new(db.clone());
+        let mut ids = vec![];
+        for name in ["design", "build", "ship", "party"] {
+            let task = Task::new(name.to_string(), "quick".to_string(), false);
+            ids.push(task.id.clone());
+            task_list.add_task(task);
+        }
+        // ship is blocked on build, which is blocked on design
+        task_list.block_task_on(&ids[1], &ids[0]);
+        task_list.block_task_on(&ids[2], &ids[1]);
+
+        let mut out = String::new();
+        print_task_tree(
+            &mut out,
+            &task_list,
+            &Filter::default(),
+            &DisplayOptions::default(),
+        )
+        .unwrap();
+        let depth_of = |name: &str| {
+            let line = out.lines().find(|line| line.contains(name)).unwrap();
+            (line.len() - line.trim_start().len() - 2) / 2
+        };
+        assert_eq!(out.lines().count(), 4);
+        assert_eq!(depth_of("design"), 0);
+        assert_eq!(depth_of("build"), 1);
+        assert_eq!(depth_of("ship"), 2);
+        assert_eq!(depth_of("party"), 0);
+        // Blockers are shown on the blocked task's line
+        let ship = out.lines().find(|line| line.contains("ship")).unwrap();
+        assert!(ship.contains(&ids[1][..9]));
+
+        // A cycle is cut off rather than recursing forever
+        task_list.block_task_on(&ids[0], &ids[2]);
+        let mut out = String::new();
+        print_task_tree(
+            &mut out,
+            &task_list,
+            &Filter::default(),
+            &DisplayOptions::default(),
+        )
+        .unwrap();
+        assert!(out.contains("(cycle)"));
+        drop(task_list);
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_list_only_ids() {
+        let db = __create_temp_db(1);
+        let args: Arguments =
+            Arguments::parse_from(["ztask", "--db", &db, "add", "second task", "third task"]);
+        run(Some(args)).unwrap();
+        let task_list = tasklist::TaskList::new(db.clone());
+        let filter = Filter {
+            status: Some(TaskStatus::Backlog),
+            ..Default::default()
+        };
+        let mut out = String::new();
+        print_ids(&mut out, listing_order(&task_list, &filter)).unwrap();
+        let mut listed: Vec<&str> = out.lines().collect();
+        listed.sort();
+        let mut backlog: Vec<&str> = task_list
+            .tasks
+            .iter()
+            .filter(|task| task.status == TaskStatus::Backlog)
+            .map(|task| task.id.as_str())
+            .collect();
+        backlog.sort();
+        assert_eq!(listed, backlog);
+        assert_eq!(out.lines().count(), 2);
+        assert!(out.ends_with('\n'));
+        drop(task_list);
+
+        let args: Arguments = Arguments::parse_from([
+            "ztask",
+            "--db",
+            &db,
+            "list",
+            "--status",
+            "backlog",
+            "--only-ids",
+        ]);
+        run(Some(args)).unwrap();
+        let args: Arguments =
+            Arguments::parse_from(["ztask", "--db", &db, "search", "--only-ids", "task"]);
+        run(Some(args)).unwrap();
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_list_only_ids_count() {
+        let db = __create_temp_db(1);
+        let mut task_list = tasklist::TaskList::new(db.clone());
+        for name in ["second task", "third task"] {
+            task_list.add_task(Task::new(name.to_string(), "quick".to_string(), false));
+        }
+        let options = ListOptions {
+            filter: Filter::default().with_status(TaskStatus::Backlog),
+            group_by: GroupBy::Status,
+            numbered: false,
+            tree: false,
+            only_ids: true,
+            due_soon: false,
+            today: false,
+        };
+        // The count is of the ids printed, not of every task in the list
+        let count = process_list(
+            &mut task_list,
+            1,
+            true,
+            &options,
+            &DisplayOptions::default(),
+            PagerMode::Never,
+        )
+        .unwrap();
+        assert_eq!(count, 2);
+        drop(task_list);
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_list_counts_tasks_shown() {
+        let db = __create_temp_db(0);
+        let mut task_list = tasklist::TaskList::new(db.clone());
+        for priority in [1, 3, 5] {
+            task_list.add_task(Task::builder().priority(priority).build());
+        }
+        let mut options = ListOptions {
+            filter: Filter {
+                max_priority: Some(2),
+                ..Default::default()
+            },
+            group_by: GroupBy::Status,
+            numbered: false,
+            tree: false,
+            only_ids: false,
+            due_soon: false,
+            today: false,
+        };
+        let count = |task_list: &mut tasklist::TaskList, options: &ListOptions| {
+            process_list(
+                task_list,
+                0,
+                true,
+                options,
+                &DisplayOptions::default(),
+                PagerMode::Never,
+            )
+            .unwrap()
+        };
+        assert_eq!(count(&mut task_list, &options), 1);
+        options.tree = true;
+        assert_eq!(count(&mut task_list, &options), 1);
+        options.tree = false;
+        options.group_by = GroupBy::Category;
+        assert_eq!(count(&mut task_list, &options), 1);
+
+        // Nothing is active, due or scheduled today
+        options.filter = Filter::default();
+        options.today = true;
+        assert_eq!(count(&mut task_list, &options), 0);
+        drop(task_list);
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_list_due_soon() {
+        let db = __create_temp_db(1);
+        let mut task_list = tasklist::TaskList::new(db.clone());
+        let now = Local.with_ymd_and_hms(2030, 6, 1, 12, 0, 0).unwrap();
+        for (summary, due_in_hours, status) in [
+            ("later", 48, TaskStatus::Backlog),
+            ("overdue", -3, TaskStatus::Blocked),
+            ("soon", 2, TaskStatus::Backlog),
+            ("finished", 1, TaskStatus::Completed),
+        ] {
+            task_list.add_task(
+                Task::builder()
+                    .summary(summary)
+                    .status(status)
+                    .due(now + chrono::Duration::hours(due_in_hours))
+                    .build(),
+            );
+        }
+
+        let mut out = String::new();
+        print_due_soon(
+            &mut out,
+            &task_list,
+            &Filter::default(),
+            &now,
+            &DisplayOptions::default(),
+        )
+        .unwrap();
+        let summaries: Vec<&str> = out
+            .lines()
+            .map(|line| line.split_whitespace().last().unwrap())
+            .collect();
+        assert_eq!(summaries, ["overdue", "soon", "later"]);
+        assert!(out.lines().next().unwrap().contains("(overdue by 3h)"));
+        assert!(out.contains("2030-06-01 14:00:00 (2h)"));
+        drop(task_list);
+
+        let args: Arguments = Arguments::parse_from(["ztask", "--db", &db, "list", "--due-soon"]);
+        run(Some(args)).unwrap();
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_list_today() {
+        let db = __create_temp_db(1);
+        let mut task_list = tasklist::TaskList::new(db.clone());
+        let now = Local.with_ymd_and_hms(2030, 6, 1, 12, 0, 0).unwrap();
+        for (summary, due_at) in [
+            ("due today", Some(now + chrono::Duration::hours(6))),
+            ("due tomorrow", Some(now + chrono::Duration::days(1))),
+            ("no due date", None),
+        ] {
+            let mut task = Task::builder().summary(summary).build();
+            task.due_at = due_at;
+            task_list.add_task(task);
+        }
+
+        let mut out = String::new();
+        print_today(
+            &mut out,
+            &task_list,
+            &Filter::default(),
+            &now,
+            &DisplayOptions::default(),
+        )
+        .unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].contains("test task 0"));
+        assert!(lines[1].contains("due today"));
+        drop(task_list);
+
+        let args: Arguments = Arguments::parse_from(["ztask", "--db", &db, "list", "--today"]);
+        run(Some(args)).unwrap();
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_group_blocked_under_blocker() {
+        let db = __create_temp_db(0);
+        let mut task_list = tasklist::TaskList::new(db.clone());
+        let mut ids = vec![];
+        for name in ["blocker", "first dependent", "second dependent"] {
+            ids.push(task_list.add_task(Task::new(name.to_string(), "quick".to_string(), false)));
+        }
+        task_list.block_task_on(&ids[1], &ids[0]);
+        task_list.block_task_on(&ids[2], &ids[0]);
+
+        let mut out = String::new();
+        print_task_tree(
+            &mut out,
+            &task_list,
+            &Filter::default(),
+            &DisplayOptions::default(),
+        )
+        .unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("  ") && lines[0].contains("blocker"));
+        for line in &lines[1..] {
+            assert!(line.starts_with("    ") && line.contains("dependent"));
+        }
+        drop(task_list);
+
+        let args: Arguments = Arguments::parse_from([
+            "ztask",
+            "--db",
+            &db,
+            "list",
+            "--group-blocked-under-blocker",
+        ]);
+        run(Some(args)).unwrap();
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_list_empty_db() {
+        let db = __create_temp_db(0);
+        let task_list = tasklist::TaskList::new(db.clone());
+        let mut out = String::new();
+        print_categorized_task_list(
+            &mut out,
+            &task_list,
+            0,
+            &Filter::default(),
+            &[],
+            &DisplayOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(out, "No tasks. Add one with 'ztask add <summary>'.\n");
+        drop(task_list);
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_sleep_default_task() {
+        let db = __create_temp_db(1);
+        let mut task_list = tasklist::TaskList::new(db.clone());
+        let id = task_list.tasks.peek().unwrap().id.clone();
+        let before = Local::now();
+        let count = process_sleep(
+            &mut task_list,
+            vec![],
+            chrono::Duration::hours(2),
+            None,
+            &DisplayOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(count, 1);
+
+        let task = task_list.copy_task(id).unwrap();
+        assert_eq!(task.status, TaskStatus::Sleeping);
+        let wake_at = task.wake_at.unwrap();
+        assert!(wake_at >= before + chrono::Duration::hours(2));
+        assert!(wake_at <= Local::now() + chrono::Duration::hours(2));
+        drop(task_list);
+        __destroy_temp_db(db);
+
+        let result = Arguments::try_parse_from(["ztask", "sleep", "-d", "bogus"]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn verify_sleep_until_task() {
+        let db = __create_temp_db(2);
+        let task_list = tasklist::TaskList::new(db.clone());
+        let ids: Vec<String> = task_list.tasks.iter().map(|t| t.id.clone()).collect();
+        drop(task_list);
+
+        let args: Arguments = Arguments::parse_from([
+            "ztask",
+            "--db",
+            &db,
+            "sleep",
+            &ids[0],
+            "--until-task",
+            &ids[1],
+        ]);
+        run(Some(args)).unwrap();
+        let mut task_list = tasklist::TaskList::new(db.clone());
+        let task = task_list.copy_task(ids[0].clone()).unwrap();
+        assert_eq!(task.status, TaskStatus::Blocked);
+        assert_eq!(task.wake_at, None);
+        assert!(task.blocked_by.contains(&ids[1]));
+
+        // Completing the blocker wakes the task
+        task_list.complete_task_at(ids[1].clone(), Local::now());
+        task_list.unblock_tasks();
+        let task = task_list.copy_task(ids[0].clone()).unwrap();
+        assert_eq!(task.status, TaskStatus::Backlog);
+        drop(task_list);
+
+        assert!(Arguments::try_parse_from(["ztask", "sleep", "abc"]).is_err());
+        assert!(
+            Arguments::try_parse_from(["ztask", "sleep", "-d", "2h", "--until-task", "abc"])
+                .is_err()
+        );
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_schedule() {
+        let db = __create_temp_db(1);
+        let task_list = tasklist::TaskList::new(db.clone());
+        let id = task_list.tasks.peek().unwrap().id.clone();
+        drop(task_list);
+
+        let args: Arguments =
+            Arguments::parse_from(["ztask", "--db", &db, "schedule", &id, "2099-01-01 09:00"]);
+        run(Some(args)).unwrap();
+        let args: Arguments = Arguments::parse_from(["ztask", "--db", &db, "list"]);
+        run(Some(args)).unwrap();
+
+        let mut task_list = tasklist::TaskList::new(db.clone());
+        let task = task_list.copy_task(id).unwrap();
+        assert_eq!(task.status, TaskStatus::Scheduled);
+        let mut out = String::new();
+        print_categorized_task_list(
+            &mut out,
+            &task_list,
+            0,
+            &Filter::default(),
+            &[],
+            &DisplayOptions::default(),
+        )
+        .unwrap();
+        assert!(out.contains("Scheduled Tasks"));
+        drop(task_list);
+
+        assert!(Arguments::try_parse_from(["ztask", "schedule", "abc", "someday"]).is_err());
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_export_fields() {
+        let db = __create_temp_db(2);
+        let task_list = tasklist::TaskList::new(db.clone());
+        let fields = ["id".to_string(), "summary".to_string()];
+        let exported =
+            export_tasks(&task_list, &Filter::default(), ExportFormat::Jsonl, &fields).unwrap();
+        for line in exported.lines() {
+            let task: serde_json::Value = serde_json::from_str(line).unwrap();
+            let keys: Vec<&String> = task.as_object().unwrap().keys().collect();
+            assert_eq!(keys, ["id", "summary"]);
+        }
+
+        let exported =
+            export_tasks(&task_list, &Filter::default(), ExportFormat::Json, &fields).unwrap();
+        let tasks: Vec<serde_json::Value> = serde_json::from_str(&exported).unwrap();
+        assert_eq!(tasks.len(), 2);
+        assert!(tasks.iter().all(|task| task.get("details").is_none()));
+
+        let unknown = ["colour".to_string()];
+        let error = export_tasks(&task_list, &Filter::default(), ExportFormat::Json, &unknown);
+        assert_eq!(error.unwrap_err().to_string(), "Unknown field 'colour'");
+        assert!(export_tasks(&task_list, &Filter::default(), ExportFormat::Tsv, &fields).is_err());
+        drop(task_list);
+
+        let args: Arguments = Arguments::parse_from([
+            "ztask", "--db", &db, "export", "--field", "id", "--field", "status",
+        ]);
+        run(Some(args)).unwrap();
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_export_jsonl() {
+        let db = __create_temp_db(3);
+        let task_list = tasklist::TaskList::new(db.clone());
+        let exported =
+            export_tasks(&task_list, &Filter::default(), ExportFormat::Jsonl, &[]).unwrap();
+        assert!(!exported.ends_with("\n\n"));
+        let lines: Vec<&str> = exported.lines().collect();
+        assert_eq!(lines.len(), task_list.num_tasks());
+        // Each line parses on its own, and the tasks come out in sorted order
+        let tasks: Vec<Task> = lines
+            .iter()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let sorted = task_list.tasks.sorted();
+        assert!(tasks.iter().zip(&sorted).all(|(a, b)| a.id == b.id));
+
+        let exported =
+            export_tasks(&task_list, &Filter::default(), ExportFormat::Json, &[]).unwrap();
+        let tasks: Vec<Task> = serde_json::from_str(&exported).unwrap();
+        assert_eq!(tasks.len(), 3);
+        drop(task_list);
+
+        let args: Arguments =
+            Arguments::parse_from(["ztask", "--db", &db, "export", "--format", "jsonl"]);
+        run(Some(args)).unwrap();
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_export_ical() {
+        let db = __create_temp_db(2);
+        let mut task_list = tasklist::TaskList::new(db.clone());
+        let mut due = Task::new("File taxes; soon".to_string(), "quick".to_string(), false);
+        due.due_at = Some(Local.with_ymd_and_hms(2030, 4, 15, 9, 0, 0).unwrap());
+        let id = task_list.add_task(due);
+
+        let exported =
+            export_tasks(&task_list, &Filter::default(), ExportFormat::Ical, &[]).unwrap();
+        assert!(exported.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+        assert!(exported.ends_with("END:VCALENDAR\r\n"));
+        assert!(!exported.replace("\r\n", "").contains('\n'));
+        // Only the task with a due date is exported
+        assert_eq!(exported.matches("BEGIN:VTODO").count(), 1);
+        assert!(exported.contains(&format!("\r\nUID:{}\r\n", id)));
+        assert!(exported.contains("SUMMARY:File taxes\\; soon\r\n"));
+        assert!(exported.contains("STATUS:NEEDS-ACTION\r\n"));
+        let due = Local.with_ymd_and_hms(2030, 4, 15, 9, 0, 0).unwrap();
+        assert!(exported.contains(&format!(
+            "DUE:{}\r\n",
+            due.naive_utc().format("%Y%m%dT%H%M%SZ")
+        )));
+        drop(task_list);
+
+        let args: Arguments =
+            Arguments::parse_from(["ztask", "--db", &db, "export", "--format", "ical"]);
+        run(Some(args)).unwrap();
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_export_ical_folds_long_lines() {
+        let mut task = Task::new(
+            "é".repeat(30) + " and a plain ascii tail",
+            "quick".to_string(),
+            false,
+        );
+        task.summary.push_str("\r\nsecond line\rthird");
+        task.due_at = Some(Local.with_ymd_and_hms(2030, 4, 15, 9, 0, 0).unwrap());
+        let exported = export_ical(&[task.clone()]);
+
+        let lines: Vec<&str> = exported.split("\r\n").collect();
+        assert!(lines.iter().all(|line| line.len() <= ICAL_LINE_LIMIT));
+        let start = lines
+            .iter()
+            .position(|line| line.starts_with("SUMMARY:"))
+            .unwrap();
+        assert!(lines[start + 1].starts_with(' '));
+        // Unfolding gives back the escaped summary, with every é intact
+        let mut summary = lines[start].to_string();
+        for line in &lines[start + 1..] {
+            match line.strip_prefix(' ') {
+                Some(rest) => summary.push_str(rest),
+                None => break,
+            }
+        }
+        assert_eq!(
+            summary,
+            format!(
+                "SUMMARY:{} and a plain ascii tail\\nsecond line\\nthird",
+                "é".repeat(30)
+            )
+        );
+        assert!(!exported.replace("\r\n", "").contains(['\r', '\n']));
+    }
+
+    #[test]
+    fn verify_export_tsv() {
+        let db = __create_temp_db(1);
+        let mut task_list = tasklist::TaskList::new(db.clone());
+        let mut task = Task::new("Pay\trent\nmonthly".to_string(), "home".to_string(), false);
+        task.due_at = Some(Local.with_ymd_and_hms(2030, 1, 31, 9, 0, 0).unwrap());
+        let id = task_list.add_task(task.clone());
+
+        let exported =
+            export_tasks(&task_list, &Filter::default(), ExportFormat::Tsv, &[]).unwrap();
+        let lines: Vec<&str> = exported.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(
+            lines[0],
+            "id\tstatus\tpriority\tcategory\tcreated_at\tdue_at\tsummary"
+        );
+        let row = lines.iter().find(|line| line.starts_with(&id)).unwrap();
+        let fields: Vec<&str> = row.split('\t').collect();
+        assert_eq!(fields.len(), TSV_COLUMNS.len());
+        assert_eq!(fields[1].parse(), Ok(task.status));
+        assert_eq!(fields[2].parse::<u8>().unwrap(), task.priority);
+        assert_eq!(fields[3], "home");
+        assert_eq!(parse_timestamp(fields[4]), Ok(task.created_at));
+        assert_eq!(parse_timestamp(fields[5]).ok(), task.due_at);
+        let summary = fields[6].replace("\\t", "\t").replace("\\n", "\n");
+        assert_eq!(summary, task.summary);
+        drop(task_list);
+
+        let args: Arguments =
+            Arguments::parse_from(["ztask", "--db", &db, "export", "--format", "tsv"]);
+        run(Some(args)).unwrap();
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_export_since() {
+        let db = __create_temp_db(3);
+        let mut task_list = tasklist::TaskList::new(db.clone());
+        let id = task_list.tasks.peek().unwrap().id.clone();
+        let since = Local::now();
+        assert_eq!(
+            __update_field(&mut task_list, id.clone(), "priority", "1"),
+            Ok(1)
+        );
+
+        let filter = Filter {
+            updated_after: Some(since),
+            ..Default::default()
+        };
+        let exported = export_tasks(&task_list, &filter, ExportFormat::Jsonl, &[]).unwrap();
+        let tasks: Vec<Task> = exported
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].id, id);
+        drop(task_list);
+
+        let since = since.to_rfc3339();
+        let args: Arguments =
+            Arguments::parse_from(["ztask", "--db", &db, "export", "--since", &since]);
+        run(Some(args)).unwrap();
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_parse_color() {
+        assert_eq!(parse_color("red"), Ok(Color::Red));
+        assert_eq!(parse_color("bright blue"), Ok(Color::BrightBlue));
+        assert_eq!(
+            parse_color("#ff8800"),
+            Ok(Color::TrueColor {
+                r: 255,
+                g: 136,
+                b: 0
+            })
+        );
+        assert!(parse_color("#ff88").is_err());
+        assert!(parse_color("#gg8800").is_err());
+        assert!(parse_color("mauve").is_err());
+    }
+
+    #[test]
+    fn verify_pager_failures() {
+        // A pager that quits without reading counts as having paged
+        let output = "line\n".repeat(100_000);
+        assert!(page_output(&output, "true").is_ok());
+        // Only a pager that can't be started falls back to stdout
+        assert!(page_output(&output, "no-such-pager-for-ztask").is_err());
+    }
+
+    #[test]
+    fn verify_category_color_names_are_normalized() {
+        let configured = BTreeMap::from([
+            (" Work ".to_string(), "#ff8800".to_string()),
+            ("home".to_string(), "green".to_string()),
+        ]);
+        let colors = parse_category_colors(&configured).unwrap();
+        assert_eq!(colors.keys().collect::<Vec<_>>(), ["home", "work"]);
+        let configured = BTreeMap::from([("work".to_string(), "mauve".to_string())]);
+        assert!(parse_category_colors(&configured).is_err());
+    }
+
+    #[test]
+    fn verify_category_color() {
+        let mut display = DisplayOptions::default();
+        display
+            .category_colors
+            .insert("work".to_string(), parse_color("#ff8800").unwrap());
+        let work = Task::new("Report".to_string(), "work".to_string(), false);
+        let home = Task::new("Garden".to_string(), "home".to_string(), false);
+
+        let _color_lock = COLOR_LOCK.lock().unwrap();
+        colored::control::set_override(true);
+        let mut out = String::new();
+        print_task_oneline(&mut out, &work, true, &display).unwrap();
+        print_task_oneline(&mut out, &home, true, &display).unwrap();
+        colored::control::unset_override();
+
+        let lines: Vec<&str> = out.lines().collect();
+        assert!(lines[0].contains("\x1b[38;2;255;136;0mReport"));
+        // Categories without a color keep the default coloring
+        assert!(lines[1].contains("\x1b[37mGarden"));
+    }
+
+    #[test]
+    fn verify_stale_backlog_tasks() {
+        let db = __create_temp_db(0);
+        let mut task_list = tasklist::TaskList::new(db.clone());
+        let mut old = Task::builder().summary("neglected").build();
+        old.created_at = Local::now() - chrono::Duration::weeks(5);
+        task_list.add_task(old);
+        task_list.add_task(Task::builder().summary("recent").build());
+        let display = DisplayOptions {
+            stale_after: Some(parse_estimate("3 weeks").unwrap()),
+            ..Default::default()
+        };
+
+        let _color_lock = COLOR_LOCK.lock().unwrap();
+        colored::control::set_override(true);
+        let mut out = String::new();
+        print_categorized_task_list(&mut out, &task_list, 0, &Filter::default(), &[], &display)
+            .unwrap();
+        colored::control::unset_override();
+
+        let stale_style = "\x1b[2;33m";
+        let line = |summary: &str| out.lines().find(|line| line.contains(summary)).unwrap();
+        assert!(line("neglected").contains(stale_style));
+        assert!(!line("recent").contains(stale_style));
+        drop(task_list);
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_no_color() {
+        let task = Task::new("Plain".to_string(), "quick".to_string(), true);
+        let render = || {
+            let mut out = String::new();
+            print_task_oneline(&mut out, &task, true, &DisplayOptions::default()).unwrap();
+            out
+        };
+        let _color_lock = COLOR_LOCK.lock().unwrap();
+        apply_color_mode(ColorMode::Auto, true);
+        assert!(!render().contains('\x1b'));
+        // An explicit --color always wins over NO_COLOR
+        apply_color_mode(ColorMode::Always, true);
+        assert!(render().contains('\x1b'));
+        colored::control::unset_override();
+    }
+
+    #[test]
+    fn verify_details_preview() {
+        let mut task = Task::builder().summary("Fix login").build();
+        task.details = "the login page\nreturns 500\n".to_string();
+        let display = DisplayOptions {
+            details_preview: Some(20),
+            ..Default::default()
+        };
+        assert_eq!(
+            display.details_preview(&task.details).as_deref(),
+            Some("the login page retur")
+        );
+        // Keep coloring from being turned on part way through
+        let _color_lock = COLOR_LOCK.lock().unwrap();
+        let mut out = String::new();
+        print_task_oneline(&mut out, &task, false, &display).unwrap();
+        assert!(out.ends_with("Fix login    the login page retur\n"));
+
+        task.details.clear();
+        assert_eq!(display.details_preview(&task.details), None);
+        assert_eq!(DisplayOptions::default().details_preview("details"), None);
+    }
+
+    #[test]
+    fn verify_show_compact() {
+        let mut task = Task::builder()
+            .id("0123456789abcdef")
+            .summary("Fix login")
+            .priority(2)
+            .build();
+        let display = DisplayOptions::default();
+        // Keep coloring from being turned on part way through
+        let _color_lock = COLOR_LOCK.lock().unwrap();
+        let mut out = String::new();
+        print_task_compact(&mut out, &task, &display).unwrap();
+        assert_eq!(
+            out,
+            format!("  {}  2  backlog  Fix login\n", short_id(&task.id))
+        );
+
+        task.details = format!("{}\n\nreturns 500\n", "word ".repeat(20));
+        let mut out = String::new();
+        print_task_compact(&mut out, &task, &display).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert!(lines[0].ends_with("Fix login"));
+        assert_eq!(lines[1], format!("    {}", ["word"; 15].join(" ")));
+        assert_eq!(lines[2], format!("    {}", ["word"; 5].join(" ")));
+        assert_eq!(lines[3], "");
+        assert_eq!(lines[4], "    returns 500");
+
+        assert_eq!(wrap_words("", 10), [""]);
+        assert_eq!(
+            wrap_words("a verylongword b", 5),
+            ["a", "verylongword", "b"]
+        );
+        assert!(Arguments::try_parse_from(["ztask", "show", "--compact"]).is_ok());
+        assert!(Arguments::try_parse_from(["ztask", "show", "--compact", "-v"]).is_err());
+    }
+
+    #[test]
+    fn verify_expand_env() {
+        let mut task = Task::builder().summary("Read $ZTASK_TEST_PROJECT").build();
+        task.details =
+            "See $ZTASK_TEST_PROJECT/docs\nand ${ZTASK_TEST_UNDEFINED}/notes".to_string();
+        let display = DisplayOptions {
+            expand_env: true,
+            env_var: |name| (name == "ZTASK_TEST_PROJECT").then(|| "/work/ztask".to_string()),
+            ..Default::default()
+        };
+        // Keep coloring from being turned on part way through
+        let _color_lock = COLOR_LOCK.lock().unwrap();
+        let mut out = String::new();
+        print_task_compact(&mut out, &task, &display).unwrap();
+        assert!(out.contains("Read /work/ztask\n"));
+        assert!(out.contains("See /work/ztask/docs\n"));
+        assert!(out.contains("and ${ZTASK_TEST_UNDEFINED}/notes\n"));
+
+        let mut out = String::new();
+        print_task_detailed(&mut out, &task, &display).unwrap();
+        assert!(out.contains("Read /work/ztask\n"));
+        assert!(out.contains("and ${ZTASK_TEST_UNDEFINED}/notes\n"));
+        assert_eq!(display.format_summary(&task.summary), "Read /work/ztask");
+
+        // Expansion is off by default, and never touches the task itself
+        let mut out = String::new();
+        print_task_compact(&mut out, &task, &DisplayOptions::default()).unwrap();
+        assert!(out.contains("See $ZTASK_TEST_PROJECT/docs\n"));
+        assert_eq!(task.summary, "Read $ZTASK_TEST_PROJECT");
+        assert!(Arguments::try_parse_from(["ztask", "list", "--expand-env"]).is_ok());
+    }
+
+    #[test]
+    fn verify_column_widths() {
+        let mut tasks = vec![
+            Task::builder().id("a1").build(),
+            Task::builder()
+                .id("a10")
+                .status(TaskStatus::Scheduled)
+                .build(),
+        ];
+        let display = DisplayOptions::default();
+        let columns = compute_columns(tasks.iter(), 80, &display);
+        assert_eq!(
+            columns,
+            Columns {
+                id: 3,
+                status: 9,
+                date: 10,
+                summary: 80 - 33,
+            }
+        );
+        // A narrow terminal still leaves room for some of the summary
+        assert_eq!(
+            compute_columns(tasks.iter(), 30, &display).summary,
+            MIN_SUMMARY_WIDTH
+        );
+        // Uuids are shown by their first 9 characters
+        tasks.push(Task::builder().build());
+        assert_eq!(compute_columns(tasks.iter(), 80, &display).id, 9);
+
+        let display = DisplayOptions {
+            columns: Some(compute_columns(tasks[..2].iter(), 60, &display)),
+            ..Default::default()
+        };
+        let mut out = String::new();
+        for task in &tasks[..2] {
+            print_task_oneline(&mut out, task, true, &display).unwrap();
+        }
+        let lines: Vec<&str> = out.lines().collect();
+        assert!(lines[0].starts_with("  a1   3  backlog    "));
+        assert!(lines[1].starts_with("  a10  3  scheduled  "));
+        let summary = "a summary that is much too long to fit in what is left";
+        assert_eq!(display.format_summary(summary).chars().count(), 60 - 33);
+    }
+
+    #[test]
+    fn verify_summary_width() {
+        let mut display = DisplayOptions::default();
+        assert_eq!(
+            display.format_summary("Crème brûlée recipe"),
+            "Crème brûlée recipe"
+        );
+
+        display.summary_width = Some(10);
+        assert_eq!(display.format_summary("Crème brûlée recipe"), "Crème brû…");
+        assert_eq!(display.format_summary("Crème"), "Crème");
+        assert_eq!(
+            display.format_summary("日本語のタスクです"),
+            "日本語のタスクです"
+        );
+        display.summary_width = Some(4);
+        assert_eq!(display.format_summary("日本語のタスクです"), "日本語…");
+
+        let task = Task::new(
+            "Crème brûlée recipe".to_string(),
+            "quick".to_string(),
+            false,
+        );
+        let mut out = String::new();
+        print_task_oneline(&mut out, &task, false, &display).unwrap();
+        assert!(out.contains("Crè…"));
+        let mut out = String::new();
+        print_task_detailed(&mut out, &task, &display).unwrap();
+        assert!(out.contains("Crème brûlée recipe"));
+    }
+
+    #[test]
+    fn verify_show_completed_timeline() {
+        let mut task = Task::new("Retrospective".to_string(), "quick".to_string(), false);
+        task.created_at = Local.with_ymd_and_hms(2024, 6, 1, 9, 0, 0).unwrap();
+        task.last_started_at = Some(Local.with_ymd_and_hms(2024, 6, 1, 10, 30, 0).unwrap());
+        task.completed_at = Some(Local.with_ymd_and_hms(2024, 6, 2, 12, 30, 0).unwrap());
+        task.status = TaskStatus::Completed;
+
+        let mut out = String::new();
+        print_task_detailed(&mut out, &task, &DisplayOptions::default()).unwrap();
+        let timeline: Vec<&str> = out
+            .lines()
+            .skip_while(|line| !line.contains("timeline:"))
+            .take(3)
+            .collect();
+        assert!(timeline[0].contains("2024-06-01 09:00:00  created"));
+        assert!(timeline[1].contains("2024-06-01 10:30:00  started  (+1h 30m)"));
+        assert!(timeline[2].contains("2024-06-02 12:30:00  completed  (+1d 2h)"));
+
+        // Open tasks keep the plain started line
+        task.completed_at = None;
+        task.status = TaskStatus::Active;
+        let mut out = String::new();
+        print_task_detailed(&mut out, &task, &DisplayOptions::default()).unwrap();
+        assert!(!out.contains("timeline:"));
+        assert!(out.contains("started:"));
+    }
+
+    #[test]
+    fn verify_details_layout() {
+        let mut task = Task::new("Layout".to_string(), "quick".to_string(), false);
+        task.details = "first line\n  indented second\n\nfourth\n\n".to_string();
+        let mut out = String::new();
+        print_task_detailed(&mut out, &task, &DisplayOptions::default()).unwrap();
+        let details: Vec<&str> = out
+            .lines()
+            .skip_while(|l| !l.contains("details:"))
+            .collect();
+        assert_eq!(
+            details,
+            [
+                "  details:    first line",
+                "                indented second",
+                "  ",
+                "              fourth",
+            ]
+        );
+        assert!(out.ends_with("fourth\n"));
+    }
+
+    #[test]
+    fn verify_show_format() {
+        let db = __create_temp_db(2);
+        let mut task_list = tasklist::TaskList::new(db.clone());
+        let mut ids: Vec<String> = task_list.tasks.iter().map(|t| t.id.clone()).collect();
+        ids.sort();
+
+        let json = serialize_shown_tasks(&task_list, vec![ids[0].clone()], None, ShowFormat::Json)
+            .unwrap();
+        let task: Task = serde_json::from_str(&json).unwrap();
+        assert_eq!(task.id, ids[0]);
+
+        let json = serialize_shown_tasks(&task_list, ids.clone(), None, ShowFormat::Json).unwrap();
+        let tasks: Vec<Task> = serde_json::from_str(&json).unwrap();
+        assert_eq!(tasks.len(), 2);
+        assert!(serialize_shown_tasks(
+            &task_list,
+            vec!["missing".to_string()],
+            None,
+            ShowFormat::Json
+        )
+        .is_err());
+
+        let mut task = task_list.copy_task(ids[0].clone()).unwrap();
+        task.summary = "Quote \"this\": now".to_string();
+        task.tags = BTreeSet::from(["a".to_string(), "b".to_string()]);
+        task_list.tasks.retain(|t| t.id != task.id);
+        task_list.tasks.push(task);
+        let yaml = serialize_shown_tasks(&task_list, vec![ids[0].clone()], None, ShowFormat::Yaml)
+            .unwrap();
+        assert!(yaml.contains("priority: 3\n"));
+        assert!(yaml.contains("tags:\n- a\n- b\n"));
+        assert!(yaml.contains("blocked_by: []\n"));
+        assert!(yaml.contains("owner: null\n"));
+        let shown: Task = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(shown.summary, "Quote \"this\": now");
+        assert_eq!(shown.id, ids[0]);
+        let yaml = serialize_shown_tasks(&task_list, ids.clone(), None, ShowFormat::Yaml).unwrap();
+        let shown: Vec<Task> = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(shown.len(), 2);
+
+        // An id matching several tasks is an error, not text in the output
+        let mut ambiguous = Task::new("twin".to_string(), "quick".to_string(), false);
+        ambiguous.id = format!("{}0", ids[0]);
+        task_list.tasks.push(ambiguous);
+        let prefix = ids[0][..ids[0].len() - 1].to_string();
+        let result = serialize_shown_tasks(&task_list, vec![prefix], None, ShowFormat::Yaml);
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("does not uniquely match"));
+        drop(task_list);
+
+        let args: Arguments =
+            Arguments::parse_from(["ztask", "--db", &db, "show", "--format", "json"]);
+        run(Some(args)).unwrap();
+        __destroy_temp_db(db);
+    }
+
+    // Tests for "search"
+
+    #[test]
+    fn verify_search_matches() {
+        let db = __create_temp_db(0);
+        let mut task_list = tasklist::TaskList::new(db.clone());
+        let mut task = Task::new("Fix login".to_string(), "quick".to_string(), false);
+        task.details = "the LOGIN page\nreturns 500".to_string();
+        task_list.add_task(task);
+        task_list.add_task(Task::new(
+            "Write docs".to_string(),
+            "quick".to_string(),
+            false,
+        ));
+
+        assert_eq!(
+            find_matching_tasks(&task_list, "login", false, true).len(),
+            1
+        );
+        assert_eq!(find_matching_tasks(&task_list, "500", false, true).len(), 1);
+        assert_eq!(
+            find_matching_tasks(&task_list, "LOGIN", false, true).len(),
+            1
+        );
+        assert_eq!(
+            find_matching_tasks(&task_list, "Login", true, true).len(),
+            1
+        );
+        assert_eq!(
+            find_matching_tasks(&task_list, "missing", true, true).len(),
+            0
+        );
+        drop(task_list);
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_search_summary_only() {
+        let db = __create_temp_db(0);
+        let mut task_list = tasklist::TaskList::new(db.clone());
+        let mut task = Task::new("Fix login".to_string(), "quick".to_string(), false);
+        task.details = "returns 500".to_string();
+        task_list.add_task(task);
+
+        assert_eq!(find_matching_tasks(&task_list, "500", false, true).len(), 1);
+        assert_eq!(
+            find_matching_tasks(&task_list, "500", false, false).len(),
+            0
+        );
+        assert_eq!(
+            find_matching_tasks(&task_list, "login", false, false).len(),
+            1
+        );
+        drop(task_list);
+
+        let args: Arguments =
+            Arguments::parse_from(["ztask", "--db", &db, "search", "--summary-only", "500"]);
+        run(Some(args)).unwrap();
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_search_limit() {
+        let db = __create_temp_db(0);
+        let mut task_list = tasklist::TaskList::new(db.clone());
+        for (name, priority) in [("low match", 5), ("top match", 1), ("mid match", 3)] {
+            let mut task = Task::new(name.to_string(), "quick".to_string(), false);
+            task.priority = priority;
+            task_list.add_task(task);
+        }
+        let tasks = find_matching_tasks(&task_list, "match", false, true);
+        let display = DisplayOptions::default();
+
+        let mut out = String::new();
+        print_matches(&mut out, &tasks, Some(2), &display).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].contains("top match"));
+        assert!(lines[1].contains("mid match"));
+        assert_eq!(lines[2], "… 1 more matches");
+
+        let mut out = String::new();
+        print_matches(&mut out, &tasks, Some(5), &display).unwrap();
+        assert_eq!(out.lines().count(), 3);
+        assert!(!out.contains("more matches"));
+        drop(task_list);
+
+        let args: Arguments =
+            Arguments::parse_from(["ztask", "--db", &db, "search", "--limit", "1", "match"]);
+        run(Some(args)).unwrap();
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_search_count_only() {
+        let db = __create_temp_db(3);
+        let task_list = tasklist::TaskList::new(db.clone());
+        assert_eq!(
+            process_search(
+                &task_list,
+                "test task",
+                &SearchOptions {
+                    include_details: true,
+                    count_only: true,
+                    ..Default::default()
+                },
+                &DisplayOptions::default()
+            )
+            .unwrap(),
+            3
+        );
+        drop(task_list);
+
+        let args: Arguments =
+            Arguments::parse_from(["ztask", "--db", &db, "search", "--count-only", "task", "1"]);
+        run(Some(args)).unwrap();
+        __destroy_temp_db(db);
+    }
+
+    // Tests for "start"
+
+    #[test]
+    fn verify_start_respects_max_active() {
+        let db = __create_temp_db(0);
+        let mut task_list = tasklist::TaskList::new(db.clone());
+        for i in 0..4 {
+            task_list.add_task(Task::new(
+                format!("backlog {i}"),
+                "quick".to_string(),
+                false,
+            ));
+        }
+        drop(task_list);
+
+        for _ in 0..4 {
+            let args: Arguments =
+                Arguments::parse_from(["ztask", "--db", &db, "--max-active", "3", "start"]);
+            run(Some(args)).unwrap();
+        }
+
+        let task_list = tasklist::TaskList::new(db.clone());
+        assert_eq!(task_list.num_active(), 3);
+        drop(task_list);
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_complete_count() {
+        let db = __create_temp_db(3);
+        let task_list = tasklist::TaskList::new(db.clone());
+        let newest = task_list
+            .filtered(&Filter::default().with_status(TaskStatus::Active))
+            .pop()
+            .unwrap();
+        drop(task_list);
+
+        let args: Arguments =
+            Arguments::parse_from(["ztask", "--db", &db, "complete", "--count", "2"]);
+        run(Some(args)).unwrap();
+
+        let task_list = tasklist::TaskList::new(db.clone());
+        assert_eq!(task_list.num_active(), 1);
+        let active = task_list.filtered(&Filter::default().with_status(TaskStatus::Active));
+        assert_eq!(active[0].id, newest.id);
+        drop(task_list);
+
+        assert!(Arguments::try_parse_from(["ztask", "complete", "abc", "--count", "2"]).is_err());
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_strict_batch() {
+        let db = __create_temp_db(2);
+        let task_list = tasklist::TaskList::new(db.clone());
+        let id = task_list.tasks.peek().unwrap().id.clone();
+        let ids = [id.clone(), "nomatch".to_string()];
+        assert_eq!(
+            check_ids_resolve(&task_list, &ids),
+            Err(
+                "Nothing changed, as not every id matches one task: 'nomatch' matches 0"
+                    .to_string()
+            )
+        );
+        assert_eq!(check_ids_resolve(&task_list, &ids[..1]), Ok(()));
+        drop(task_list);
+
+        let args: Arguments =
+            Arguments::parse_from(["ztask", "--db", &db, "--strict", "complete", &id, "nomatch"]);
+        assert!(run(Some(args)).is_err());
+        let mut task_list = tasklist::TaskList::new(db.clone());
+        assert_eq!(
+            task_list.copy_task(id.clone()).unwrap().status,
+            TaskStatus::Active
+        );
+        drop(task_list);
+
+        // Without --strict the valid id is still completed
+        let args: Arguments =
+            Arguments::parse_from(["ztask", "--db", &db, "complete", &id, "nomatch"]);
+        run(Some(args)).unwrap();
+        let mut task_list = tasklist::TaskList::new(db.clone());
+        assert_eq!(
+            task_list.copy_task(id).unwrap().status,
+            TaskStatus::Completed
+        );
+        drop(task_list);
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_complete_delete() {
+        let db = __create_temp_db(2);
+        let task_list = tasklist::TaskList::new(db.clone());
+        let ids: Vec<String> = task_list.tasks.iter().map(|t| t.id.clone()).collect();
+        drop(task_list);
+
+        let args: Arguments =
+            Arguments::parse_from(["ztask", "--db", &db, "complete", "--delete", &ids[0]]);
+        run(Some(args)).unwrap();
+
+        let task_list = tasklist::TaskList::new(db.clone());
+        assert_eq!(task_list.tasks.len(), 1);
+        assert!(task_list.matching(&ids[0]).is_empty());
+        assert!(task_list
+            .tasks
+            .iter()
+            .all(|task| task.status != TaskStatus::Completed));
+        drop(task_list);
+
+        assert!(Arguments::try_parse_from([
+            "ztask",
+            "complete",
+            "abc",
+            "--delete",
+            "--at",
+            "2024-01-01"
+        ])
+        .is_err());
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_complete_then_next() {
+        let db = __create_temp_db(1);
+        let mut task_list = tasklist::TaskList::new(db.clone());
+        let active = task_list.tasks.peek().unwrap().id.clone();
+        let next = Task::new("next up".to_string(), "quick".to_string(), false);
+        let next_id = next.id.clone();
+        task_list.add_task(next);
+        drop(task_list);
+
+        let args: Arguments =
+            Arguments::parse_from(["ztask", "--db", &db, "complete", "--then-next"]);
+        run(Some(args)).unwrap();
+
+        let mut task_list = tasklist::TaskList::new(db.clone());
+        assert_eq!(
+            task_list.copy_task(active).unwrap().status,
+            TaskStatus::Completed
+        );
+        assert_eq!(
+            task_list.copy_task(next_id).unwrap().status,
+            TaskStatus::Active
+        );
+        drop(task_list);
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_select_oldest_and_newest() {
+        let db = __create_temp_db(3);
+        let task_list = tasklist::TaskList::new(db.clone());
+        let mut tasks = task_list.tasks.sorted();
+        tasks.sort_by_key(|task| task.created_at);
+        let (oldest, newest) = (tasks[0].id.clone(), tasks[2].id.clone());
+        let oldest_only = AgeSelector {
+            oldest: true,
+            newest: false,
+        };
+        assert_eq!(
+            select_task_ids(&task_list, None, oldest_only, None),
+            Ok(vec![oldest.clone()])
+        );
+        assert_eq!(
+            select_task_ids(&task_list, None, oldest_only, Some(TaskStatus::Backlog)),
+            Err("No backlog tasks to select from".to_string())
+        );
+        drop(task_list);
+
+        let args: Arguments = Arguments::parse_from(["ztask", "--db", &db, "complete", "--newest"]);
+        run(Some(args)).unwrap();
+        let args: Arguments = Arguments::parse_from(["ztask", "--db", &db, "show", "--oldest"]);
+        run(Some(args)).unwrap();
+
+        let mut task_list = tasklist::TaskList::new(db.clone());
+        assert_eq!(
+            task_list.copy_task(newest).unwrap().status,
+            TaskStatus::Completed
+        );
+        assert_eq!(
+            task_list.copy_task(oldest).unwrap().status,
+            TaskStatus::Active
+        );
+        assert_eq!(task_list.num_active(), 2);
+        drop(task_list);
+
+        assert!(Arguments::try_parse_from(["ztask", "complete", "abc", "--oldest"]).is_err());
+        assert!(Arguments::try_parse_from(["ztask", "show", "--oldest", "--newest"]).is_err());
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_bump_command() {
+        let db = __create_temp_db(1);
+        let task_list = tasklist::TaskList::new(db.clone());
+        let id = task_list.tasks.peek().unwrap().id.clone();
+        let priority = task_list.tasks.peek().unwrap().priority;
+        drop(task_list);
+
+        let args: Arguments = Arguments::parse_from(["ztask", "--db", &db, "bump", &id, "-1"]);
+        run(Some(args)).unwrap();
+
+        let mut task_list = tasklist::TaskList::new(db.clone());
+        let task = task_list.copy_task(id.clone()).unwrap();
+        assert_eq!(task.priority, (priority - 1).max(1));
+        drop(task_list);
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_complete_backdated() {
+        let db = __create_temp_db(2);
+        let mut task_list = tasklist::TaskList::new(db.clone());
+        let ids: Vec<String> = task_list.tasks.iter().map(|t| t.id.clone()).collect();
+        __backdate_tasks(
+            &mut task_list,
+            Local.with_ymd_and_hms(2024, 1, 1, 9, 0, 0).unwrap(),
+        );
+        drop(task_list);
+
+        let args: Arguments = Arguments::parse_from([
+            "ztask",
+            "--db",
+            &db,
+            "complete",
+            &ids[0],
+            "--at",
+            "2024-06-01 17:00",
+        ]);
+        run(Some(args)).unwrap();
+        let args: Arguments = Arguments::parse_from([
+            "ztask",
+            "--db",
+            &db,
+            "complete",
+            &ids[1],
+            "--at",
+            "2999-01-01",
+        ]);
+        run(Some(args)).unwrap();
+
+        let mut task_list = tasklist::TaskList::new(db.clone());
+        let task = task_list.copy_task(ids[0].clone()).unwrap();
+        assert_eq!(task.status, TaskStatus::Completed);
+        assert_eq!(
+            task.completed_at.unwrap().format("%F %R").to_string(),
+            "2024-06-01 17:00"
+        );
+        // Completing in the future is refused
+        let task = task_list.copy_task(ids[1].clone()).unwrap();
+        assert_eq!(task.status, TaskStatus::Active);
+        assert_eq!(task.completed_at, None);
+        drop(task_list);
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_complete_exact_id() {
+        let db = __create_temp_db(2);
+        let task_list = tasklist::TaskList::new(db.clone());
+        let id = task_list.tasks.peek().unwrap().id.clone();
+        drop(task_list);
+        assert_eq!(id.len(), 32);
+
+        let args: Arguments =
+            Arguments::parse_from(["ztask", "--db", &db, "--exact", "complete", &id]);
+        run(Some(args)).unwrap();
+        let mut task_list = tasklist::TaskList::new(db.clone());
+        assert_eq!(
+            task_list.copy_task(id).unwrap().status,
+            TaskStatus::Completed
+        );
+        drop(task_list);
+        __destroy_temp_db(db);
+    }
+
+    // Tests for "stats"
+
+    #[test]
+    fn verify_stats_estimate_rollup() {
+        let db = __create_temp_db(1);
+        let args: Arguments =
+            Arguments::parse_from(["ztask", "--db", &db, "add", "big job", "--estimate", "12h"]);
+        run(Some(args)).unwrap();
+        let args: Arguments = Arguments::parse_from([
+            "ztask",
+            "--db",
+            &db,
+            "add",
+            "small job",
+            "--estimate",
+            "30m",
+        ]);
+        run(Some(args)).unwrap();
+
+        let task_list = tasklist::TaskList::new(db.clone());
+        let mut out = String::new();
+        let week = parse_estimate("7d").unwrap();
+        print_stats(&mut out, &task_list, week, &Local::now()).unwrap();
+        assert!(out.contains("estimated remaining work: 12h 30m"), "{}", out);
+        assert!(out.contains("1 open task(s) have no estimate"), "{}", out);
+        drop(task_list);
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_stats_completed_since() {
+        let db = __create_temp_db(3);
+        let mut task_list = tasklist::TaskList::new(db.clone());
+        let now = Local::now();
+        let ids: Vec<String> = task_list.tasks.iter().map(|task| task.id.clone()).collect();
+        __backdate_tasks(&mut task_list, now - chrono::Duration::days(30));
+        task_list.complete_task_at(ids[0].clone(), now - chrono::Duration::days(2));
+        task_list.complete_task_at(ids[1].clone(), now - chrono::Duration::days(10));
+
+        let mut out = String::new();
+        print_stats(&mut out, &task_list, parse_estimate("7d").unwrap(), &now).unwrap();
+        assert!(out.contains("completed in last 7d: 1\n"), "{}", out);
+        let mut out = String::new();
+        print_stats(&mut out, &task_list, parse_estimate("30d").unwrap(), &now).unwrap();
+        assert!(out.contains("completed in last 30d: 2\n"), "{}", out);
+        drop(task_list);
+
+        let args: Arguments =
+            Arguments::parse_from(["ztask", "--db", &db, "stats", "--completed-since", "2w"]);
+        run(Some(args)).unwrap();
+        __destroy_temp_db(db);
+    }
+
+    // Tests for "history"
+
+    #[test]
+    fn verify_history() {
+        let db = __create_temp_db(0);
+        let log = db.replace("-test.json", "-events.log");
+        let args: Arguments =
+            Arguments::parse_from(["ztask", "--db", &db, "--event-log", &log, "add", "logged"]);
+        run(Some(args)).unwrap();
+
+        let mut task_list = tasklist::TaskList::new(db.clone());
+        task_list.event_log = Some(log.clone());
+        let display = DisplayOptions::default();
+        assert_eq!(process_history(&task_list, None, &display).unwrap(), 1);
+        assert_eq!(
+            process_history(&task_list, Some("nomatch".to_string()), &display).unwrap(),
+            0
+        );
+        drop(task_list);
+
+        let _ = std::fs::remove_file(log);
+        __destroy_temp_db(db);
+    }
+
+    // Tests for "restore"
+
+    #[test]
+    fn verify_restore_default_backup() {
+        let db = __create_temp_db(1);
+        let backup = format!("{}.bak", db);
+        std::fs::copy(&db, &backup).unwrap();
+        let args: Arguments = Arguments::parse_from(["ztask", "--db", &db, "add", "extra"]);
+        run(Some(args)).unwrap();
+
+        let args: Arguments = Arguments::parse_from(["ztask", "--db", &db, "restore"]);
+        run(Some(args)).unwrap();
+        let task_list = tasklist::TaskList::new(db.clone());
+        assert_eq!(task_list.num_tasks(), 1);
+        drop(task_list);
+
+        let _ = std::fs::remove_file(backup);
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_interactive_choice() {
+        let db = __create_temp_db(0);
+        let mut task_list = tasklist::TaskList::new(db.clone());
+        for (id, summary) in [("abc1", "first"), ("abc2", "second"), ("xyz", "other")] {
+            let mut task = Task::new(summary.to_string(), "quick".to_string(), false);
+            task.id = id.to_string();
+            task_list.add_task(task);
+        }
+
+        let ids = vec!["abc".to_string(), "xyz".to_string()];
+        let mut input = std::io::Cursor::new("2\n");
+        let mut output = Vec::new();
+        let chosen =
+            prompt_for_ambiguous_ids(&task_list, ids.clone(), &mut input, &mut output).unwrap();
+        assert_eq!(chosen, ["abc2", "xyz"]);
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("Id 'abc' matches 2 tasks"));
+        assert!(output.contains("1  abc1  first"));
+        assert!(output.contains("2  abc2  second"));
+
+        // An invalid choice leaves the id for the command to report
+        let mut input = std::io::Cursor::new("3\n");
+        let chosen = prompt_for_ambiguous_ids(&task_list, ids, &mut input, &mut Vec::new());
+        assert_eq!(chosen.unwrap(), ["abc", "xyz"]);
+        drop(task_list);
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_start_deadlock_names_active_tasks() {
+        let db = __create_temp_db(1);
+        let mut task_list = tasklist::TaskList::new(db.clone());
+        let active = task_list.tasks.peek().unwrap().clone();
+        let next = task_list.add_task(Task::new("waiting".to_string(), "quick".to_string(), false));
+
+        let message = describe_start_deadlock(&task_list);
+        assert!(message.contains(&format!("{}  {}", &active.id[..9], active.summary)));
+        assert!(message.contains(&format!("ztask stop {}", &active.id[..9])));
+        assert!(message.contains(&format!("ztask start {}", &next[..9])));
+        assert!(message.contains("--max-active 2"));
+        assert_eq!(
+            process_start(&mut task_list, vec![], Local::now()).unwrap(),
+            0
+        );
+        drop(task_list);
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_start_and_stop_multiple() {
+        let db = __create_temp_db(0);
+        let mut task_list = tasklist::TaskList::new(db.clone());
+        let ids: Vec<String> = (0..3)
+            .map(|i| task_list.add_task(Task::new(format!("task {i}"), "quick".to_string(), false)))
+            .collect();
+        task_list.max_active = 0;
+
+        assert_eq!(
+            process_start(&mut task_list, ids.clone(), Local::now()).unwrap(),
+            3
+        );
+        assert_eq!(task_list.num_active(), 3);
+        assert_eq!(process_stop(&mut task_list, ids[..2].to_vec()).unwrap(), 2);
+        assert_eq!(task_list.num_active(), 1);
+
+        // The max-active limit still applies to each task started
+        task_list.max_active = 2;
+        assert_eq!(
+            process_start(&mut task_list, ids[..2].to_vec(), Local::now()).unwrap(),
+            1
+        );
+        assert_eq!(task_list.num_active(), 2);
+
+        drop(task_list);
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_active_order_default_task() {
+        for (order, expected) in [
+            (tasklist::ActiveOrder::Oldest, "older"),
+            (tasklist::ActiveOrder::Newest, "newer"),
+        ] {
+            let db = __create_temp_db(0);
+            let mut task_list = tasklist::TaskList::new(db.clone());
+            task_list.active_order = order;
+            for (summary, days_ago) in [("older", 2), ("newer", 1)] {
+                let mut task = Task::new(summary.to_string(), "quick".to_string(), true);
+                task.created_at = Local::now() - chrono::Duration::days(days_ago);
+                task_list.add_task(task);
+            }
+
+            let active = Filter::default().with_status(TaskStatus::Active);
+            assert_eq!(task_list.filtered(&active)[0].summary, expected);
+
+            assert_eq!(process_stop(&mut task_list, vec![]).unwrap(), 1);
+            let remaining = task_list.filtered(&active);
+            assert_eq!(remaining.len(), 1);
+            assert_ne!(remaining[0].summary, expected);
+
+            drop(task_list);
+            __destroy_temp_db(db);
+        }
+    }
+
+    #[test]
+    fn verify_list_then_complete_by_number() {
+        let db = __create_temp_db(0);
+        let mut task_list = tasklist::TaskList::new(db.clone());
+        for i in 0..3 {
+            task_list.add_task(Task::new(
+                format!("numbered {i}"),
+                "quick".to_string(),
+                false,
+            ));
+        }
+        let second = listing_order(&task_list, &Filter::default())[1].clone();
+        drop(task_list);
+
+        let args: Arguments = Arguments::parse_from(["ztask", "--db", &db, "list", "--numbered"]);
+        run(Some(args)).unwrap();
+        let args: Arguments = Arguments::parse_from(["ztask", "--db", &db, "complete", "#2"]);
+        run(Some(args)).unwrap();
+
+        let mut task_list = tasklist::TaskList::new(db.clone());
+        assert_eq!(
+            task_list.copy_task(second).unwrap().status,
+            TaskStatus::Completed
+        );
+        assert_eq!(
+            task_list
+                .tasks
+                .iter()
+                .filter(|task| task.status == TaskStatus::Completed)
+                .count(),
+            1
+        );
+        drop(task_list);
+        let _ = std::fs::remove_file(format!("{}.ordinals", db));
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_locked_db_is_refused() {
+        let db = __create_temp_db(1);
+        let lock = tasklist::DbLock::acquire(&db).unwrap();
+        let args: Arguments = Arguments::parse_from(["ztask", "--db", &db, "list"]);
+        assert!(run(Some(args)).is_err());
+        drop(lock);
+
+        let args: Arguments = Arguments::parse_from(["ztask", "--db", &db, "list"]);
+        run(Some(args)).unwrap();
+        __destroy_temp_db(db);
+    }
+
+    // Tests for "sweep"
+
+    #[test]
+    fn verify_sweep_stops_idle_tasks() {
+        let db = __create_temp_db(1);
+        let mut task_list = tasklist::TaskList::new(db.clone());
+        let fresh = task_list.tasks.peek().unwrap().id.clone();
+        let mut forgotten = Task::new("forgotten".to_string(), "quick".to_string(), true);
+        forgotten.last_started_at = Some(Local::now() - chrono::Duration::days(3));
+        let forgotten = task_list.add_task(forgotten);
+
+        let swept = process_sweep(
+            &mut task_list,
+            parse_estimate("1 day").unwrap(),
+            &Local::now(),
+            &DisplayOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(swept, 1);
+        assert_eq!(
+            task_list.copy_task(forgotten).unwrap().status,
+            TaskStatus::Backlog
+        );
+        assert!(task_list.notes.is_empty());
+        assert_eq!(
+            task_list.copy_task(fresh).unwrap().status,
+            TaskStatus::Active
+        );
+        drop(task_list);
+
+        let args: Arguments =
+            Arguments::parse_from(["ztask", "--db", &db, "sweep", "--idle", "8h"]);
+        run(Some(args)).unwrap();
+        __destroy_temp_db(db);
+    }
+
+    // Tests for "dedupe"
+
+    #[test]
+    fn verify_dedupe_asks_per_group() {
+        let db = __create_temp_db(0);
+        let mut task_list = tasklist::TaskList::new(db.clone());
+        for (summary, details) in [
+            ("water plants", "ferns"),
+            ("Water plants", "cactus"),
+            ("pay rent", ""),
+            ("pay rent", ""),
+        ] {
+            let mut task = Task::new(summary.to_string(), "quick".to_string(), false);
+            task.details = details.to_string();
+            task_list.add_task(task);
+        }
+
+        // Groups come in summary order: decline "pay rent", accept the plants
+        let mut input = std::io::Cursor::new("n\ny\n");
+        let mut output = Vec::new();
+        let merged = process_dedupe(&mut task_list, false, &mut input, &mut output).unwrap();
+        assert_eq!(merged, 1);
+        assert_eq!(task_list.num_tasks(), 3);
+        let plants = task_list
+            .tasks
+            .iter()
+            .find(|task| task.summary.ends_with("plants"))
+            .unwrap();
+        assert!(plants.details.contains("ferns") && plants.details.contains("cactus"));
+        drop(task_list);
+
+        let args: Arguments = Arguments::parse_from(["ztask", "--db", &db, "dedupe", "--yes"]);
+        run(Some(args)).unwrap();
+        assert_eq!(tasklist::TaskList::new(db.clone()).num_tasks(), 2);
+        __destroy_temp_db(db);
+    }
+
+    // Tests for "block"
+
+    #[test]
+    fn verify_block_needs_two_ids() {
+        let db = __create_temp_db(2);
+        let mut task_list = tasklist::TaskList::new(db.clone());
+        let id = task_list.tasks.peek().unwrap().id.clone();
+        assert!(process_block_on(&mut task_list, vec![]).is_err());
+        assert!(process_block_on(&mut task_list, vec![id.clone()]).is_err());
+        assert!(!task_list.dirty);
+        drop(task_list);
+
+        assert!(Arguments::try_parse_from(["ztask", "--db", &db, "block", &id]).is_err());
+        __destroy_temp_db(db);
+    }
+
+    // Tests for "del"
+
+    #[test]
+    fn verify_delete_default() {
+        let db = __create_temp_db(0);
+        let args: Arguments = Arguments::parse_from(["ztask", "--db", &db, "-v", "del"]);
+        println!("args: {:?}", args);
+        run(Some(args)).unwrap();
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_delete_single() {
+        let db = __create_temp_db(2);
+        let task_list = tasklist::TaskList::new(db.clone());
+        let mut iter = task_list.tasks.iter().skip(1);
+        let id = iter.next().unwrap().id.clone();
+        let args: Arguments = Arguments::parse_from(["ztask", "--db", &db, "-v", "del", &id]);
+        drop(task_list);
+        println!("args: {:?}", args);
+        run(Some(args)).unwrap();
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_delete_nonexisting() {
+        let db = __create_temp_db(0);
+        let id = "invalid";
+        let args: Arguments = Arguments::parse_from(["ztask", "--db", &db, "-v", "del", id]);
+        println!("args: {:?}", args);
+        run(Some(args)).unwrap();
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_delete_nonexisting_removes_nothing() {
+        let db = __create_temp_db(2);
+        let mut task_list = tasklist::TaskList::new(db.clone());
+        let removed = process_del(&mut task_list, vec!["invalid".to_string()]).unwrap();
+        assert_eq!(removed, 0);
+        assert_eq!(task_list.tasks.len(), 2);
+        assert!(!task_list.dirty);
+        drop(task_list);
+        __destroy_temp_db(db);
+    }
+
+    // Tests for "edit"
+
+    #[test]
+    fn verify_edit_default() {
+        let db = __create_temp_db(0);
+        let args: Arguments = Arguments::parse_from(["ztask", "--db", &db, "-v", "edit"]);
+        println!("args: {:?}", args);
+        run(Some(args)).unwrap();
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_edit_single() {
+        let db = __create_temp_db(2);
+        let task_list = tasklist::TaskList::new(db.clone());
+        let mut iter = task_list.tasks.iter().skip(1);
+        let id = iter.next().unwrap().id.clone();
+        let args: Arguments = Arguments::parse_from(["ztask", "--db", &db, "-v", "edit", &id]);
+        drop(task_list);
+        println!("args: {:?}", args);
+        run(Some(args)).unwrap();
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_edit_estimate() {
+        let db = __create_temp_db(2);
+        let task_list = tasklist::TaskList::new(db.clone());
+        let id = task_list.tasks.peek().unwrap().id.clone();
+        drop(task_list);
+        assert!(
+            Arguments::try_parse_from(["ztask", "edit", &id, "--estimate", "soonish"]).is_err()
+        );
+        assert!(
+            Arguments::try_parse_from(["ztask", "edit", &id, "-d", "--estimate", "1h"]).is_err()
+        );
+        let args: Arguments =
+            Arguments::parse_from(["ztask", "--db", &db, "edit", &id, "--estimate", "2h 30m"]);
+        run(Some(args)).unwrap();
+        let mut task_list = tasklist::TaskList::new(db.clone());
+        let task = task_list.copy_task(id).unwrap();
+        assert_eq!(task.estimate, Some(chrono::Duration::minutes(150)));
+        drop(task_list);
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_edit_owner() {
+        let db = __create_temp_db(2);
+        let task_list = tasklist::TaskList::new(db.clone());
+        let id = task_list.tasks.peek().unwrap().id.clone();
+        drop(task_list);
+        let args: Arguments =
+            Arguments::parse_from(["ztask", "--db", &db, "edit", &id, "--owner", "carol"]);
+        run(Some(args)).unwrap();
+        let mut task_list = tasklist::TaskList::new(db.clone());
+        let task = task_list.copy_task(id).unwrap();
+        assert_eq!(task.owner, Some("carol".to_string()));
+        drop(task_list);
+        __destroy_temp_db(db);
+
+        let result =
+            Arguments::try_parse_from(["ztask", "edit", "abc", "--owner", "bob", "--details-only"]);
+        assert!(result.is_err());
+    }
 }
@@ -0,0 +1,91 @@
+use crate::task::IdGenerator;
+use crate::tasklist::{ActiveOrder, DbOrder};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+
+/// Default configuration file path
+pub const CONFIG_PATH: &str = "$HOME/.ztask/config.json";
+
+/// User configuration, read from a JSON file.  Every key is optional and
+/// command line flags take precedence over values set here.
+#[derive(Deserialize, Default, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Maximum number of tasks that may be active at once (0 means unlimited)
+    pub max_active: Option<usize>,
+    /// Maximum number of characters in a task summary
+    pub max_summary_length: Option<usize>,
+    /// strftime format used when displaying dates
+    pub date_format: Option<String>,
+    /// Event log path (an empty string disables logging)
+    pub event_log: Option<String>,
+    /// Hour of the day (0-23) that sleeping tasks wake at
+    pub wake_at_hour: Option<u32>,
+    /// Whether add parses +tag, @category, p: and due: tokens out of summaries
+    pub quick_add: Option<bool>,
+    /// How long a task may sit in the backlog before it is shown as stale,
+    /// such as "3 weeks"
+    pub stale_after: Option<String>,
+    /// How to choose ids for new tasks, "uuid" or "short"
+    pub id_generator: Option<IdGenerator>,
+    /// Which active task comes first and is acted on by default, "oldest" or "newest"
+    pub active_order: Option<ActiveOrder>,
+    /// The order tasks are saved in, "display" or "id"
+    pub db_order: Option<DbOrder>,
+    /// Colors for task categories, either named ("red") or "#rrggbb"
+    pub category_colors: BTreeMap<String, String>,
+}
+
+impl Config {
+    /// Load the configuration file.  A missing file yields the default config.
+    pub fn load(path: &str) -> Result<Config, io::Error> {
+        match fs::read_to_string(path) {
+            Ok(contents) => Ok(serde_json::from_str(&contents)?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verify_missing_config_is_default() {
+        let config = Config::load("data/temp/no-such-config.json").unwrap();
+        assert_eq!(config, Config::default());
+    }
+
+    #[test]
+    fn verify_config_keys() {
+        let config: Config =
+            serde_json::from_str(r#"{ "max_active": 3, "date_format": "%d/%m/%Y" }"#).unwrap();
+        assert_eq!(config.max_active, Some(3));
+        assert_eq!(config.date_format, Some("%d/%m/%Y".to_string()));
+        assert!(config.category_colors.is_empty());
+        assert_eq!(config.id_generator, None);
+        assert_eq!(config.max_summary_length, None);
+
+        let config: Config = serde_json::from_str(r#"{ "id_generator": "short" }"#).unwrap();
+        assert_eq!(config.id_generator, Some(IdGenerator::Short));
+
+        let config: Config = serde_json::from_str(r#"{ "active_order": "oldest" }"#).unwrap();
+        assert_eq!(config.active_order, Some(ActiveOrder::Oldest));
+
+        let config: Config = serde_json::from_str(r#"{ "db_order": "id" }"#).unwrap();
+        assert_eq!(config.db_order, Some(DbOrder::Id));
+    }
+
+    #[test]
+    fn verify_category_colors() {
+        let config: Config = serde_json::from_str(
+            r##"{ "category_colors": { "work": "#ff8800", "home": "green" } }"##,
+        )
+        .unwrap();
+        assert_eq!(config.category_colors["work"], "#ff8800");
+        assert_eq!(config.category_colors["home"], "green");
+    }
+}
@@ -0,0 +1,111 @@
+use crate::task::TaskStatus;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+
+/// Default event log path
+pub const EVENT_LOG_PATH: &str = "$HOME/.ztask/events.log";
+
+/// A single state transition, stored as one line of JSON in the event log
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Event {
+    pub ts: DateTime<Local>,
+    pub op: String,
+    pub task_id: String,
+    pub from_status: Option<TaskStatus>,
+    pub to_status: Option<TaskStatus>,
+}
+
+impl Event {
+    pub fn new(
+        op: &str,
+        task_id: &str,
+        from_status: Option<TaskStatus>,
+        to_status: Option<TaskStatus>,
+    ) -> Self {
+        Event {
+            ts: Local::now(),
+            op: op.to_string(),
+            task_id: task_id.to_string(),
+            from_status,
+            to_status,
+        }
+    }
+}
+
+/// Append an event to the log at the given path, creating the log and its
+/// directory if needed.
+pub fn append(path: &str, event: &Event) -> Result<(), io::Error> {
+    if let Some(parent) = std::path::Path::new(path).parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", serde_json::to_string(event)?)?;
+    Ok(())
+}
+
+/// Read every event from the log at the given path, skipping lines that
+/// can't be parsed.  A missing log holds no events.
+pub fn read(path: &str) -> Result<Vec<Event>, io::Error> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e),
+    };
+    Ok(contents
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn verify_append_and_read() {
+        fs::create_dir_all("data/temp").unwrap();
+        let path = format!("data/temp/{}-events.log", Uuid::new_v4().simple());
+
+        append(
+            &path,
+            &Event::new("add", "abc", None, Some(TaskStatus::Backlog)),
+        )
+        .unwrap();
+        append(
+            &path,
+            &Event::new(
+                "start",
+                "abc",
+                Some(TaskStatus::Backlog),
+                Some(TaskStatus::Active),
+            ),
+        )
+        .unwrap();
+
+        let events = read(&path).unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[1].op, "start");
+        assert_eq!(events[1].to_status, Some(TaskStatus::Active));
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 2);
+
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn verify_append_creates_directory() {
+        let dir = format!("data/temp/{}-events", Uuid::new_v4().simple());
+        let path = format!("{dir}/nested/events.log");
+        append(&path, &Event::new("add", "abc", None, None)).unwrap();
+        assert_eq!(read(&path).unwrap().len(), 1);
+
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn verify_missing_log_is_empty() {
+        assert!(read("data/temp/no-such-events.log").unwrap().is_empty());
+    }
+}
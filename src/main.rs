@@ -3,8 +3,15 @@
 use std::process;
 
 mod command_line_interface;
+mod config;
+mod eventlog;
+mod quick_add;
+mod search;
+mod snooze;
 mod task;
 mod tasklist;
+mod taskstore;
+mod template;
 
 fn main() {
     if let Err(e) = command_line_interface::run(None) {
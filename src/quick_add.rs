@@ -0,0 +1,121 @@
+//! Parsing of metadata embedded in a new task's summary, taskwarrior style.
+//!
+//! A summary is split on whitespace and these tokens are taken out of it:
+//!
+//! | Token         | Meaning                                         |
+//! |---------------|-------------------------------------------------|
+//! | `+name`       | add the tag `name`                              |
+//! | `@name`       | set the category to `name`                      |
+//! | `p:priority`  | set the priority (1-5, high, medium or low)     |
+//! | `due:when`    | set the due date (YYYY-MM-DD [HH:MM] or "3d")   |
+//!
+//! Prefix a word with a backslash to keep it in the summary as is, so
+//! `\+1` and `\@home` become `+1` and `@home`.  Tokens with an empty name or
+//! an invalid value are also left in the summary.
+
+use crate::task::{normalize_label, parse_priority, parse_when};
+use chrono::{DateTime, Local};
+
+/// Metadata parsed out of a quick add summary
+#[derive(Debug, Default, PartialEq)]
+pub struct ParsedMeta {
+    pub tags: Vec<String>,
+    pub category: Option<String>,
+    pub priority: Option<u8>,
+    pub due_at: Option<DateTime<Local>>,
+}
+
+/// Split a quick add input into the remaining summary and its metadata
+pub fn parse_quick_add(input: &str) -> (String, ParsedMeta) {
+    let mut meta = ParsedMeta::default();
+    let mut words: Vec<&str> = Vec::new();
+    for word in input.split_whitespace() {
+        if let Some(literal) = word.strip_prefix('\\') {
+            words.push(literal);
+        } else if let Some(tag) = word.strip_prefix('+').filter(|tag| !tag.is_empty()) {
+            meta.tags.push(normalize_label(tag));
+        } else if let Some(category) = word.strip_prefix('@').filter(|c| !c.is_empty()) {
+            meta.category = Some(normalize_label(category));
+        } else if let Some(priority) = word.strip_prefix("p:").and_then(|p| parse_priority(p).ok())
+        {
+            meta.priority = Some(priority);
+        } else if let Some(due_at) = word.strip_prefix("due:").and_then(|d| parse_when(d).ok()) {
+            meta.due_at = Some(due_at);
+        } else {
+            words.push(word);
+        }
+    }
+    (words.join(" "), meta)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn plain_summary() {
+        let (summary, meta) = parse_quick_add("fix  the login page");
+        assert_eq!(summary, "fix the login page");
+        assert_eq!(meta, ParsedMeta::default());
+    }
+
+    #[test]
+    fn all_tokens() {
+        let before = Local::now();
+        let (summary, meta) = parse_quick_add("fix login +urgent @work p:1 due:3d");
+        assert_eq!(summary, "fix login");
+        assert_eq!(meta.tags, vec!["urgent".to_string()]);
+        assert_eq!(meta.category, Some("work".to_string()));
+        assert_eq!(meta.priority, Some(1));
+        let due_at = meta.due_at.unwrap();
+        assert!(due_at >= before + Duration::days(3));
+        assert!(due_at <= Local::now() + Duration::days(3));
+    }
+
+    #[test]
+    fn tokens_anywhere() {
+        let (summary, meta) = parse_quick_add("+a call @home mum +b p:low");
+        assert_eq!(summary, "call mum");
+        assert_eq!(meta.tags, vec!["a".to_string(), "b".to_string()]);
+        assert_eq!(meta.category, Some("home".to_string()));
+        assert_eq!(meta.priority, Some(5));
+    }
+
+    #[test]
+    fn labels_are_normalized() {
+        let (_, meta) = parse_quick_add("task @Work +Urgent");
+        assert_eq!(meta.category, Some("work".to_string()));
+        assert_eq!(meta.tags, vec!["urgent".to_string()]);
+    }
+
+    #[test]
+    fn last_category_wins() {
+        let (_, meta) = parse_quick_add("task @home @work");
+        assert_eq!(meta.category, Some("work".to_string()));
+    }
+
+    #[test]
+    fn escaped_tokens() {
+        let (summary, meta) = parse_quick_add(r"vote \+1 for \@alice \\ \p:1");
+        assert_eq!(summary, r"vote +1 for @alice \ p:1");
+        assert_eq!(meta, ParsedMeta::default());
+    }
+
+    #[test]
+    fn invalid_tokens_are_kept() {
+        let (summary, meta) = parse_quick_add("c + d @ p:9 due:someday");
+        assert_eq!(summary, "c + d @ p:9 due:someday");
+        assert_eq!(meta, ParsedMeta::default());
+    }
+
+    #[test]
+    fn due_date() {
+        let (summary, meta) = parse_quick_add("file taxes due:2030-04-15");
+        assert_eq!(summary, "file taxes");
+        assert_eq!(
+            meta.due_at.unwrap().format("%F %R").to_string(),
+            "2030-04-15 00:00"
+        );
+    }
+}
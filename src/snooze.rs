@@ -0,0 +1,161 @@
+//! Rules that put a task back to sleep on a schedule, such as
+//! `weekdays@09:00`.  A task with a rule sleeps until the rule's next
+//! occurrence whenever it is completed or stopped, instead of being finished.
+//!
+//! The rules are:
+//!
+//! - `daily@HH:MM`: every day at the given time
+//! - `weekdays@HH:MM`: Monday to Friday at the given time
+//! - `weekly@DAY[@HH:MM]`: once a week on the given day, such as `mon`, at
+//!   the given time or else at the start of the day
+
+use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveTime, Weekday};
+
+/// A parsed snooze rule, see the module documentation for the grammar
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SnoozeRule {
+    Daily(NaiveTime),
+    Weekdays(NaiveTime),
+    Weekly(Weekday, NaiveTime),
+}
+
+impl SnoozeRule {
+    /// Return true if the rule fires on the given date
+    fn fires_on(&self, date: NaiveDate) -> bool {
+        match self {
+            SnoozeRule::Daily(_) => true,
+            SnoozeRule::Weekdays(_) => !matches!(date.weekday(), Weekday::Sat | Weekday::Sun),
+            SnoozeRule::Weekly(day, _) => date.weekday() == *day,
+        }
+    }
+
+    fn time(&self) -> NaiveTime {
+        match self {
+            SnoozeRule::Daily(time) | SnoozeRule::Weekdays(time) | SnoozeRule::Weekly(_, time) => {
+                *time
+            }
+        }
+    }
+
+    /// Return the first time the rule fires strictly after the given time
+    pub fn next_after(&self, after: DateTime<Local>) -> DateTime<Local> {
+        let mut date = after.date_naive();
+        // Every rule fires at least once a week.  A day whose time falls in
+        // a daylight saving gap is skipped, so allow for a second week.
+        for _ in 0..15 {
+            if self.fires_on(date) {
+                if let Some(at) = date
+                    .and_time(self.time())
+                    .and_local_timezone(Local)
+                    .earliest()
+                {
+                    if at > after {
+                        return at;
+                    }
+                }
+            }
+            date = date.succ_opt().expect("date out of range");
+        }
+        unreachable!("snooze rule never fires")
+    }
+}
+
+/// Parse a snooze rule, such as `daily@09:00`, `weekdays@09:00` or `weekly@mon`.
+pub fn parse_snooze_rule(s: &str) -> Result<SnoozeRule, String> {
+    let invalid = || {
+        format!(
+            "Invalid snooze rule '{}', use daily@HH:MM, weekdays@HH:MM or weekly@DAY[@HH:MM]",
+            s
+        )
+    };
+    let parse_time = |time: &str| NaiveTime::parse_from_str(time, "%H:%M").map_err(|_| invalid());
+    let lowercase = s.trim().to_lowercase();
+    let (kind, rest) = lowercase.split_once('@').ok_or_else(invalid)?;
+    match kind {
+        "daily" => Ok(SnoozeRule::Daily(parse_time(rest)?)),
+        "weekdays" => Ok(SnoozeRule::Weekdays(parse_time(rest)?)),
+        "weekly" => {
+            let (day, time) = match rest.split_once('@') {
+                Some((day, time)) => (day, parse_time(time)?),
+                None => (rest, NaiveTime::MIN),
+            };
+            Ok(SnoozeRule::Weekly(
+                day.parse().map_err(|_| invalid())?,
+                time,
+            ))
+        }
+        _ => Err(invalid()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn local(date: &str, time: &str) -> DateTime<Local> {
+        let at = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .unwrap()
+            .and_time(NaiveTime::parse_from_str(time, "%H:%M").unwrap());
+        Local.from_local_datetime(&at).earliest().unwrap()
+    }
+
+    #[test]
+    fn verify_parse_snooze_rule() {
+        let nine = NaiveTime::from_hms_opt(9, 0, 0).unwrap();
+        assert_eq!(
+            parse_snooze_rule("daily@09:00"),
+            Ok(SnoozeRule::Daily(nine))
+        );
+        assert_eq!(
+            parse_snooze_rule("Weekdays@09:00"),
+            Ok(SnoozeRule::Weekdays(nine))
+        );
+        assert_eq!(
+            parse_snooze_rule("weekly@mon"),
+            Ok(SnoozeRule::Weekly(Weekday::Mon, NaiveTime::MIN))
+        );
+        assert_eq!(
+            parse_snooze_rule("weekly@friday@09:00"),
+            Ok(SnoozeRule::Weekly(Weekday::Fri, nine))
+        );
+        assert!(parse_snooze_rule("daily").is_err());
+        assert!(parse_snooze_rule("daily@9am").is_err());
+        assert!(parse_snooze_rule("weekly@someday").is_err());
+        assert!(parse_snooze_rule("hourly@09:00").is_err());
+    }
+
+    #[test]
+    fn verify_next_occurrence_across_weekend() {
+        let weekdays = parse_snooze_rule("weekdays@09:00").unwrap();
+        // 2024-03-08 is a Friday
+        assert_eq!(
+            weekdays.next_after(local("2024-03-08", "08:00")),
+            local("2024-03-08", "09:00")
+        );
+        assert_eq!(
+            weekdays.next_after(local("2024-03-08", "09:00")),
+            local("2024-03-11", "09:00")
+        );
+        assert_eq!(
+            weekdays.next_after(local("2024-03-09", "12:00")),
+            local("2024-03-11", "09:00")
+        );
+
+        let daily = parse_snooze_rule("daily@09:00").unwrap();
+        assert_eq!(
+            daily.next_after(local("2024-03-08", "17:30")),
+            local("2024-03-09", "09:00")
+        );
+
+        let weekly = parse_snooze_rule("weekly@mon").unwrap();
+        assert_eq!(
+            weekly.next_after(local("2024-03-10", "23:00")),
+            local("2024-03-11", "00:00")
+        );
+        assert_eq!(
+            weekly.next_after(local("2024-03-11", "00:00")),
+            local("2024-03-18", "00:00")
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use chrono::{DateTime, Local};
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveDateTime};
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
 use std::collections::BTreeSet;
@@ -18,6 +18,8 @@ pub enum TaskStatus {
     Blocked,
     #[serde(rename = "sleeping")]
     Sleeping, // Would Snoozed be better?
+    #[serde(rename = "scheduled")]
+    Scheduled,
     #[serde(rename = "completed")]
     Completed,
 }
@@ -30,10 +32,223 @@ impl std::fmt::Display for TaskStatus {
             TaskStatus::Blocked => write!(f, "blocked"),
             TaskStatus::Completed => write!(f, "completed"),
             TaskStatus::Sleeping => write!(f, "sleeping"),
+            TaskStatus::Scheduled => write!(f, "scheduled"),
         }
     }
 }
 
+impl std::str::FromStr for TaskStatus {
+    type Err = String;
+
+    /// Parse a status by the name it is shown and saved under, ignoring case
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "active" => Ok(TaskStatus::Active),
+            "backlog" => Ok(TaskStatus::Backlog),
+            "blocked" => Ok(TaskStatus::Blocked),
+            "sleeping" => Ok(TaskStatus::Sleeping),
+            "scheduled" => Ok(TaskStatus::Scheduled),
+            "completed" => Ok(TaskStatus::Completed),
+            _ => Err(format!(
+                "Invalid status '{}', expected active, backlog, blocked, sleeping, scheduled or completed",
+                s
+            )),
+        }
+    }
+}
+
+impl TryFrom<&str> for TaskStatus {
+    type Error = String;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// How ids are chosen for new tasks
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum IdGenerator {
+    /// A random UUID, shown by its first 9 characters
+    #[default]
+    Uuid,
+    /// A short sequential id such as "a1", "a2", ...
+    Short,
+}
+
+/// Prefix of the ids made by IdGenerator::Short
+const SHORT_ID_PREFIX: &str = "a";
+
+impl IdGenerator {
+    /// Return a new id that isn't among the existing ids.  Short ids count
+    /// up from the highest one in use, so ids of deleted tasks aren't reused.
+    pub fn next_id<'a>(&self, existing: impl Iterator<Item = &'a str>) -> String {
+        match self {
+            IdGenerator::Uuid => Uuid::new_v4().simple().to_string(),
+            IdGenerator::Short => {
+                let highest = existing
+                    .filter_map(|id| id.strip_prefix(SHORT_ID_PREFIX)?.parse::<u64>().ok())
+                    .max()
+                    .unwrap_or(0);
+                format!("{}{}", SHORT_ID_PREFIX, highest + 1)
+            }
+        }
+    }
+}
+
+/// Parse an id generator name, uuid or short.
+pub fn parse_id_generator(s: &str) -> Result<IdGenerator, String> {
+    match s.trim().to_lowercase().as_str() {
+        "uuid" => Ok(IdGenerator::Uuid),
+        "short" => Ok(IdGenerator::Short),
+        _ => Err(format!(
+            "Invalid id generator '{}', expected uuid or short",
+            s
+        )),
+    }
+}
+
+/// The part of an id shown in listings: its first 9 characters, or all of
+/// a shorter id.
+pub fn short_id(id: &str) -> &str {
+    id.get(..9).unwrap_or(id)
+}
+
+/// Parse a priority, given either as a number from 1 (highest) to 5
+/// (lowest) or as one of the words high, medium or low.
+pub fn parse_priority(s: &str) -> Result<u8, String> {
+    match s.trim().to_lowercase().as_str() {
+        "high" => Ok(1),
+        "medium" => Ok(3),
+        "low" => Ok(5),
+        other => match other.parse::<u8>() {
+            Ok(priority) if (1..=5).contains(&priority) => Ok(priority),
+            _ => Err(format!(
+                "Invalid priority '{}', expected 1-5, high, medium or low",
+                s
+            )),
+        },
+    }
+}
+
+/// Normalize a category or tag name by trimming it and lowercasing it, so
+/// "Work" and "work " name the same bucket.  New names are normalized when
+/// they are stored, and names are also compared in normalized form, so tasks
+/// saved before normalization still match without migrating the database.
+pub fn normalize_label(label: &str) -> String {
+    label.trim().to_lowercase()
+}
+
+/// Default limit on the number of characters in a summary
+pub const DEFAULT_MAX_SUMMARY_LENGTH: usize = 200;
+
+/// Parse a task summary, trimming surrounding whitespace.  A summary must
+/// have some text left after trimming, or the task couldn't be recognized,
+/// and be at most max_length characters long, so it fits in a listing.
+pub fn parse_summary(s: &str, max_length: usize) -> Result<String, String> {
+    match s.trim() {
+        "" => Err("Task summary can't be empty".to_string()),
+        summary if summary.chars().count() > max_length => Err(format!(
+            "Task summary is {} characters long, more than the limit of {}.  \
+             Put the rest in the details instead",
+            summary.chars().count(),
+            max_length
+        )),
+        summary => Ok(summary.to_string()),
+    }
+}
+
+/// Normalize task details by trimming trailing whitespace, so details that
+/// are only whitespace or stray newlines become empty and aren't shown.
+/// Leading indentation is kept.
+pub fn normalize_details(details: &str) -> String {
+    details.trim_end().to_string()
+}
+
+/// Parse a point in time, given as an RFC 3339 timestamp (as exported),
+/// "YYYY-MM-DD HH:MM" or a date (meaning the start of that day).
+pub fn parse_timestamp(s: &str) -> Result<DateTime<Local>, String> {
+    let s = s.trim();
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(s) {
+        return Ok(timestamp.with_timezone(&Local));
+    }
+    let naive = NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M")
+        .ok()
+        .or_else(|| {
+            NaiveDate::parse_from_str(s, "%Y-%m-%d")
+                .ok()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+        })
+        .ok_or(format!("Invalid time '{}', expected YYYY-MM-DD [HH:MM]", s))?;
+    naive
+        .and_local_timezone(Local)
+        .earliest()
+        .ok_or(format!("Invalid local time '{}'", s))
+}
+
+/// Parse a future time, given either as a timestamp accepted by
+/// parse_timestamp or as a duration from now such as "3 days".
+pub fn parse_when(s: &str) -> Result<DateTime<Local>, String> {
+    parse_timestamp(s).or_else(|_| {
+        parse_estimate(s)
+            .map(|duration| Local::now() + duration)
+            .map_err(|_| {
+                format!(
+                    "Invalid time '{}', expected YYYY-MM-DD [HH:MM] or a duration",
+                    s.trim()
+                )
+            })
+    })
+}
+
+/// Parse a duration such as "2h 30m" or "3 days" into an effort estimate.
+pub fn parse_estimate(s: &str) -> Result<Duration, String> {
+    parse_duration::parse(s)
+        .ok()
+        .and_then(|duration| Duration::from_std(duration).ok())
+        .ok_or(format!("Invalid duration '{}'", s))
+}
+
+/// Serialize an optional duration as a whole number of seconds
+pub(crate) mod duration_seconds {
+    use chrono::Duration;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        duration: &Option<Duration>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match duration {
+            Some(duration) => serializer.serialize_some(&duration.num_seconds()),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Duration>, D::Error> {
+        Ok(Option::<i64>::deserialize(deserializer)?.map(Duration::seconds))
+    }
+}
+
+/// Editor used when neither $VISUAL nor $EDITOR is set
+const DEFAULT_EDITOR: &str = "nano";
+
+/// Determine the editor to use from the environment: $VISUAL, then $EDITOR,
+/// then nano.
+pub fn resolve_editor() -> String {
+    choose_editor(env::var("VISUAL").ok(), env::var("EDITOR").ok())
+}
+
+/// Pick the first of the given editors that is set and not empty
+fn choose_editor(visual: Option<String>, editor: Option<String>) -> String {
+    visual
+        .into_iter()
+        .chain(editor)
+        .find(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_EDITOR.to_string())
+}
+
 /// Task structure
 #[derive(Serialize, Deserialize, Clone, Eq, PartialEq)]
 pub struct Task {
@@ -46,6 +261,120 @@ pub struct Task {
     pub status: TaskStatus,
     pub blocked_by: BTreeSet<String>,
     pub wake_at: Option<DateTime<Local>>,
+    /// When a scheduled task becomes active
+    #[serde(default)]
+    pub scheduled_at: Option<DateTime<Local>>,
+    #[serde(default)]
+    pub owner: Option<String>,
+    /// Estimated effort, stored in seconds
+    #[serde(default, with = "duration_seconds")]
+    pub estimate: Option<Duration>,
+    #[serde(default)]
+    pub tags: BTreeSet<String>,
+    /// Deadline for the task
+    #[serde(default)]
+    pub due_at: Option<DateTime<Local>>,
+    /// When the task was last made active
+    #[serde(default)]
+    pub last_started_at: Option<DateTime<Local>>,
+    /// When the task was completed
+    #[serde(default)]
+    pub completed_at: Option<DateTime<Local>>,
+    /// When the task last changed.  Missing from tasks saved before this
+    /// was tracked, see `last_updated`.
+    #[serde(default)]
+    pub updated_at: Option<DateTime<Local>>,
+    /// Schedule the task sleeps on when completed or stopped, such as
+    /// "weekdays@09:00", see snooze::parse_snooze_rule
+    #[serde(default)]
+    pub snooze_rule: Option<String>,
+}
+
+/// Builds a task with chainable setters, see Task::builder
+#[derive(Clone)]
+pub struct TaskBuilder {
+    task: Task,
+}
+
+impl TaskBuilder {
+    /// Set the id, replacing the generated one
+    pub fn id(mut self, id: impl Into<String>) -> Self {
+        self.task.id = id.into();
+        self
+    }
+
+    /// Set the summary, used as given
+    pub fn summary(mut self, summary: impl Into<String>) -> Self {
+        self.task.summary = summary.into();
+        self
+    }
+
+    /// Set the category, used as given rather than normalized
+    pub fn category(mut self, category: impl Into<String>) -> Self {
+        self.task.category = category.into();
+        self
+    }
+
+    /// Set the priority, 1 being the most urgent
+    pub fn priority(mut self, priority: u8) -> Self {
+        self.task.priority = priority;
+        self
+    }
+
+    /// Set the status, without setting any of the times that go with it
+    pub fn status(mut self, status: TaskStatus) -> Self {
+        self.task.status = status;
+        self
+    }
+
+    /// Set the deadline for the task
+    pub fn due(mut self, due_at: DateTime<Local>) -> Self {
+        self.task.due_at = Some(due_at);
+        self
+    }
+
+    /// Make the task sleep until the given time
+    pub fn sleep_until(mut self, wake_at: DateTime<Local>) -> Self {
+        self.task.status = TaskStatus::Sleeping;
+        self.task.wake_at = Some(wake_at);
+        self
+    }
+
+    /// Return the task that has been built
+    pub fn build(self) -> Task {
+        self.task
+    }
+}
+
+/// Criteria for selecting tasks.  A task matches when it meets every
+/// criterion that is set, so an empty filter matches every task.
+#[derive(Clone, Debug, Default)]
+pub struct Filter {
+    pub status: Option<TaskStatus>,
+    pub category: Option<String>,
+    /// Only tasks with this tag
+    pub tag: Option<String>,
+    pub owner: Option<String>,
+    /// Only tasks created at or after this time
+    pub created_after: Option<DateTime<Local>>,
+    /// Only tasks created before this time
+    pub created_before: Option<DateTime<Local>>,
+    /// Only tasks changed after this time
+    pub updated_after: Option<DateTime<Local>>,
+    /// Only tasks with this priority number or lower, so at least this urgent
+    pub max_priority: Option<u8>,
+    /// Only tasks with this priority number or higher, so at most this urgent
+    pub min_priority: Option<u8>,
+}
+
+impl Filter {
+    /// Return a copy of this filter that also requires the given status
+    pub fn with_status(&self, status: TaskStatus) -> Self {
+        Filter {
+            status: Some(status),
+            ..self.clone()
+        }
+    }
 }
 
 impl Ord for Task {
@@ -54,20 +383,27 @@ impl Ord for Task {
 
     fn cmp(&self, other: &Self) -> Ordering {
         if self.status == TaskStatus::Active && other.status == TaskStatus::Active {
-            // These should be sorted in descending order by date (only)
-            return self.created_at.cmp(&other.created_at);
+            // These are sorted by date only, which lists the newest first
+            return self
+                .created_at
+                .cmp(&other.created_at)
+                .then_with(|| self.id.cmp(&other.id));
         }
         // In case of a priority tie we compare created_at - this step
         // is necessary to make implementations of `PartialEq` and
-        // `Ord` consistent.
+        // `Ord` consistent.  Tasks added together can share a timestamp,
+        // so the id breaks any remaining tie to keep the order total.
         other
             .status
             .cmp(&self.status)
             .then_with(|| other.priority.cmp(&self.priority))
             .then_with(|| other.created_at.cmp(&self.created_at))
+            .then_with(|| other.id.cmp(&self.id))
     }
 }
 
+// The heap relies on partial_cmp being the reverse of cmp, so keep it as is.
+#[allow(clippy::non_canonical_partial_ord_impl)]
 impl PartialOrd for Task {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(other.cmp(self))
@@ -76,13 +412,14 @@ impl PartialOrd for Task {
 
 impl Task {
     pub fn new(summary: String, category: String, is_interrupt: bool) -> Self {
+        let created_at = Local::now();
         Task {
-            id: Uuid::new_v4().simple().to_string(),
+            id: IdGenerator::Uuid.next_id(std::iter::empty()),
             summary,
             details: "".to_string(),
             priority: 3,
             category,
-            created_at: Local::now(),
+            created_at,
             status: match is_interrupt {
                 true => TaskStatus::Active,
                 false => TaskStatus::Backlog,
@@ -90,107 +427,158 @@ impl Task {
             // blocked_by: VecDeque::from(["9d8607f24".to_string(), "c1ed178b5".to_string()]),
             blocked_by: BTreeSet::new(),
             wake_at: None,
+            scheduled_at: None,
+            owner: None,
+            estimate: None,
+            tags: BTreeSet::new(),
+            due_at: None,
+            last_started_at: None,
+            completed_at: None,
+            updated_at: Some(created_at),
+            snooze_rule: None,
         }
     }
 
-    fn update_from(&mut self, other: &Task) {
+    /// Start building a task.  Unless set otherwise, it is a backlog task
+    /// in the "quick" category, with the defaults of Task::new.
+    pub fn builder() -> TaskBuilder {
+        TaskBuilder {
+            task: Task::new("New task".to_string(), "quick".to_string(), false),
+        }
+    }
+
+    /// When the task last changed, falling back to its creation time
+    pub fn last_updated(&self) -> DateTime<Local> {
+        self.updated_at.unwrap_or(self.created_at)
+    }
+
+    /// Record that the task has just changed
+    pub fn touch(&mut self) {
+        self.updated_at = Some(Local::now());
+    }
+
+    pub fn update_from(&mut self, other: &Task) {
         assert_eq!(self.id, other.id);
         self.priority = other.priority;
         self.summary.clone_from(&other.summary);
-        self.details.clone_from(&other.details);
+        self.details = normalize_details(&other.details);
         self.category.clone_from(&other.category);
         self.status.clone_from(&other.status);
         self.blocked_by.clone_from(&other.blocked_by);
         self.wake_at.clone_from(&other.wake_at);
+        self.scheduled_at.clone_from(&other.scheduled_at);
+        self.owner.clone_from(&other.owner);
+        self.estimate = other.estimate;
+        self.tags.clone_from(&other.tags);
+        self.due_at = other.due_at;
+        self.last_started_at = other.last_started_at;
+        self.completed_at = other.completed_at;
+        self.snooze_rule.clone_from(&other.snooze_rule);
+        self.touch();
+    }
+
+    /// Return true if the task meets every criterion of the filter
+    pub fn matches(&self, filter: &Filter) -> bool {
+        filter.status.as_ref().is_none_or(|s| &self.status == s)
+            && filter
+                .category
+                .as_ref()
+                .is_none_or(|c| normalize_label(&self.category) == normalize_label(c))
+            && filter.tag.as_ref().is_none_or(|tag| {
+                let tag = normalize_label(tag);
+                self.tags.iter().any(|t| normalize_label(t) == tag)
+            })
+            && filter
+                .owner
+                .as_ref()
+                .is_none_or(|o| self.owner.as_ref() == Some(o))
+            && filter.created_after.is_none_or(|t| self.created_at >= t)
+            && filter.created_before.is_none_or(|t| self.created_at < t)
+            && filter.updated_after.is_none_or(|t| self.last_updated() > t)
+            && filter.max_priority.is_none_or(|p| self.priority <= p)
+            && filter.min_priority.is_none_or(|p| self.priority >= p)
     }
 
     pub fn block_on(&mut self, blocker_id: String) {
         self.blocked_by.insert(blocker_id);
         self.status = TaskStatus::Blocked;
+        self.touch();
     }
 
-    /// Invoke the default editor to edit the task
-    pub fn invoke_editor(&mut self) -> Result<(), io::Error> {
+    /// Invoke the default editor to edit the task.  Returns whether the
+    /// task was updated.
+    pub fn invoke_editor(&mut self) -> Result<bool, io::Error> {
         let serialized = serde_json::to_string_pretty(&self)?;
-
-        // Create a temporary file
-        let mut temp_file = tempfile::Builder::new().suffix(".json").tempfile()?;
-
-        // Write some content to the temporary file
-        writeln!(temp_file, "{}", serialized)?;
-
-        // Get the path to the temporary file
-        let file_path = temp_file.path();
-
-        // Determine the default editor based on the environment variables
-        let editor = env::var("EDITOR").unwrap_or_else(|_| "nano".to_string());
-
-        if !cfg!(test) {
-            // Invoke the default editor to open the temporary file,
-            // as long as we're not running tests
-            Command::new(editor)
-                .arg(file_path)
-                .status()
-                .expect("Failed to open the editor");
+        let updates = edit_text(&serialized, ".json")?;
+        match self.apply_edit(&updates) {
+            Ok(true) => Ok(true),
+            Ok(false) => {
+                println!("The edited task was empty, so the edit was cancelled");
+                Ok(false)
+            }
+            Err(e) => {
+                println!("Couldn't read the edited task, so it is unchanged: {}", e);
+                Ok(false)
+            }
         }
+    }
 
-        // Reopen the temporary file for reading
-        let file_path = temp_file.path();
-        let mut file = File::open(file_path)?;
-
-        // Read the entire contents into a buffer
-        let mut updates = String::new();
-        file.read_to_string(&mut updates)?;
-
-        // Deserialize the buffer into a Task.  If it can't be parsed,
-        // default to the original task values
-        let updated_task: Task = serde_json::from_str(&updates).unwrap_or(self.clone());
+    /// Update the task from the buffer it was edited in.  A buffer left
+    /// empty (or only whitespace) cancels the edit, returning false, and one
+    /// that can't be parsed is an error; either way the task is unchanged.
+    pub fn apply_edit(&mut self, updates: &str) -> Result<bool, serde_json::Error> {
+        if updates.trim().is_empty() {
+            return Ok(false);
+        }
+        let updated_task: Task = serde_json::from_str(updates)?;
         self.update_from(&updated_task);
-
-        Ok(())
+        Ok(true)
     }
 
-    /// Invoke the default editor to edit the task
-    pub fn invoke_editor_for_details(&mut self) -> Result<(), io::Error> {
-        // let serialized = serde_json::to_string_pretty(&self)?;
-
-        // Create a temporary file
-        let mut temp_file = tempfile::Builder::new().suffix(".txt").tempfile()?;
+    /// Invoke the default editor to edit the task details.  Returns
+    /// whether the details changed; closing the editor without changing
+    /// them leaves the task untouched.
+    pub fn invoke_editor_for_details(&mut self) -> Result<bool, io::Error> {
+        let updates = normalize_details(&edit_text(&self.details, ".txt")?);
+        if updates == self.details {
+            return Ok(false);
+        }
+        self.details = updates;
 
-        // Write some content to the temporary file
-        writeln!(temp_file, "{}", self.details)?;
+        Ok(true)
+    }
+}
 
-        // Get the path to the temporary file
-        let file_path = temp_file.path();
+/// Open the contents in the default editor, in a temporary file with the
+/// given suffix, and return them as edited
+pub fn edit_text(contents: &str, suffix: &str) -> Result<String, io::Error> {
+    // Create a temporary file
+    let mut temp_file = tempfile::Builder::new().suffix(suffix).tempfile()?;
 
-        // Determine the default editor based on the environment variables
-        let editor = env::var("EDITOR").unwrap_or_else(|_| "nano".to_string());
+    // Write some content to the temporary file
+    writeln!(temp_file, "{}", contents)?;
 
-        if !cfg!(test) {
-            // Invoke the default editor to open the temporary file,
-            // as long as we're not running tests
-            Command::new(editor)
-                .arg(file_path)
-                .status()
-                .expect("Failed to open the editor");
-        }
+    // Get the path to the temporary file
+    let file_path = temp_file.path();
 
-        // Reopen the temporary file for reading
-        let file_path = temp_file.path();
-        let mut file = File::open(file_path)?;
+    let editor = resolve_editor();
 
-        // Read the entire contents into a buffer
-        let mut updates = String::new();
-        file.read_to_string(&mut updates)?;
-        self.details = updates.trim().to_string();
+    if !cfg!(test) {
+        // Invoke the default editor to open the temporary file,
+        // as long as we're not running tests
+        Command::new(editor)
+            .arg(file_path)
+            .status()
+            .expect("Failed to open the editor");
+    }
 
-        // Deserialize the buffer into a Task.  If it can't be parsed,
-        // default to the original task values
-        // let updated_descripiton: Task = serde_json::from_str(&updates).unwrap_or(self.clone());
-        // self.update_from(&updated_task);
+    // Reopen the temporary file for reading
+    let mut file = File::open(file_path)?;
 
-        Ok(())
-    }
+    // Read the entire contents into a buffer
+    let mut updates = String::new();
+    file.read_to_string(&mut updates)?;
+    Ok(updates)
 }
 
 // xref: /usr/local/develop/rust-commandline-example/src/main.rs
@@ -198,6 +586,7 @@ impl Task {
 #[cfg(test)]
 pub mod tests {
     use super::*;
+    use std::collections::BinaryHeap;
 
     /// Verify default task settings
     #[test]
@@ -209,4 +598,310 @@ pub mod tests {
         assert_eq!(task.status, TaskStatus::Active);
         assert_eq!(task.id.len(), 32);
     }
+
+    #[test]
+    fn check_task_builder() {
+        let task = Task::builder().build();
+        assert_eq!(task.summary, "New task");
+        assert_eq!(task.category, "quick");
+        assert_eq!(task.priority, 3);
+        assert_eq!(task.status, TaskStatus::Backlog);
+        assert_eq!(task.due_at, None);
+        assert_eq!(task.id.len(), 32);
+
+        let due = Local::now() + Duration::days(1);
+        let task = Task::builder()
+            .id("a7")
+            .summary("Renew passport")
+            .category("errands")
+            .priority(1)
+            .status(TaskStatus::Active)
+            .due(due)
+            .build();
+        assert_eq!(task.id, "a7");
+        assert_eq!(task.summary, "Renew passport");
+        assert_eq!(task.category, "errands");
+        assert_eq!(task.priority, 1);
+        assert_eq!(task.status, TaskStatus::Active);
+        assert_eq!(task.due_at, Some(due));
+
+        // Each built task gets its own id
+        assert_ne!(Task::builder().build().id, Task::builder().build().id);
+    }
+
+    #[test]
+    fn check_ordering_with_shared_timestamp() {
+        for status in [TaskStatus::Active, TaskStatus::Backlog] {
+            let mut first = Task::new("first".to_string(), "".to_string(), false);
+            first.status = status.clone();
+            let mut second = first.clone();
+            first.id = "aaaa".to_string();
+            second.id = "bbbb".to_string();
+
+            assert_ne!(first.cmp(&second), Ordering::Equal);
+            assert_eq!(first.cmp(&second), second.cmp(&first).reverse());
+
+            let forward: BinaryHeap<Task> = [first.clone(), second.clone()].into();
+            let backward: BinaryHeap<Task> = [second.clone(), first.clone()].into();
+            let forward: Vec<String> = forward
+                .into_sorted_vec()
+                .into_iter()
+                .map(|t| t.id)
+                .collect();
+            let backward: Vec<String> = backward
+                .into_sorted_vec()
+                .into_iter()
+                .map(|t| t.id)
+                .collect();
+            assert_eq!(forward, backward);
+        }
+    }
+
+    #[test]
+    fn check_estimate_serialized_as_seconds() {
+        let mut task = Task::new("Estimate".to_string(), "Category".to_string(), false);
+        task.estimate = Some(parse_estimate("1h 30m").unwrap());
+        let serialized = serde_json::to_string(&task).unwrap();
+        assert!(serialized.contains("\"estimate\":5400"));
+        let task: Task = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(task.estimate, Some(Duration::minutes(90)));
+        assert!(parse_estimate("soon").is_err());
+    }
+
+    #[test]
+    fn check_filter_criteria() {
+        let mut task = Task::new("Filtered".to_string(), "quick".to_string(), false);
+        assert!(task.matches(&Filter::default()));
+
+        assert!(task.matches(&Filter::default().with_status(TaskStatus::Backlog)));
+        assert!(!task.matches(&Filter::default().with_status(TaskStatus::Active)));
+
+        let category = |c: &str| Filter {
+            category: Some(c.to_string()),
+            ..Default::default()
+        };
+        assert!(task.matches(&category("quick")));
+        assert!(!task.matches(&category("slow")));
+
+        let owner = |o: &str| Filter {
+            owner: Some(o.to_string()),
+            ..Default::default()
+        };
+        assert!(!task.matches(&owner("alice")));
+        task.owner = Some("alice".to_string());
+        assert!(task.matches(&owner("alice")));
+        assert!(!task.matches(&owner("bob")));
+
+        let hour = Duration::hours(1);
+        let created = |after, before| Filter {
+            created_after: after,
+            created_before: before,
+            ..Default::default()
+        };
+        assert!(task.matches(&created(Some(task.created_at), None)));
+        assert!(!task.matches(&created(Some(task.created_at + hour), None)));
+        assert!(task.matches(&created(None, Some(task.created_at + hour))));
+        assert!(!task.matches(&created(None, Some(task.created_at))));
+
+        // Priority 3 is inside the band 2-3, and outside 1-2 and 4-5
+        let band = |min_priority, max_priority| Filter {
+            min_priority,
+            max_priority,
+            ..Default::default()
+        };
+        assert!(task.matches(&band(Some(2), Some(3))));
+        assert!(!task.matches(&band(None, Some(2))));
+        assert!(!task.matches(&band(Some(4), None)));
+    }
+
+    #[test]
+    fn check_filter_ignores_label_case() {
+        let mut task = Task::new("Filtered".to_string(), "Work".to_string(), false);
+        task.tags.insert("Urgent".to_string());
+        let filter = Filter {
+            category: Some("work ".to_string()),
+            tag: Some("URGENT".to_string()),
+            ..Default::default()
+        };
+        assert!(task.matches(&filter));
+        assert!(!task.matches(&Filter {
+            tag: Some("later".to_string()),
+            ..Default::default()
+        }));
+        assert_eq!(normalize_label("  Home Office "), "home office");
+    }
+
+    #[test]
+    fn check_filter_combination() {
+        let mut task = Task::new("Filtered".to_string(), "quick".to_string(), true);
+        task.owner = Some("alice".to_string());
+        let filter = Filter {
+            category: Some("quick".to_string()),
+            owner: Some("alice".to_string()),
+            created_before: Some(task.created_at + Duration::hours(1)),
+            ..Default::default()
+        };
+        assert!(task.matches(&filter.with_status(TaskStatus::Active)));
+        assert!(!task.matches(&filter.with_status(TaskStatus::Backlog)));
+        task.category = "slow".to_string();
+        assert!(!task.matches(&filter));
+    }
+
+    #[test]
+    fn check_parse_priority() {
+        assert_eq!(parse_priority("high"), Ok(1));
+        assert_eq!(parse_priority("Medium"), Ok(3));
+        assert_eq!(parse_priority("low"), Ok(5));
+        assert_eq!(parse_priority("3"), Ok(3));
+        assert!(parse_priority("urgent").is_err());
+        assert!(parse_priority("0").is_err());
+        assert!(parse_priority("6").is_err());
+    }
+
+    #[test]
+    fn check_short_id_generator() {
+        let existing = ["a1", "a7", "9d8607f24c1ed178b5", "abc"];
+        assert_eq!(
+            IdGenerator::Short.next_id(existing.iter().copied()),
+            "a8".to_string()
+        );
+        assert_eq!(IdGenerator::Short.next_id(std::iter::empty()), "a1");
+        assert_eq!(
+            IdGenerator::Uuid.next_id(existing.iter().copied()).len(),
+            32
+        );
+        assert_eq!(parse_id_generator("Short"), Ok(IdGenerator::Short));
+        assert!(parse_id_generator("base64").is_err());
+        assert_eq!(short_id("a12"), "a12");
+        assert_eq!(short_id("9d8607f24c1ed178b5"), "9d8607f24");
+    }
+
+    #[test]
+    fn check_empty_edit_cancels() {
+        let mut task = Task::new("Original".to_string(), "quick".to_string(), false);
+        let original = task.clone();
+        assert!(!task.apply_edit("").unwrap());
+        assert!(!task.apply_edit("  \n\n").unwrap());
+        assert!(task.apply_edit("{ not json").is_err());
+        assert!(task == original);
+
+        let mut edited = original.clone();
+        edited.summary = "Edited".to_string();
+        assert!(task
+            .apply_edit(&serde_json::to_string(&edited).unwrap())
+            .unwrap());
+        assert_eq!(task.summary, "Edited");
+    }
+
+    #[test]
+    fn check_parse_summary() {
+        assert_eq!(parse_summary("  hi  ", 2), Ok("hi".to_string()));
+        assert!(parse_summary("", 2).is_err());
+        assert!(parse_summary(" \t\n", 2).is_err());
+        assert_eq!(
+            parse_summary("hello", 4),
+            Err(
+                "Task summary is 5 characters long, more than the limit of 4.  \
+                 Put the rest in the details instead"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn check_whitespace_details_are_pruned() {
+        let mut task = Task::new("Details".to_string(), "quick".to_string(), false);
+        task.details = "   \n  ".to_string();
+        task.invoke_editor_for_details().unwrap();
+        assert_eq!(task.details, "");
+
+        let mut edited = task.clone();
+        edited.details = "\n\t \n".to_string();
+        task.update_from(&edited);
+        assert_eq!(task.details, "");
+        edited.details = "  kept\n    indented\n".to_string();
+        task.update_from(&edited);
+        assert_eq!(task.details, "  kept\n    indented");
+    }
+
+    #[test]
+    fn check_editor_precedence() {
+        let some = |editor: &str| Some(editor.to_string());
+        assert_eq!(choose_editor(some("emacs"), some("vim")), "emacs");
+        assert_eq!(choose_editor(None, some("vim")), "vim");
+        assert_eq!(choose_editor(some(""), some("vim")), "vim");
+        assert_eq!(choose_editor(None, None), "nano");
+    }
+
+    #[test]
+    fn check_status_from_str() {
+        for status in [
+            TaskStatus::Active,
+            TaskStatus::Backlog,
+            TaskStatus::Blocked,
+            TaskStatus::Sleeping,
+            TaskStatus::Scheduled,
+            TaskStatus::Completed,
+        ] {
+            let name = status.to_string();
+            assert_eq!(name.parse::<TaskStatus>(), Ok(status.clone()));
+            assert_eq!(TaskStatus::try_from(name.as_str()), Ok(status.clone()));
+            let saved = serde_json::to_string(&status).unwrap();
+            assert_eq!(saved, format!("\"{}\"", name));
+        }
+        assert_eq!(" Sleeping".parse(), Ok(TaskStatus::Sleeping));
+        assert_eq!(
+            "done".parse::<TaskStatus>(),
+            Err("Invalid status 'done', expected active, backlog, blocked, sleeping, scheduled or completed".to_string())
+        );
+    }
+
+    #[test]
+    fn check_parse_when() {
+        let when = parse_when("2030-01-02 09:30").unwrap();
+        assert_eq!(when.format("%F %R").to_string(), "2030-01-02 09:30");
+        let when = parse_when("2030-01-02").unwrap();
+        assert_eq!(when.format("%F %R").to_string(), "2030-01-02 00:00");
+        let when = parse_when("2h").unwrap();
+        assert!(when > Local::now() + Duration::minutes(119));
+        assert!(parse_when("someday").is_err());
+    }
+
+    #[test]
+    fn check_parse_timestamp() {
+        let timestamp = parse_timestamp("2030-01-02T09:30:00+00:00").unwrap();
+        assert_eq!(timestamp.timestamp(), 1893576600);
+        assert!(parse_timestamp("2030-01-02").is_ok());
+        assert!(parse_timestamp("2h").is_err());
+    }
+
+    #[test]
+    fn check_updated_at_defaults_to_created_at() {
+        let mut task = Task::new("Old".to_string(), "quick".to_string(), false);
+        let mut value = serde_json::to_value(&task).unwrap();
+        value.as_object_mut().unwrap().remove("updated_at");
+        let old: Task = serde_json::from_value(value).unwrap();
+        assert_eq!(old.last_updated(), task.created_at);
+
+        task.block_on("abc".to_string());
+        assert!(task.last_updated() >= task.created_at);
+        let filter = Filter {
+            updated_after: Some(task.created_at - Duration::seconds(1)),
+            ..Default::default()
+        };
+        assert!(task.matches(&filter));
+        assert!(!old.matches(&Filter {
+            updated_after: Some(task.created_at),
+            ..Default::default()
+        }));
+    }
+
+    #[test]
+    fn check_scheduled_at_defaults_when_missing() {
+        let task = Task::new("Old".to_string(), "quick".to_string(), false);
+        let mut value = serde_json::to_value(&task).unwrap();
+        value.as_object_mut().unwrap().remove("scheduled_at");
+        let task: Task = serde_json::from_value(value).unwrap();
+        assert_eq!(task.scheduled_at, None);
+    }
 }
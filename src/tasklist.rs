@@ -1,11 +1,24 @@
-use crate::task::{Task, TaskStatus};
-use chrono::Local;
-use parse_duration::parse;
-use std::collections::{BTreeSet, BinaryHeap};
+use crate::eventlog::{self, Event};
+use crate::snooze::parse_snooze_rule;
+use crate::task::{
+    edit_text, normalize_label, parse_estimate, parse_priority, parse_summary, short_id, Filter,
+    IdGenerator, Task, TaskBuilder, TaskStatus, DEFAULT_MAX_SUMMARY_LENGTH,
+};
+use crate::taskstore::{Backend, TaskStore};
+use chrono::{DateTime, Duration, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
 use std::fs;
 use std::fs::File;
 use std::io::{self, Write};
 
+/// Default limit on the number of simultaneously active tasks
+pub const DEFAULT_MAX_ACTIVE: usize = 1;
+
+/// How long to wait for another instance to release the database lock
+const LOCK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(1);
+const LOCK_RETRY_INTERVAL: std::time::Duration = std::time::Duration::from_millis(50);
+
 /// Task list data structure, includeing a priority queue of tasks
 /// and a database path.
 pub struct TaskList {
@@ -14,83 +27,518 @@ pub struct TaskList {
     // pub blocked_tasks: VecDeque<Task>,
     // pub completed_tasks: VecDeque<Task>,
     // pub sleeping_tasks: VecDeque<Task>,
-    pub tasks: BinaryHeap<Task>,
+    pub tasks: TaskStore,
     pub db_path: String,
+    /// Maximum number of tasks that may be active at once (0 means unlimited)
+    pub max_active: usize,
+    /// Maximum number of characters in a summary
+    pub max_summary_length: usize,
+    /// Require ids to match a task id in full, rather than as a prefix
+    pub exact: bool,
+    /// Path of the event log that state transitions are appended to, if any
+    pub event_log: Option<String>,
+    /// Set when the tasks have changed since they were loaded
+    pub dirty: bool,
+    /// Never save changes back to the database
+    pub no_save: bool,
+    /// How ids are chosen for tasks created through task_builder
+    pub id_generator: IdGenerator,
+    /// Which active tasks come first, and so are acted on by default
+    pub active_order: ActiveOrder,
+    /// The order tasks are saved in
+    pub db_order: DbOrder,
+    /// Don't print incidental messages, such as tasks waking up
+    pub quiet: bool,
+    /// Incidental messages printed so far
+    pub notes: Vec<String>,
+}
+
+/// The order of active tasks, which decides the task that commands such as
+/// show, stop and complete act on when no id is given
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ActiveOrder {
+    /// The oldest active task comes first
+    Oldest,
+    /// The newest active task comes first, the order of Task::cmp
+    #[default]
+    Newest,
+}
+
+/// Parse an active task order, oldest or newest.
+pub fn parse_active_order(s: &str) -> Result<ActiveOrder, String> {
+    match s.trim().to_lowercase().as_str() {
+        "oldest" => Ok(ActiveOrder::Oldest),
+        "newest" => Ok(ActiveOrder::Newest),
+        _ => Err(format!(
+            "Invalid active order '{}', expected oldest or newest",
+            s
+        )),
+    }
+}
+
+/// Why an id doesn't select exactly one task
+#[derive(Debug, PartialEq)]
+pub enum LookupError {
+    /// No task id matches the id
+    NotFound(String),
+    /// Several task ids match the id, this many
+    Ambiguous(String, usize),
+}
+
+impl std::fmt::Display for LookupError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (id, count) = match self {
+            LookupError::NotFound(id) => (id, 0),
+            LookupError::Ambiguous(id, count) => (id, *count),
+        };
+        write!(
+            f,
+            "Id '{}' does not uniquely match one task.  It matches {}",
+            id, count
+        )
+    }
+}
+
+impl std::error::Error for LookupError {}
+
+/// The order tasks are written to the database file in
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DbOrder {
+    /// The order tasks are listed in
+    #[default]
+    Display,
+    /// Sorted by id, which never changes, so edits move no other tasks and
+    /// a version controlled database diffs cleanly
+    Id,
+}
+
+/// Parse a database order, display or id.
+pub fn parse_db_order(s: &str) -> Result<DbOrder, String> {
+    match s.trim().to_lowercase().as_str() {
+        "display" => Ok(DbOrder::Display),
+        "id" => Ok(DbOrder::Id),
+        _ => Err(format!("Invalid db order '{}', expected display or id", s)),
+    }
 }
 
 impl Drop for TaskList {
     fn drop(&mut self) {
-        self.save().unwrap();
+        // Only rewrite the database when something actually changed
+        if self.dirty && !self.no_save {
+            self.save().unwrap();
+        }
         self.tasks.clear();
     }
 }
 
 impl TaskList {
-    /// Create a new task list.
-    pub fn new(db_path: String) -> Self {
-        let result = TaskList::load(db_path.clone());
-
-        match result {
-            Ok(tasks) => {
-                let mut task_list = TaskList { tasks, db_path };
-                let awakened = task_list.wake_tasks();
-                if awakened > 0 {
-                    println!("Awakened {} task(s)", awakened);
-                }
-                let unblocked = task_list.unblock_tasks();
-                if unblocked > 0 {
-                    println!("Unblocked {} task(s)", unblocked);
-                }
-                task_list
+    /// Open the task list, optionally without printing incidental messages
+    /// about the tasks woken, started or unblocked on loading.  A missing
+    /// database is created empty, but any other failure to load it is an
+    /// error, so that a database that doesn't parse is never overwritten.
+    pub fn open(db_path: String, quiet: bool) -> Result<Self, io::Error> {
+        let (tasks, missing) = match TaskList::load(db_path.clone()) {
+            Ok(tasks) => (tasks, false),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => (vec![], true),
+            Err(e) => return Err(e),
+        };
+        let mut task_list = TaskList {
+            tasks: TaskStore::from_tasks(Backend::default(), tasks),
+            db_path,
+            max_active: DEFAULT_MAX_ACTIVE,
+            max_summary_length: DEFAULT_MAX_SUMMARY_LENGTH,
+            exact: false,
+            event_log: None,
+            dirty: false,
+            no_save: false,
+            id_generator: IdGenerator::default(),
+            active_order: ActiveOrder::default(),
+            db_order: DbOrder::default(),
+            quiet,
+            notes: vec![],
+        };
+        if missing {
+            // Write the empty database now, so it exists even if
+            // this run never gets as far as saving
+            if let Err(e) = task_list.create() {
+                eprintln!("Failed to create {}: {}", task_list.db_path, e);
             }
-            Err(_) => TaskList {
-                tasks: BinaryHeap::new(),
-                db_path,
-            },
+            return Ok(task_list);
+        }
+        let awakened = task_list.wake_tasks();
+        if awakened > 0 {
+            task_list.note(format!("Awakened {} task(s)", awakened));
+        }
+        let started = task_list.activate_scheduled_tasks();
+        if started > 0 {
+            task_list.note(format!("Started {} scheduled task(s)", started));
+        }
+        let unblocked = task_list.unblock_tasks();
+        if unblocked > 0 {
+            task_list.note(format!("Unblocked {} task(s)", unblocked));
         }
+        Ok(task_list)
+    }
+
+    /// Print an incidental message, unless quiet.  Errors and the results
+    /// of commands are printed directly instead.
+    pub fn note(&mut self, message: String) {
+        if !self.quiet {
+            println!("{}", message);
+            self.notes.push(message);
+        }
+    }
+
+    /// Create the database file, and its directory if needed.
+    fn create(&self) -> Result<(), io::Error> {
+        if let Some(parent) = std::path::Path::new(&self.db_path).parent() {
+            fs::create_dir_all(parent)?;
+        }
+        self.save()
     }
 
     /// Save the task list to the database file.
     pub fn save(&self) -> Result<(), io::Error> {
-        let serialized = serde_json::to_string_pretty(&self.tasks)?;
-        let mut file = File::create(&self.db_path)?;
+        self.save_to(&self.db_path)
+    }
+
+    /// Save the task list to the given file, in the configured order.
+    pub fn save_to(&self, path: &str) -> Result<(), io::Error> {
+        let mut tasks = self.tasks.sorted();
+        if self.db_order == DbOrder::Id {
+            tasks.sort_by(|a, b| a.id.cmp(&b.id));
+        }
+        let serialized = serde_json::to_string_pretty(&tasks)?;
+        let mut file = File::create(path)?;
         file.write_all(serialized.as_bytes())?;
         Ok(())
     }
 
+    /// File holding the task order of the last numbered listing.
+    fn ordinals_path(&self) -> String {
+        format!("{}.ordinals", self.db_path)
+    }
+
+    /// Sorted ids of every task, used to detect a stale ordinal map.
+    fn all_ids(&self) -> Vec<String> {
+        let mut ids: Vec<String> = self.tasks.iter().map(|task| task.id.clone()).collect();
+        ids.sort();
+        ids
+    }
+
+    /// Remember the order tasks were listed in, so they can later be
+    /// referred to as #1, #2, ...
+    pub fn save_ordinals(&self, listed_ids: &[String]) -> Result<(), io::Error> {
+        let ordinals = OrdinalMap {
+            listed_ids: listed_ids.to_vec(),
+            all_ids: self.all_ids(),
+        };
+        fs::write(self.ordinals_path(), serde_json::to_string(&ordinals)?)
+    }
+
+    /// Return the id of the task listed as number n (counting from 1) in the
+    /// last numbered listing.  Returns None, with a warning, if there was no
+    /// such listing or tasks have been added or removed since.
+    pub fn resolve_ordinal(&self, n: usize) -> Option<String> {
+        let contents = fs::read_to_string(self.ordinals_path()).ok();
+        let ordinals: Option<OrdinalMap> = contents.and_then(|c| serde_json::from_str(&c).ok());
+        let Some(ordinals) = ordinals else {
+            println!(
+                "No numbered listing to resolve #{} against, run 'list --numbered'",
+                n
+            );
+            return None;
+        };
+        if ordinals.all_ids != self.all_ids() {
+            println!(
+                "Tasks have changed since the last numbered listing, run 'list --numbered' again"
+            );
+            return None;
+        }
+        let id = n
+            .checked_sub(1)
+            .and_then(|i| ordinals.listed_ids.get(i))
+            .cloned();
+        if id.is_none() {
+            println!("No task #{} in the last numbered listing", n);
+        }
+        id
+    }
+
+    /// Replace any "#n" references with the id of the task they refer to.
+    /// References that can't be resolved are passed through unchanged.
+    pub fn resolve_ordinals(&self, ids: Vec<String>) -> Vec<String> {
+        ids.into_iter()
+            .map(
+                |id| match id.strip_prefix('#').and_then(|n| n.parse::<usize>().ok()) {
+                    Some(n) => self.resolve_ordinal(n).unwrap_or(id),
+                    None => id,
+                },
+            )
+            .collect()
+    }
+
+    /// Default backup file for the database.
+    pub fn backup_path(&self) -> String {
+        format!("{}.bak", self.db_path)
+    }
+
+    /// Replace the task list with the contents of a backup file.  The backup
+    /// must parse, and the current tasks are first written to the default
+    /// backup path so the restore can itself be undone.
+    /// Returns the number of tasks restored.
+    pub fn restore_from(&mut self, path: &str) -> Result<usize, io::Error> {
+        let tasks = TaskList::load(path.to_string())?;
+        self.save_to(&self.backup_path())?;
+        self.tasks = TaskStore::from_tasks(self.tasks.backend(), tasks);
+        self.dirty = true;
+        Ok(self.tasks.len())
+    }
+
     /// Load the task list from the database file.
-    pub fn load(db_path: String) -> Result<BinaryHeap<Task>, io::Error> {
-        let contents = fs::read_to_string(db_path)?;
-        let tasks: BinaryHeap<Task> = serde_json::from_str(&contents)?;
+    pub fn load(db_path: String) -> Result<Vec<Task>, io::Error> {
+        let contents = fs::read_to_string(&db_path)?;
+        let tasks: Vec<Task> = serde_json::from_str(&contents).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                describe_parse_error(&db_path, &contents, &e),
+            )
+        })?;
         Ok(tasks)
     }
 
+    /// Return true if the id only selects tasks by their whole id: in exact
+    /// mode, or when the id is some task's whole id, so short ids like "a1"
+    /// stay usable once "a10" exists.  Check once per lookup, not per task.
+    fn matches_whole_id(&self, id: &str) -> bool {
+        self.exact || self.tasks.iter().any(|task| task.id == id)
+    }
+
+    /// Return the one task the id selects
+    pub fn find_unique(&self, id: &str) -> Result<&Task, LookupError> {
+        let exact = self.matches_whole_id(id);
+        let mut matches = self
+            .tasks
+            .iter()
+            .filter(|task| id_matches(&task.id, id, exact));
+        match (matches.next(), matches.count()) {
+            (Some(task), 0) => Ok(task),
+            (Some(_), others) => Err(LookupError::Ambiguous(id.to_string(), others + 1)),
+            (None, _) => Err(LookupError::NotFound(id.to_string())),
+        }
+    }
+
+    /// Return the one task the id selects, or print why there isn't one
+    fn find_or_report(&self, id: &str) -> Option<&Task> {
+        self.find_unique(id).inspect_err(|e| println!("{}", e)).ok()
+    }
+
+    /// Start building a task whose id comes from the list's id generator,
+    /// unique among the tasks already in the list.  Building the task
+    /// doesn't add it; pass it to add_task for that.
+    pub fn task_builder(&self) -> TaskBuilder {
+        Task::builder().id(self
+            .id_generator
+            .next_id(self.tasks.iter().map(|task| task.id.as_str())))
+    }
+
+    /// Record a state transition in the event log.  Logging is best effort,
+    /// so a failure is reported but never stops the operation itself.
+    /// Nothing is logged with no_save set, as the transition won't be saved.
+    fn log_event(
+        &self,
+        op: &str,
+        task_id: &str,
+        from_status: Option<TaskStatus>,
+        to_status: Option<TaskStatus>,
+    ) {
+        self.log_event_at(op, task_id, from_status, to_status, Local::now());
+    }
+
+    /// Record a state transition that took effect at the given time, such
+    /// as a start or completion backdated with --at
+    fn log_event_at(
+        &self,
+        op: &str,
+        task_id: &str,
+        from_status: Option<TaskStatus>,
+        to_status: Option<TaskStatus>,
+        at: DateTime<Local>,
+    ) {
+        if self.no_save {
+            return;
+        }
+        if let Some(path) = &self.event_log {
+            let event = Event {
+                ts: at,
+                ..Event::new(op, task_id, from_status, to_status)
+            };
+            if let Err(e) = eventlog::append(path, &event) {
+                eprintln!("Failed to write event log '{}': {}", path, e);
+            }
+        }
+    }
+
+    /// Replace the task having the same id as the one passed in.
+    fn replace_task(&mut self, mut updated_task: Task) {
+        updated_task.touch();
+        let id = updated_task.id.clone();
+        self.tasks.retain(|task| task.id != id);
+        self.tasks.push(updated_task);
+        self.dirty = true;
+    }
+
+    /// Move the tasks into the given storage backend
+    pub fn set_backend(&mut self, backend: Backend) {
+        self.tasks = std::mem::take(&mut self.tasks).with_backend(backend);
+    }
+
     /// Return the number of tasks in the list.
     pub fn num_tasks(&self) -> usize {
         self.tasks.len()
     }
 
+    /// Return the tasks matching the filter, in sorted order.  Active tasks
+    /// come first, in the configured active order.
+    pub fn filtered(&self, filter: &Filter) -> Vec<Task> {
+        let mut tasks = self.sorted(filter);
+        if self.active_order == ActiveOrder::Oldest {
+            let active_count = tasks
+                .iter()
+                .take_while(|task| task.status == TaskStatus::Active)
+                .count();
+            tasks[..active_count].reverse();
+        }
+        tasks
+    }
+
+    /// Return the tasks matching the filter, in the order of Task::cmp
+    fn sorted(&self, filter: &Filter) -> Vec<Task> {
+        self.tasks.sorted_matching(|task| task.matches(filter))
+    }
+
+    /// Return the tasks the id selects, in sorted order.
+    pub fn matching(&self, id: &str) -> Vec<Task> {
+        let exact = self.matches_whole_id(id);
+        self.tasks
+            .sorted_matching(|task| id_matches(&task.id, id, exact))
+    }
+
+    /// Return the number of active tasks in the list.
+    pub fn num_active(&self) -> usize {
+        self.tasks
+            .iter()
+            .filter(|task| task.status == TaskStatus::Active)
+            .count()
+    }
+
+    /// Return true if another task may be started without exceeding max_active.
+    pub fn can_start_another(&self) -> bool {
+        self.has_room_for(1)
+    }
+
+    /// Return true if count more tasks can be started without going over
+    /// the limit on active tasks
+    pub fn has_room_for(&self, count: usize) -> bool {
+        count == 0 || self.max_active == 0 || self.num_active() + count <= self.max_active
+    }
+
+    /// Print the currently active tasks, explaining why no more can be started.
+    pub fn report_active_limit(&self) {
+        print!("{}", self.describe_active_limit());
+    }
+
+    /// Describe why no more tasks can be started, naming the active tasks.
+    pub fn describe_active_limit(&self) -> String {
+        let mut message = format!(
+            "Can't start another task: {} task(s) already active (max {})\n",
+            self.num_active(),
+            self.max_active
+        );
+        for task in self.filtered(&Filter::default().with_status(TaskStatus::Active)) {
+            message.push_str(&format!("  {}  {}\n", short_id(&task.id), task.summary));
+        }
+        message
+    }
+
+    /// Sum the estimates of the active and backlog tasks.
+    /// Returns the total and the number of those tasks without an estimate.
+    pub fn remaining_estimate(&self) -> (Duration, usize) {
+        let mut total = Duration::zero();
+        let mut unestimated = 0;
+        for task in self
+            .tasks
+            .iter()
+            .filter(|task| task.status == TaskStatus::Active || task.status == TaskStatus::Backlog)
+        {
+            match task.estimate {
+                Some(estimate) => total = total + estimate,
+                None => unestimated += 1,
+            }
+        }
+        (total, unestimated)
+    }
+
     /// Wake any tasks whose snooze timer has expired
     pub fn wake_tasks(&mut self) -> usize {
         let mut num_woken = 0;
         let now = Local::now();
 
-        // let updated_tasks = self.tasks.clone().into_sorted_vec();
-        let mut updated_tasks: BinaryHeap<Task> = BinaryHeap::new();
-
-        // Process every node in the BinaryHeap
-        while let Some(mut task) = self.tasks.pop() {
+        // Process every task, restoring the order afterwards
+        self.tasks.update(|task| {
             if task.status == TaskStatus::Sleeping && task.wake_at.unwrap() <= now {
                 task.status = TaskStatus::Backlog;
                 task.wake_at = None;
+                task.touch();
                 num_woken += 1;
             }
-            updated_tasks.push(task);
+        });
+        if num_woken > 0 {
+            self.dirty = true;
         }
-        self.tasks = updated_tasks;
         num_woken
     }
 
+    /// Start any scheduled tasks whose start time has arrived, earliest
+    /// first, as long as the limit on active tasks allows.  Tasks with no
+    /// free slot stay scheduled until one frees up.
+    pub fn activate_scheduled_tasks(&mut self) -> usize {
+        let now = Local::now();
+        let mut due: Vec<Task> = self
+            .tasks
+            .iter()
+            .filter(|task| {
+                task.status == TaskStatus::Scheduled
+                    && task
+                        .scheduled_at
+                        .is_none_or(|scheduled_at| scheduled_at <= now)
+            })
+            .cloned()
+            .collect();
+        due.sort_by_key(|task| task.scheduled_at);
+        let mut started_count = 0;
+        for task in due {
+            if !self.can_start_another() {
+                break;
+            }
+            let mut updated_task = task.clone();
+            updated_task.status = TaskStatus::Active;
+            updated_task.last_started_at = Some(now);
+            updated_task.scheduled_at = None;
+            self.log_event(
+                "start",
+                &task.id,
+                Some(TaskStatus::Scheduled),
+                Some(TaskStatus::Active),
+            );
+            self.replace_task(updated_task);
+            started_count += 1;
+        }
+        started_count
+    }
+
     /// Check for tasks that are blocked on other tasks that have been completed
     /// or deleted and unblock them.
     /// Returns the number of tasks unblocked.
@@ -104,11 +552,8 @@ impl TaskList {
             .map(|task| task.id.clone())
             .collect();
 
-        // let updated_tasks = self.tasks.clone().into_sorted_vec();
-        let mut updated_tasks: BinaryHeap<Task> = BinaryHeap::new();
-
-        // Process every node in the BinaryHeap
-        while let Some(mut task) = self.tasks.pop() {
+        // Process every task, restoring the order afterwards
+        self.tasks.update(|task| {
             if task.status == TaskStatus::Blocked {
                 let intersection: BTreeSet<_> = task
                     .blocked_by
@@ -118,245 +563,760 @@ impl TaskList {
                 task.blocked_by = intersection;
                 if task.blocked_by.is_empty() {
                     task.status = TaskStatus::Backlog;
+                    task.touch();
                     num_unblocked += 1;
                 }
             }
-            updated_tasks.push(task);
+        });
+        if num_unblocked > 0 {
+            self.dirty = true;
         }
-        self.tasks = updated_tasks;
         num_unblocked
     }
 
-    /// Clone a task
-    pub fn copy_task(&mut self, id: String) -> Option<Task> {
-        let tasks = self.tasks.iter().filter(|task| task.id[0..id.len()] == id);
-        let match_count = tasks.count();
-        if match_count != 1 {
-            println!(
-                "Id '{}' does not uniquely match one task.  It matches {}",
-                id, match_count
-            );
-            return None;
+    /// Spread the priorities of backlog tasks evenly across the 1-5 range,
+    /// keeping their current sorted order.
+    /// Returns the number of tasks whose priority changed.
+    pub fn rebalance_priorities(&mut self) -> usize {
+        let backlog = self
+            .tasks
+            .sorted_matching(|task| task.status == TaskStatus::Backlog);
+        let count = backlog.len();
+
+        let mut num_changed = 0;
+        self.tasks.retain(|task| task.status != TaskStatus::Backlog);
+        for (index, mut task) in backlog.into_iter().enumerate() {
+            let priority = (1 + index * 5 / count) as u8;
+            if task.priority != priority {
+                task.priority = priority;
+                task.touch();
+                num_changed += 1;
+            }
+            self.tasks.push(task);
         }
+        if num_changed > 0 {
+            self.dirty = true;
+        }
+        num_changed
+    }
 
-        // There will be only one match, so unwrap is safe
-        let task = self
-            .tasks
-            .iter()
-            .find(|task| task.id[0..id.len()] == id)
-            .unwrap();
+    /// Move every task in the category `from` to the category `to`.
+    /// Returns the number of tasks changed.
+    pub fn rename_category(&mut self, from: &str, to: &str) -> usize {
+        let (from, to) = (normalize_label(from), normalize_label(to));
+        self.rebuild_changed(|task| {
+            if normalize_label(&task.category) != from || task.category == to {
+                return false;
+            }
+            task.category.clone_from(&to);
+            true
+        })
+    }
+
+    /// Replace the tag `from` with the tag `to` on every task that has it.
+    /// Returns the number of tasks changed.
+    pub fn rename_tag(&mut self, from: &str, to: &str) -> usize {
+        let (from, to) = (normalize_label(from), normalize_label(to));
+        self.rebuild_changed(|task| {
+            let before = task.tags.len();
+            task.tags.retain(|tag| normalize_label(tag) != from);
+            if task.tags.len() == before {
+                return false;
+            }
+            task.tags.insert(to.clone());
+            true
+        })
+    }
+
+    /// Rebuild the task order, applying the change to every task.  The
+    /// change returns whether it modified the task, and modified tasks are
+    /// touched.  Returns the number of tasks changed.
+    fn rebuild_changed(&mut self, mut change: impl FnMut(&mut Task) -> bool) -> usize {
+        let mut num_changed = 0;
+        self.tasks.update(|task| {
+            if change(task) {
+                task.touch();
+                num_changed += 1;
+            }
+        });
+        if num_changed > 0 {
+            self.dirty = true;
+        }
+        num_changed
+    }
 
-        Some(task.clone())
+    /// Clone a task
+    pub fn copy_task(&mut self, id: String) -> Option<Task> {
+        self.find_or_report(&id).cloned()
     }
 
     /// Add a task to the list.
     pub fn add_task(&mut self, task: Task) -> String {
         let id = task.id.clone();
+        self.log_event("add", &id, None, Some(task.status.clone()));
         self.tasks.push(task);
+        self.dirty = true;
         id
     }
 
     /// Remove the task whose id starts with the id string passed in.
-    pub fn remove_task(&mut self, id: String) {
-        // If we don't find exactly one task that starts with 'id',
-        // print a warning and return
-        let match_count = self
+    /// Returns the number of tasks removed, 0 or 1.
+    pub fn remove_task(&mut self, id: String) -> usize {
+        let Some(task) = self.find_or_report(&id) else {
+            return 0;
+        };
+        self.log_event("remove", &task.id, Some(task.status.clone()), None);
+        let id = task.id.clone();
+        self.tasks.retain(|task| task.id != id);
+        self.dirty = true;
+        1
+    }
+
+    /// Group the ids of open tasks whose summaries are the same, ignoring
+    /// case and spacing.  Each group is sorted oldest first, and only groups
+    /// of two or more tasks are returned.
+    pub fn find_duplicate_groups(&self) -> Vec<Vec<String>> {
+        let mut groups: BTreeMap<String, Vec<&Task>> = BTreeMap::new();
+        for task in self
             .tasks
             .iter()
-            .filter(|task| task.id[0..id.len()] == id)
-            .count();
-        if match_count != 1 {
-            println!(
-                "Id '{}' does not uniquely match one task.  It matches {}",
-                id, match_count
-            );
-            return;
+            .filter(|task| task.status != TaskStatus::Completed)
+        {
+            let key = task
+                .summary
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+                .to_lowercase();
+            groups.entry(key).or_default().push(task);
         }
-        self.tasks.retain(|task| task.id[0..id.len()] != id)
+        groups
+            .into_values()
+            .filter(|group| group.len() > 1)
+            .map(|mut group| {
+                group.sort_by_key(|task| (task.created_at, task.id.clone()));
+                group.into_iter().map(|task| task.id.clone()).collect()
+            })
+            .collect()
     }
 
-    /// Block the blockee on the blocker(s)
-    pub fn block_task_on(&mut self, blockee_id: &String, blocker_id: &String) -> usize {
-        // If we don't find exactly one task that starts with 'id',
-        // print a warning and return
-        let blockee_match_count = self
+    /// Merge the tasks with the given (full) ids into the first of them,
+    /// which should be the oldest.  The kept task gains the others' details,
+    /// tags and blockers and the earliest creation time, and is blocked if
+    /// it ends up with any blockers.  Tasks blocked on a merged task are
+    /// blocked on the kept task instead.
+    /// Returns the number of tasks merged away.
+    pub fn merge_tasks(&mut self, ids: &[String]) -> usize {
+        let Some((keep_id, merged_ids)) = ids.split_first() else {
+            return 0;
+        };
+        let Some(mut kept) = self.tasks.iter().find(|task| &task.id == keep_id).cloned() else {
+            return 0;
+        };
+        let merged: Vec<Task> = self
+            .tasks
+            .iter()
+            .filter(|task| merged_ids.contains(&task.id))
+            .cloned()
+            .collect();
+        for task in &merged {
+            if !task.details.is_empty() && !kept.details.contains(&task.details) {
+                if !kept.details.is_empty() {
+                    kept.details.push_str("\n\n");
+                }
+                kept.details.push_str(&task.details);
+            }
+            kept.tags.extend(task.tags.iter().cloned());
+            kept.blocked_by.extend(task.blocked_by.iter().cloned());
+            kept.created_at = kept.created_at.min(task.created_at);
+            self.log_event("merge", &task.id, Some(task.status.clone()), None);
+        }
+        kept.blocked_by.retain(|id| !ids.contains(id));
+        if kept.blocked_by.is_empty() && kept.status == TaskStatus::Blocked {
+            kept.status = TaskStatus::Backlog;
+        }
+        self.tasks.retain(|task| !merged_ids.contains(&task.id));
+        // A task that took on blockers is blocked, whatever its status was
+        if !kept.blocked_by.is_empty() && kept.status != TaskStatus::Completed {
+            kept.status = TaskStatus::Blocked;
+        }
+
+        // Point anything blocked on a merged task at the kept one
+        let blocked: Vec<Task> = self
             .tasks
             .iter()
-            .filter(|task| &task.id[0..blockee_id.len()] == blockee_id)
-            .count();
-        if blockee_match_count != 1 {
+            .filter(|task| task.blocked_by.iter().any(|id| merged_ids.contains(id)))
+            .cloned()
+            .collect();
+        for mut task in blocked {
+            task.blocked_by.retain(|id| !merged_ids.contains(id));
+            if task.id != kept.id {
+                task.blocked_by.insert(kept.id.clone());
+            }
+            self.replace_task(task);
+        }
+        self.replace_task(kept);
+        merged.len()
+    }
+
+    /// Block the blockee on the blocker(s)
+    pub fn block_task_on(&mut self, blockee_id: &str, blocker_id: &str) -> usize {
+        let blockee = match self.find_unique(blockee_id) {
+            Ok(task) => task,
+            Err(e) => {
+                println!("Blockee {}", e);
+                return 0;
+            }
+        };
+        let blocker = match self.find_unique(blocker_id) {
+            Ok(task) => task,
+            Err(e) => {
+                println!("Blocker {}", e);
+                return 0;
+            }
+        };
+
+        // A task blocked on itself could never be unblocked
+        if blockee.id == blocker.id {
+            println!("Task '{}' can't be blocked on itself", blockee.id);
+            return 0;
+        }
+        if blockee.status == TaskStatus::Completed {
             println!(
-                "Blockee Id '{}' does not uniquely match one task.  It matches {}",
-                blockee_id, blockee_match_count
+                "Task '{}' is already completed and can't be blocked",
+                blockee.id
             );
             return 0;
         }
-        let blocker_match_count = self
-            .tasks
-            .iter()
-            .filter(|task| &task.id[0..blocker_id.len()] == blocker_id)
-            .count();
-        if blocker_match_count != 1 {
+        if blocker.status == TaskStatus::Completed {
             println!(
-                "Blocker Id '{}' does not uniquely match one task.  It matches {}",
-                blocker_id, blocker_match_count
+                "Task '{}' is already completed and can't block anything",
+                blocker.id
             );
             return 0;
         }
-        // There will be only one match, so unwrap is safe
-        let blockee = self
-            .tasks
-            .iter()
-            .find(|task| &task.id[0..blockee_id.len()] == blockee_id)
-            .unwrap();
-        let blocker = self
-            .tasks
-            .iter()
-            .find(|task| &task.id[0..blocker_id.len()] == blocker_id)
-            .unwrap();
 
         let mut updated_task = blockee.clone();
         updated_task.block_on(blocker.id.clone());
         // updated_task.invoke_editor().unwrap_or_default();  // TODO: Handle errors
-        let id = blockee.id.clone();
-        self.tasks.retain(|task| task.id != id);
-        self.tasks.push(updated_task);
+        self.replace_task(updated_task);
 
         1
     }
 
     /// Edit the task whose id starts with the id string passed in.
+    /// Returns the number of tasks changed, so 0 if the edit was cancelled.
     pub fn edit_task(&mut self, id: String) -> usize {
-        let tasks = self.tasks.iter().filter(|task| task.id[0..id.len()] == id);
-        let match_count = tasks.count();
-        if match_count != 1 {
-            println!(
-                "Id '{}' does not uniquely match one task.  It matches {}",
-                id, match_count
-            );
+        let Some(task) = self.find_or_report(&id) else {
             return 0;
-        }
-
-        // There will be only one match, so unwrap is safe
-        let task = self
-            .tasks
-            .iter()
-            .find(|task| task.id[0..id.len()] == id)
-            .unwrap();
+        };
         let mut updated_task = task.clone();
-        updated_task.invoke_editor().unwrap_or_default(); // TODO: Handle errors
-        let id = task.id.clone();
-        self.tasks.retain(|task| task.id != id);
-        self.tasks.push(updated_task);
+        // TODO: Handle errors
+        if !updated_task.invoke_editor().unwrap_or_default() {
+            return 0;
+        }
+        self.replace_task(updated_task);
         1
     }
 
     /// Edit the details for the task whose id starts with the id string passed in.
+    /// Returns the number of tasks changed, so 0 if the details weren't changed.
     pub fn edit_task_details(&mut self, id: String) -> usize {
-        let tasks = self.tasks.iter().filter(|task| task.id[0..id.len()] == id);
-        let match_count = tasks.count();
-        if match_count != 1 {
-            println!(
-                "Id '{}' does not uniquely match one task.  It matches {}",
-                id, match_count
-            );
+        let Some(task) = self.find_or_report(&id) else {
             return 0;
-        }
-
-        // There will be only one match, so unwrap is safe
-        let task = self
-            .tasks
-            .iter()
-            .find(|task| task.id[0..id.len()] == id)
-            .unwrap();
+        };
         let mut updated_task = task.clone();
-        updated_task.invoke_editor_for_details().unwrap_or_default(); // TODO: Handle errors
-        let id = task.id.clone();
-        self.tasks.retain(|task| task.id != id);
-        self.tasks.push(updated_task);
+        // TODO: Handle errors
+        if !updated_task.invoke_editor_for_details().unwrap_or_default() {
+            return 0;
+        }
+        self.replace_task(updated_task);
         1
     }
 
-    /// Complete the task whose id starts with the id string passed in.
-    pub fn complete_task(&mut self, id: String) -> usize {
-        let tasks = self.tasks.iter().filter(|task| task.id[0..id.len()] == id);
-        let match_count = tasks.count();
-        if match_count != 1 {
-            println!(
-                "Id '{}' does not uniquely match one task.  It matches {}",
-                id, match_count
-            );
-            return 0;
+    /// Edit the tasks whose ids start with the id strings passed in, all
+    /// in a single editor buffer holding a JSON array of them.
+    pub fn edit_tasks_batch(&mut self, ids: Vec<String>) -> Result<usize, String> {
+        let mut tasks = vec![];
+        for id in ids {
+            if let Some(task) = self.find_or_report(&id) {
+                tasks.push(task.clone());
+            }
+        }
+        if tasks.is_empty() {
+            return Ok(0);
         }
+        let serialized = serde_json::to_string_pretty(&tasks).map_err(|e| e.to_string())?;
+        let edited = edit_text(&serialized, ".json").map_err(|e| e.to_string())?;
+        self.apply_batch_edit(&tasks, &edited)
+    }
 
-        // There will be only one match, so unwrap is safe
-        let task = self
-            .tasks
-            .iter()
-            .find(|task| task.id[0..id.len()] == id)
-            .unwrap();
-        let mut updated_task = task.clone();
-        updated_task.status = TaskStatus::Completed;
-        let id = task.id.clone();
-        self.tasks.retain(|task| task.id != id);
-        self.tasks.push(updated_task);
-        1
+    /// Apply an edited JSON array of tasks to the original tasks, matching
+    /// them up by id, and return the number of tasks changed.  Tasks taken
+    /// out of the array are left as they were, and entries that aren't one
+    /// of the original tasks (such as a task whose id was changed) are
+    /// ignored with a warning.
+    pub fn apply_batch_edit(&mut self, originals: &[Task], edited: &str) -> Result<usize, String> {
+        let entries: Vec<serde_json::Value> = serde_json::from_str(edited)
+            .map_err(|e| format!("Failed to parse the edited tasks: {}", e))?;
+        let mut edit_count = 0;
+        for (index, entry) in entries.into_iter().enumerate() {
+            let task: Task = match serde_json::from_value(entry) {
+                Ok(task) => task,
+                Err(e) => {
+                    println!("Ignoring edited task #{}: {}", index + 1, e);
+                    continue;
+                }
+            };
+            let Some(original) = originals.iter().find(|original| original.id == task.id) else {
+                println!(
+                    "Ignoring task '{}', which wasn't being edited.  Task ids can't be changed.",
+                    task.id
+                );
+                continue;
+            };
+            if &task == original {
+                continue;
+            }
+            let mut updated_task = original.clone();
+            updated_task.update_from(&task);
+            self.replace_task(updated_task);
+            edit_count += 1;
+        }
+        Ok(edit_count)
     }
 
-    /// Start the task whose id starts with the id string passed in.
-    pub fn start_task(&mut self, id: String) -> usize {
-        let tasks = self.tasks.iter().filter(|task| task.id[0..id.len()] == id);
-        let match_count = tasks.count();
-        if match_count != 1 {
-            println!(
-                "Id '{}' does not uniquely match one task.  It matches {}",
-                id, match_count
-            );
-            return 0;
+    /// Set fields of every task whose id starts with one of the id strings
+    /// passed in.  Every id and value is checked first, so either all the
+    /// tasks are changed or, on an error, none of them are.
+    pub fn update_fields(
+        &mut self,
+        ids: &[String],
+        fields: &[(String, String)],
+    ) -> Result<usize, String> {
+        let mut updated_tasks: Vec<Task> = vec![];
+        let mut started_count = 0;
+        for id in ids {
+            let task = self.find_unique(id).map_err(|e| e.to_string())?;
+            if updated_tasks.iter().any(|updated| updated.id == task.id) {
+                continue;
+            }
+            let mut updated_task = task.clone();
+            for (key, value) in fields {
+                self.apply_field(&mut updated_task, key, value)?;
+            }
+            if updated_task.status == TaskStatus::Active && task.status != TaskStatus::Active {
+                started_count += 1;
+            }
+            updated_tasks.push(updated_task);
+        }
+        if !self.has_room_for(started_count) {
+            return Err(self.describe_active_limit().trim_end().to_string());
         }
+        let updated_count = updated_tasks.len();
+        for updated_task in updated_tasks {
+            self.replace_task(updated_task);
+        }
+        Ok(updated_count)
+    }
 
-        // There will be only one match, so unwrap is safe
-        let task = self
-            .tasks
-            .iter()
-            .find(|task| task.id[0..id.len()] == id)
-            .unwrap();
+    /// Set a single field of a task, validating the new value
+    fn apply_field(&self, task: &mut Task, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "priority" => task.priority = parse_priority(value)?,
+            "summary" => task.summary = parse_summary(value, self.max_summary_length)?,
+            "category" => task.category = normalize_label(value),
+            "estimate" => {
+                task.estimate = match value {
+                    "" => None,
+                    estimate => Some(parse_estimate(estimate)?),
+                }
+            }
+            "owner" => {
+                task.owner = match value {
+                    "" => None,
+                    owner => Some(owner.to_string()),
+                }
+            }
+            "snooze" => {
+                task.snooze_rule = match value {
+                    "" => None,
+                    rule => {
+                        parse_snooze_rule(rule)?;
+                        Some(rule.to_string())
+                    }
+                }
+            }
+            "status" => {
+                task.status = match value.parse()? {
+                    status @ (TaskStatus::Blocked
+                    | TaskStatus::Sleeping
+                    | TaskStatus::Scheduled) => {
+                        return Err(format!(
+                            "Status '{}' can't be set directly, use the {} command",
+                            status,
+                            match status {
+                                TaskStatus::Blocked => "block",
+                                TaskStatus::Sleeping => "sleep",
+                                _ => "schedule",
+                            }
+                        ))
+                    }
+                    status => status,
+                };
+                task.blocked_by.clear();
+                task.wake_at = None;
+                task.scheduled_at = None;
+                task.completed_at = match task.status {
+                    TaskStatus::Completed => Some(Local::now()),
+                    _ => None,
+                };
+            }
+            _ => return Err(format!("Unknown field '{}'", key)),
+        }
+        Ok(())
+    }
+
+    /// Shift the priority of the task whose id starts with the id string
+    /// passed in by delta, clamped to 1-5.  A negative delta makes the task
+    /// more urgent.
+    pub fn bump_priority(&mut self, id: String, delta: i8) -> usize {
+        let Some(task) = self.find_or_report(&id) else {
+            return 0;
+        };
         let mut updated_task = task.clone();
-        updated_task.status = TaskStatus::Active;
-        let id = task.id.clone();
-        self.tasks.retain(|task| task.id != id);
-        self.tasks.push(updated_task);
+        updated_task.priority = (task.priority as i16 + delta as i16).clamp(1, 5) as u8;
+        self.replace_task(updated_task);
         1
     }
 
-    /// Suspend the task whose id starts with the id string passed in.
-    pub fn suspend_task(&mut self, id: String, duration: String) -> usize {
-        let tasks = self.tasks.iter().filter(|task| task.id[0..id.len()] == id);
-        let match_count = tasks.count();
-        if match_count != 1 {
-            println!(
-                "Id '{}' does not uniquely match one task.  It matches {}",
-                id, match_count
-            );
-            return 0;
+    /// Return the task whose id starts with the id string passed in to the
+    /// backlog, clearing its wake time, scheduled time, blockers and
+    /// completion time.
+    /// Returns the names of the things cleared, or None if the id doesn't
+    /// match exactly one task.
+    pub fn reset_task(&mut self, id: String) -> Option<Vec<&'static str>> {
+        let task = self.find_or_report(&id)?;
+        let mut cleared = vec![];
+        if task.status != TaskStatus::Backlog {
+            cleared.push("status");
         }
-
-        // There will be only one match, so unwrap is safe
-        let task = self
-            .tasks
-            .iter()
-            .find(|task| task.id[0..id.len()] == id)
-            .unwrap();
-        let mut updated_task = task.clone();
+        if task.wake_at.is_some() {
+            cleared.push("wake time");
+        }
+        if task.scheduled_at.is_some() {
+            cleared.push("scheduled time");
+        }
+        if !task.blocked_by.is_empty() {
+            cleared.push("blockers");
+        }
+        if task.completed_at.is_some() {
+            cleared.push("completion time");
+        }
+        if cleared.is_empty() {
+            return Some(cleared);
+        }
+        let mut updated_task = task.clone();
+        updated_task.status = TaskStatus::Backlog;
+        updated_task.wake_at = None;
+        updated_task.scheduled_at = None;
+        updated_task.blocked_by.clear();
+        updated_task.completed_at = None;
+        self.log_event(
+            "reset",
+            &task.id,
+            Some(task.status.clone()),
+            Some(TaskStatus::Backlog),
+        );
+        self.replace_task(updated_task);
+        Some(cleared)
+    }
+
+    /// Complete the task whose id starts with the id string passed in,
+    /// recording it as completed at the given time.  A task with a snooze
+    /// rule sleeps until the rule next fires instead.
+    pub fn complete_task_at(&mut self, id: String, at: DateTime<Local>) -> usize {
+        let Some(task) = self.find_or_report(&id) else {
+            return 0;
+        };
+        if at < task.created_at {
+            println!(
+                "Task '{}' can't be completed before it was created",
+                task.id
+            );
+            return 0;
+        }
+        let mut updated_task = task.clone();
+        // A completion backdated with --at still snoozes until the rule next
+        // fires from now, rather than to a time that has already passed
+        let op = if let Some(wake_at) = next_snooze(task, at.max(Local::now())) {
+            updated_task.status = TaskStatus::Sleeping;
+            updated_task.wake_at = Some(wake_at);
+            "snooze"
+        } else {
+            updated_task.status = TaskStatus::Completed;
+            updated_task.completed_at = Some(at);
+            "complete"
+        };
+        self.log_event_at(
+            op,
+            &task.id,
+            Some(task.status.clone()),
+            Some(updated_task.status.clone()),
+            at,
+        );
+        self.replace_task(updated_task);
+        1
+    }
+
+    /// Move the task whose id starts with the id string passed in straight
+    /// back to the backlog, as sweeping does with tasks left active too long.
+    pub fn sweep_task(&mut self, id: String) -> usize {
+        let Some(task) = self.find_or_report(&id) else {
+            return 0;
+        };
+        let mut updated_task = task.clone();
+        updated_task.status = TaskStatus::Backlog;
+        self.log_event(
+            "sweep",
+            &task.id,
+            Some(task.status.clone()),
+            Some(TaskStatus::Backlog),
+        );
+        self.replace_task(updated_task);
+        1
+    }
+
+    /// Stop the task whose id starts with the id string passed in, moving it
+    /// back to the backlog.  A task with a snooze rule sleeps until the rule
+    /// next fires instead.
+    pub fn stop_task(&mut self, id: String) -> usize {
+        let Some(task) = self.find_or_report(&id) else {
+            return 0;
+        };
+        let Some(wake_at) = next_snooze(task, Local::now()) else {
+            return self.suspend_task(id, Duration::zero(), None);
+        };
+        let mut updated_task = task.clone();
         updated_task.status = TaskStatus::Sleeping;
-        let time_delta = parse(&duration).unwrap();
-        println!("Sleeping for {} seconds", time_delta.as_secs());
-        updated_task.wake_at = Some(Local::now() + time_delta);
-        let id = task.id.clone();
-        self.tasks.retain(|task| task.id != id);
-        self.tasks.push(updated_task);
+        updated_task.wake_at = Some(wake_at);
+        self.log_event(
+            "suspend",
+            &task.id,
+            Some(task.status.clone()),
+            Some(TaskStatus::Sleeping),
+        );
+        self.replace_task(updated_task);
+        self.note(format!("Snoozing until {}", wake_at.format("%a %F %R")));
+        1
+    }
+
+    /// Start the task whose id starts with the id string passed in,
+    /// recording it as started at the given time.
+    pub fn start_task_at(&mut self, id: String, at: DateTime<Local>) -> usize {
+        let Some(task) = self.find_or_report(&id) else {
+            return 0;
+        };
+        if task.status == TaskStatus::Active {
+            return 0;
+        }
+        if at < task.created_at {
+            println!("Task '{}' can't be started before it was created", task.id);
+            return 0;
+        }
+        if !self.can_start_another() {
+            self.report_active_limit();
+            return 0;
+        }
+        let mut updated_task = task.clone();
+        updated_task.status = TaskStatus::Active;
+        updated_task.last_started_at = Some(at);
+        updated_task.completed_at = None;
+        self.log_event_at(
+            "start",
+            &task.id,
+            Some(task.status.clone()),
+            Some(TaskStatus::Active),
+            at,
+        );
+        self.replace_task(updated_task);
+        1
+    }
+
+    /// Suspend the task whose id starts with the id string passed in.  With
+    /// wake_at_hour, the wake time is moved to that hour of the day it falls on.
+    pub fn suspend_task(
+        &mut self,
+        id: String,
+        duration: Duration,
+        wake_at_hour: Option<u32>,
+    ) -> usize {
+        let Some(task) = self.find_or_report(&id) else {
+            return 0;
+        };
+        let mut updated_task = task.clone();
+        updated_task.status = TaskStatus::Sleeping;
+        let now = Local::now();
+        let wake_at = now + duration;
+        updated_task.wake_at = Some(match wake_at_hour {
+            Some(hour) => round_wake_time(wake_at, hour, now),
+            None => wake_at,
+        });
+        self.log_event(
+            "suspend",
+            &task.id,
+            Some(task.status.clone()),
+            Some(TaskStatus::Sleeping),
+        );
+        self.replace_task(updated_task);
+        self.note(format!("Sleeping for {} seconds", duration.num_seconds()));
         1
     }
+
+    /// Schedule the task whose id starts with the id string passed in to
+    /// become active at the given time, which must be in the future.
+    /// Completed and blocked tasks can't be scheduled.
+    pub fn schedule_task(&mut self, id: String, when: DateTime<Local>) -> usize {
+        let Some(task) = self.find_or_report(&id) else {
+            return 0;
+        };
+        if matches!(task.status, TaskStatus::Completed | TaskStatus::Blocked) {
+            println!(
+                "Task '{}' is {} and can't be scheduled",
+                task.id,
+                task.status.to_string().to_lowercase()
+            );
+            return 0;
+        }
+        if when <= Local::now() {
+            println!("Task '{}' can't be scheduled in the past", task.id);
+            return 0;
+        }
+        let mut updated_task = task.clone();
+        updated_task.status = TaskStatus::Scheduled;
+        updated_task.scheduled_at = Some(when);
+        updated_task.wake_at = None;
+        self.log_event(
+            "schedule",
+            &task.id,
+            Some(task.status.clone()),
+            Some(TaskStatus::Scheduled),
+        );
+        self.replace_task(updated_task);
+        1
+    }
+}
+
+/// When a task with a snooze rule should next wake, counting from the given
+/// time.  Rules are checked when set, so one that no longer parses is ignored.
+fn next_snooze(task: &Task, after: DateTime<Local>) -> Option<DateTime<Local>> {
+    let rule = parse_snooze_rule(task.snooze_rule.as_deref()?).ok()?;
+    Some(rule.next_after(after))
+}
+
+/// Move a wake time to the given hour (0-23) on the day it falls on.  If
+/// that is no longer in the future, wake at that hour the next day instead.
+pub fn round_wake_time(
+    wake_at: DateTime<Local>,
+    hour: u32,
+    now: DateTime<Local>,
+) -> DateTime<Local> {
+    let at_hour = |date: chrono::NaiveDate| {
+        date.and_hms_opt(hour, 0, 0)
+            .and_then(|time| time.and_local_timezone(Local).earliest())
+    };
+    let date = wake_at.date_naive();
+    match at_hour(date) {
+        Some(rounded) if rounded > now => rounded,
+        Some(_) => date.succ_opt().and_then(at_hour).unwrap_or(wake_at),
+        None => wake_at,
+    }
+}
+
+/// Advisory lock on a database, held by exclusively creating `{db}.lock`.
+/// The lock file is removed again when the lock is dropped.
+pub struct DbLock {
+    path: String,
+}
+
+impl DbLock {
+    /// Take the lock on the database, waiting briefly if another instance
+    /// holds it.
+    pub fn acquire(db_path: &str) -> Result<DbLock, io::Error> {
+        let path = format!("{}.lock", db_path);
+        let deadline = std::time::Instant::now() + LOCK_TIMEOUT;
+        loop {
+            match fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+            {
+                Ok(mut file) => {
+                    // The pid is only there to help diagnose a stale lock
+                    let _ = write!(file, "{}", std::process::id());
+                    return Ok(DbLock { path });
+                }
+                Err(e) if e.kind() != io::ErrorKind::AlreadyExists => return Err(e),
+                Err(_) if std::time::Instant::now() < deadline => {
+                    std::thread::sleep(LOCK_RETRY_INTERVAL)
+                }
+                Err(_) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::WouldBlock,
+                        format!(
+                            "another ztask instance is using this database (remove '{}' if not)",
+                            path
+                        ),
+                    ))
+                }
+            }
+        }
+    }
+}
+
+impl Drop for DbLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Task order of the last numbered listing, saved alongside the database
+#[derive(Serialize, Deserialize)]
+struct OrdinalMap {
+    /// Ids in the order they were listed
+    listed_ids: Vec<String>,
+    /// Sorted ids of every task at the time of listing
+    all_ids: Vec<String>,
+}
+
+/// Return true if the id selects the task id, either as a prefix or,
+/// when exact, as the whole id.
+fn id_matches(task_id: &str, id: &str, exact: bool) -> bool {
+    if exact {
+        task_id == id
+    } else {
+        task_id.starts_with(id)
+    }
+}
+
+/// Describe a database parse error, including the offending line and a
+/// marker under the column where parsing failed.
+fn describe_parse_error(db_path: &str, contents: &str, e: &serde_json::Error) -> String {
+    let mut message = format!(
+        "Failed to parse '{}' at line {}, column {}: {}",
+        db_path,
+        e.line(),
+        e.column(),
+        e
+    );
+    if let Some(line) = contents.lines().nth(e.line().saturating_sub(1)) {
+        message.push_str(&format!(
+            "\n  {}\n  {}^",
+            line,
+            " ".repeat(e.column().saturating_sub(1))
+        ));
+    }
+    message
 }
 
 // xref: /usr/local/develop/rust-commandline-example/src/main.rs
@@ -366,6 +1326,13 @@ pub mod tests {
     use super::*;
     use uuid::Uuid;
 
+    impl TaskList {
+        /// Create a new task list, printing any incidental messages
+        pub fn new(db_path: String) -> Self {
+            TaskList::open(db_path, false).unwrap()
+        }
+    }
+
     /// Create a temporary test database with the given number of tasks
     pub fn __create_temp_db(initial_task_count: i32) -> String {
         use std::path::Path;
@@ -407,13 +1374,999 @@ pub mod tests {
         let mut iter = task_list.tasks.iter().skip(1);
         let id = iter.next().unwrap().id.clone();
 
-        task_list.remove_task(id);
+        assert_eq!(task_list.remove_task(id), 1);
         assert_eq!(task_list.tasks.len(), 1);
 
         drop(task_list);
         __destroy_temp_db(db);
     }
 
+    #[test]
+    fn verify_load_reports_parse_location() {
+        let db = __create_temp_db(0);
+        fs::write(&db, "[\n  {\n    \"id\": \"abc\"\n  },\n]\n").unwrap();
+
+        let err = TaskList::load(db.clone()).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let message = err.to_string();
+        assert!(message.contains("line 4, column"), "{}", message);
+        assert!(message.contains("  },"), "{}", message);
+        assert!(message.contains('^'), "{}", message);
+
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_load_reports_trailing_comma() {
+        let db = __create_temp_db(2);
+        let contents = fs::read_to_string(&db).unwrap();
+        let contents = contents
+            .trim_end()
+            .trim_end_matches(']')
+            .trim_end()
+            .to_string()
+            + ",\n]";
+        fs::write(&db, contents).unwrap();
+
+        let err = TaskList::load(db.clone()).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("trailing comma"), "{}", err);
+
+        __destroy_temp_db(db);
+    }
+
+    /// Set one field of one task, as `edit --set key=value` does
+    pub fn __update_field(
+        task_list: &mut TaskList,
+        id: String,
+        key: &str,
+        value: &str,
+    ) -> Result<usize, String> {
+        task_list.update_fields(&[id], &[(key.to_string(), value.to_string())])
+    }
+
+    /// Make every task look as if it was created at the given time, so
+    /// tests can start and complete them at earlier, fixed times
+    pub fn __backdate_tasks(task_list: &mut TaskList, created_at: DateTime<Local>) {
+        task_list.tasks.update(|task| task.created_at = created_at);
+        task_list.dirty = true;
+    }
+
+    /// Create a task list holding the given number of backlog tasks
+    fn __create_backlog(db: &str, count: usize) -> TaskList {
+        let mut task_list = TaskList::new(db.to_string());
+        for i in 0..count {
+            task_list.add_task(Task::new(
+                format!("backlog task {i}"),
+                "quick".to_string(),
+                false,
+            ));
+        }
+        task_list
+    }
+
+    /// Compare the backends on the queries listing a 50k-task database
+    /// makes, one per list section.  Run with
+    /// `cargo test --release bench_backends -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn bench_backends() {
+        let mut task_list = TaskList::new(format!("data/temp/{}-bench.json", Uuid::new_v4()));
+        task_list.no_save = true;
+        let statuses = [
+            TaskStatus::Active,
+            TaskStatus::Backlog,
+            TaskStatus::Blocked,
+            TaskStatus::Sleeping,
+            TaskStatus::Scheduled,
+            TaskStatus::Completed,
+        ];
+        for i in 0..50_000 {
+            let mut task = Task::new(format!("bench {i}"), "quick".to_string(), false);
+            task.status = statuses[i % statuses.len()].clone();
+            task.priority = (i % 5 + 1) as u8;
+            task_list.tasks.push(task);
+        }
+        for backend in [Backend::Heap, Backend::Vec] {
+            task_list.set_backend(backend);
+            let start = std::time::Instant::now();
+            for _ in 0..5 {
+                for status in &statuses {
+                    task_list.filtered(&Filter::default().with_status(status.clone()));
+                }
+            }
+            println!("{:?}: {:?} for 5 listings", backend, start.elapsed());
+        }
+        let _ = fs::remove_file(&task_list.db_path);
+    }
+
+    #[test]
+    fn verify_max_active_of_one() {
+        let db = __create_temp_db(0);
+        let mut task_list = __create_backlog(&db, 2);
+        let ids: Vec<String> = task_list.tasks.iter().map(|t| t.id.clone()).collect();
+
+        assert_eq!(task_list.start_task_at(ids[0].clone(), Local::now()), 1);
+        assert_eq!(task_list.start_task_at(ids[1].clone(), Local::now()), 0);
+        assert_eq!(task_list.num_active(), 1);
+
+        drop(task_list);
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_max_active_of_three() {
+        let db = __create_temp_db(0);
+        let mut task_list = __create_backlog(&db, 4);
+        task_list.max_active = 3;
+        let ids: Vec<String> = task_list.tasks.iter().map(|t| t.id.clone()).collect();
+
+        let started: usize = ids
+            .into_iter()
+            .map(|id| task_list.start_task_at(id, Local::now()))
+            .sum();
+        assert_eq!(started, 3);
+        assert_eq!(task_list.num_active(), 3);
+
+        drop(task_list);
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_max_active_unlimited() {
+        let db = __create_temp_db(0);
+        let mut task_list = __create_backlog(&db, 4);
+        task_list.max_active = 0;
+        let ids: Vec<String> = task_list.tasks.iter().map(|t| t.id.clone()).collect();
+
+        let started: usize = ids
+            .into_iter()
+            .map(|id| task_list.start_task_at(id, Local::now()))
+            .sum();
+        assert_eq!(started, 4);
+
+        drop(task_list);
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_update_fields_is_atomic() {
+        let db = __create_temp_db(0);
+        let mut task_list = __create_backlog(&db, 3);
+        task_list.max_active = 1;
+        let ids: Vec<String> = task_list.tasks.iter().map(|t| t.id.clone()).collect();
+        let fields = |pairs: &[(&str, &str)]| -> Vec<(String, String)> {
+            pairs
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect()
+        };
+
+        // A bad value anywhere leaves every task untouched
+        let bad = fields(&[("priority", "1"), ("status", "bogus")]);
+        assert!(task_list.update_fields(&ids[..2], &bad).is_err());
+        // So does an id that doesn't match a task
+        let good = fields(&[("priority", "1")]);
+        let missing = vec![ids[0].clone(), "nonexistent".to_string()];
+        assert!(task_list.update_fields(&missing, &good).is_err());
+        assert!(task_list.tasks.iter().all(|task| task.priority != 1));
+
+        // Activating goes through the limit on active tasks
+        let active = fields(&[("status", "active")]);
+        assert!(task_list.update_fields(&ids[..2], &active).is_err());
+        assert_eq!(task_list.num_active(), 0);
+        assert_eq!(task_list.update_fields(&ids[..1], &active), Ok(1));
+        assert!(__update_field(&mut task_list, ids[1].clone(), "status", "active").is_err());
+        assert_eq!(task_list.num_active(), 1);
+
+        assert_eq!(task_list.update_fields(&ids, &good), Ok(3));
+        assert!(task_list.tasks.iter().all(|task| task.priority == 1));
+
+        drop(task_list);
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_update_field() {
+        let db = __create_temp_db(0);
+        let mut task_list = __create_backlog(&db, 1);
+        let id = task_list.tasks.peek().unwrap().id.clone();
+
+        assert_eq!(
+            __update_field(&mut task_list, id.clone(), "priority", "1"),
+            Ok(1)
+        );
+        assert_eq!(
+            __update_field(&mut task_list, id.clone(), "status", "completed"),
+            Ok(1)
+        );
+        let task = task_list.copy_task(id.clone()).unwrap();
+        assert_eq!(task.priority, 1);
+        assert_eq!(task.status, TaskStatus::Completed);
+
+        assert!(__update_field(&mut task_list, id.clone(), "priority", "9").is_err());
+        assert!(__update_field(&mut task_list, id.clone(), "status", "done").is_err());
+        assert!(__update_field(&mut task_list, id.clone(), "colour", "red").is_err());
+
+        drop(task_list);
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_bump_priority() {
+        let db = __create_temp_db(0);
+        let mut task_list = __create_backlog(&db, 1);
+        let id = task_list.tasks.peek().unwrap().id.clone();
+        __update_field(&mut task_list, id.clone(), "priority", "3").unwrap();
+
+        assert_eq!(task_list.bump_priority(id.clone(), -2), 1);
+        assert_eq!(task_list.copy_task(id.clone()).unwrap().priority, 1);
+        task_list.bump_priority(id.clone(), -1);
+        assert_eq!(task_list.copy_task(id.clone()).unwrap().priority, 1);
+        task_list.bump_priority(id.clone(), 10);
+        assert_eq!(task_list.copy_task(id.clone()).unwrap().priority, 5);
+
+        drop(task_list);
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_completed_tasks_are_not_blocked() {
+        let db = __create_temp_db(0);
+        let mut task_list = __create_backlog(&db, 3);
+        let ids: Vec<String> = task_list.tasks.iter().map(|t| t.id.clone()).collect();
+        task_list.complete_task_at(ids[0].clone(), Local::now());
+
+        // A completed blockee is rejected, and stays completed
+        assert_eq!(task_list.block_task_on(&ids[0], &ids[1]), 0);
+        let task = task_list.copy_task(ids[0].clone()).unwrap();
+        assert_eq!(task.status, TaskStatus::Completed);
+        assert!(task.blocked_by.is_empty());
+
+        // A completed blocker is skipped
+        assert_eq!(task_list.block_task_on(&ids[1], &ids[0]), 0);
+        assert_eq!(
+            task_list.copy_task(ids[1].clone()).unwrap().status,
+            TaskStatus::Backlog
+        );
+
+        assert_eq!(task_list.block_task_on(&ids[1], &ids[2]), 1);
+        assert_eq!(
+            task_list.copy_task(ids[1].clone()).unwrap().status,
+            TaskStatus::Blocked
+        );
+
+        drop(task_list);
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_task_is_not_blocked_on_itself() {
+        let db = __create_temp_db(0);
+        let mut task_list = __create_backlog(&db, 2);
+        let id = task_list.tasks.peek().unwrap().id.clone();
+
+        // Ids that resolve to the same task are rejected, however abbreviated
+        assert_eq!(task_list.block_task_on(&id, &id), 0);
+        assert_eq!(task_list.block_task_on(&id, &id[..7]), 0);
+        let task = task_list.copy_task(id.clone()).unwrap();
+        assert_eq!(task.status, TaskStatus::Backlog);
+        assert!(task.blocked_by.is_empty());
+
+        drop(task_list);
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_only_dirty_lists_are_saved() {
+        let db = __create_temp_db(2);
+        let task_list = TaskList::new(db.clone());
+        assert!(!task_list.dirty);
+        drop(task_list);
+
+        let mut task_list = TaskList::new(db.clone());
+        task_list.no_save = true;
+        task_list.add_task(Task::new("unsaved".to_string(), "quick".to_string(), false));
+        assert!(task_list.dirty);
+        drop(task_list);
+        assert_eq!(TaskList::new(db.clone()).num_tasks(), 2);
+
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_resolve_ordinal() {
+        let db = __create_temp_db(3);
+        let mut task_list = TaskList::new(db.clone());
+        let listed: Vec<String> = task_list.tasks.iter().map(|t| t.id.clone()).collect();
+        assert_eq!(task_list.resolve_ordinal(1), None);
+
+        task_list.save_ordinals(&listed).unwrap();
+        assert_eq!(task_list.resolve_ordinal(2), Some(listed[1].clone()));
+        assert_eq!(task_list.resolve_ordinal(0), None);
+        assert_eq!(task_list.resolve_ordinal(4), None);
+        assert_eq!(
+            task_list.resolve_ordinals(vec!["#3".to_string(), "abc".to_string()]),
+            vec![listed[2].clone(), "abc".to_string()]
+        );
+
+        // Adding a task makes the numbering stale
+        task_list.add_task(Task::new("new".to_string(), "quick".to_string(), false));
+        assert_eq!(task_list.resolve_ordinal(2), None);
+
+        drop(task_list);
+        let _ = fs::remove_file(format!("{}.ordinals", db));
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_scheduled_tasks_start_when_due() {
+        let db = __create_temp_db(0);
+        let mut task_list = __create_backlog(&db, 2);
+        let ids: Vec<String> = task_list.tasks.iter().map(|t| t.id.clone()).collect();
+        let now = Local::now();
+        assert_eq!(
+            task_list.schedule_task(ids[0].clone(), now + Duration::days(1)),
+            1
+        );
+        assert_eq!(
+            task_list.schedule_task(ids[1].clone(), now + Duration::minutes(1)),
+            1
+        );
+        // Bring the second task's start time into the past, as if time passed
+        let mut task = task_list.copy_task(ids[1].clone()).unwrap();
+        task.scheduled_at = Some(now - Duration::minutes(1));
+        task_list.replace_task(task);
+        assert_eq!(
+            __update_field(&mut task_list, ids[0].clone(), "status", "scheduled"),
+            Err("Status 'scheduled' can't be set directly, use the schedule command".to_string())
+        );
+        drop(task_list);
+
+        // Loading the list starts the task that is due, not the later one
+        let mut task_list = TaskList::new(db.clone());
+        let later = task_list.copy_task(ids[0].clone()).unwrap();
+        assert_eq!(later.status, TaskStatus::Scheduled);
+        assert_eq!(later.scheduled_at, Some(now + Duration::days(1)));
+        let due = task_list.copy_task(ids[1].clone()).unwrap();
+        assert_eq!(due.status, TaskStatus::Active);
+        assert_eq!(due.scheduled_at, None);
+        assert_eq!(task_list.activate_scheduled_tasks(), 0);
+
+        drop(task_list);
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_schedule_task_rejections() {
+        let db = __create_temp_db(0);
+        let mut task_list = __create_backlog(&db, 3);
+        let ids: Vec<String> = task_list.tasks.iter().map(|t| t.id.clone()).collect();
+        let now = Local::now();
+
+        // Times in the past, and completed or blocked tasks, are rejected
+        assert_eq!(
+            task_list.schedule_task(ids[0].clone(), now - Duration::minutes(1)),
+            0
+        );
+        task_list.complete_task_at(ids[1].clone(), now);
+        assert_eq!(
+            task_list.schedule_task(ids[1].clone(), now + Duration::days(1)),
+            0
+        );
+        task_list.block_task_on(&ids[2], &ids[0]);
+        assert_eq!(
+            task_list.schedule_task(ids[2].clone(), now + Duration::days(1)),
+            0
+        );
+        assert_eq!(
+            task_list.copy_task(ids[2].clone()).unwrap().blocked_by,
+            BTreeSet::from([ids[0].clone()])
+        );
+        assert_eq!(
+            task_list.copy_task(ids[0].clone()).unwrap().status,
+            TaskStatus::Backlog
+        );
+
+        drop(task_list);
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_due_scheduled_tasks_respect_max_active() {
+        let db = __create_temp_db(0);
+        let mut task_list = __create_backlog(&db, 3);
+        task_list.max_active = 2;
+        let ids: Vec<String> = task_list.tasks.iter().map(|t| t.id.clone()).collect();
+        let now = Local::now();
+        task_list.start_task_at(ids[0].clone(), now);
+        for (id, minutes) in [(&ids[1], 2), (&ids[2], 1)] {
+            task_list.schedule_task(id.clone(), now + Duration::days(1));
+            let mut task = task_list.copy_task(id.clone()).unwrap();
+            task.scheduled_at = Some(now - Duration::minutes(minutes));
+            task_list.replace_task(task);
+        }
+
+        // Only one slot is free, so the task due first takes it
+        assert_eq!(task_list.activate_scheduled_tasks(), 1);
+        assert_eq!(
+            task_list.copy_task(ids[1].clone()).unwrap().status,
+            TaskStatus::Active
+        );
+        assert_eq!(
+            task_list.copy_task(ids[2].clone()).unwrap().status,
+            TaskStatus::Scheduled
+        );
+
+        // It starts once a slot frees up
+        task_list.complete_task_at(ids[0].clone(), now);
+        assert_eq!(task_list.activate_scheduled_tasks(), 1);
+        assert_eq!(task_list.num_active(), 2);
+
+        drop(task_list);
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_new_creates_empty_db() {
+        let db = format!("data/temp/{}/fresh-test.json", Uuid::new_v4().simple());
+        let task_list = TaskList::new(db.clone());
+        assert_eq!(task_list.num_tasks(), 0);
+        assert!(!task_list.dirty);
+
+        let contents = fs::read_to_string(&db).unwrap();
+        let tasks: Vec<Task> = serde_json::from_str(&contents).unwrap();
+        assert!(tasks.is_empty());
+        drop(task_list);
+
+        fs::remove_file(&db).unwrap();
+        fs::remove_dir(std::path::Path::new(&db).parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn verify_round_wake_time() {
+        let at = |s: &str| {
+            chrono::NaiveDateTime::parse_from_str(s, "%F %R")
+                .unwrap()
+                .and_local_timezone(Local)
+                .unwrap()
+        };
+        let now = at("2030-03-10 15:00");
+        // Rounded to the hour on the day the sleep ends
+        assert_eq!(
+            round_wake_time(at("2030-03-13 15:00"), 8, now),
+            at("2030-03-13 08:00")
+        );
+        assert_eq!(
+            round_wake_time(at("2030-03-13 15:00"), 23, now),
+            at("2030-03-13 23:00")
+        );
+        // Later the same day is fine, but earlier moves to the next day
+        assert_eq!(
+            round_wake_time(at("2030-03-10 16:00"), 18, now),
+            at("2030-03-10 18:00")
+        );
+        assert_eq!(
+            round_wake_time(at("2030-03-10 16:00"), 9, now),
+            at("2030-03-11 09:00")
+        );
+    }
+
+    #[test]
+    fn verify_suspend_with_wake_at_hour() {
+        let db = __create_temp_db(1);
+        let mut task_list = TaskList::new(db.clone());
+        let id = task_list.tasks.peek().unwrap().id.clone();
+        assert_eq!(
+            task_list.suspend_task(id.clone(), Duration::days(3), Some(7)),
+            1
+        );
+        let wake_at = task_list.copy_task(id).unwrap().wake_at.unwrap();
+        assert_eq!(wake_at.format("%T").to_string(), "07:00:00");
+        assert!(wake_at > Local::now() + Duration::days(2));
+        drop(task_list);
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_snooze_rule_transitions() {
+        let db = __create_temp_db(2);
+        let mut task_list = TaskList::new(db.clone());
+        let ids: Vec<String> = task_list.tasks.iter().map(|task| task.id.clone()).collect();
+        assert!(__update_field(&mut task_list, ids[0].clone(), "snooze", "weekdays@9am").is_err());
+        for id in &ids {
+            __update_field(&mut task_list, id.clone(), "snooze", "weekdays@09:00").unwrap();
+        }
+
+        let at = Local::now();
+        assert_eq!(task_list.complete_task_at(ids[0].clone(), at), 1);
+        let completed = task_list.copy_task(ids[0].clone()).unwrap();
+        assert_eq!(completed.status, TaskStatus::Sleeping);
+        assert_eq!(completed.completed_at, None);
+        let rule = crate::snooze::parse_snooze_rule("weekdays@09:00").unwrap();
+        assert_eq!(completed.wake_at, Some(rule.next_after(at)));
+
+        assert_eq!(task_list.stop_task(ids[1].clone()), 1);
+        let stopped = task_list.copy_task(ids[1].clone()).unwrap();
+        assert_eq!(stopped.status, TaskStatus::Sleeping);
+        assert!(stopped.wake_at.unwrap() > Local::now());
+        drop(task_list);
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_backdated_snooze_wakes_in_future() {
+        let db = __create_temp_db(0);
+        let log = db.replace("-test.json", "-events.log");
+        let mut task_list = TaskList::new(db.clone());
+        task_list.event_log = Some(log.clone());
+        let mut task = Task::new("standup".to_string(), "quick".to_string(), false);
+        task.created_at = Local::now() - Duration::weeks(4);
+        task.snooze_rule = Some("daily@09:00".to_string());
+        let id = task_list.add_task(task);
+
+        assert_eq!(
+            task_list.complete_task_at(id.clone(), Local::now() - Duration::weeks(2)),
+            1
+        );
+        let task = task_list.copy_task(id).unwrap();
+        assert_eq!(task.status, TaskStatus::Sleeping);
+        assert!(task.wake_at.unwrap() > Local::now());
+        let events = eventlog::read(&log).unwrap();
+        assert_eq!(events.last().unwrap().op, "snooze");
+        assert_eq!(events.last().unwrap().to_status, Some(TaskStatus::Sleeping));
+
+        drop(task_list);
+        let _ = fs::remove_file(log);
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_reset_task() {
+        let db = __create_temp_db(2);
+        let mut task_list = TaskList::new(db.clone());
+        let ids: Vec<String> = task_list.tasks.iter().map(|task| task.id.clone()).collect();
+        let mut messy = task_list.copy_task(ids[0].clone()).unwrap();
+        messy.status = TaskStatus::Sleeping;
+        messy.wake_at = Some(Local::now() - Duration::days(1));
+        messy.scheduled_at = Some(Local::now() + Duration::days(1));
+        messy.blocked_by.insert("gone".to_string());
+        messy.completed_at = Some(Local::now() - Duration::days(2));
+        task_list.replace_task(messy);
+
+        assert_eq!(
+            task_list.reset_task(ids[0].clone()),
+            Some(vec![
+                "status",
+                "wake time",
+                "scheduled time",
+                "blockers",
+                "completion time"
+            ])
+        );
+        let task = task_list.copy_task(ids[0].clone()).unwrap();
+        assert_eq!(task.status, TaskStatus::Backlog);
+        assert_eq!(task.wake_at, None);
+        assert_eq!(task.scheduled_at, None);
+        assert!(task.blocked_by.is_empty());
+        assert_eq!(task.completed_at, None);
+        assert_eq!(task_list.reset_task(ids[0].clone()), Some(vec![]));
+        assert_eq!(task_list.reset_task("nomatch".to_string()), None);
+        drop(task_list);
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_db_order_by_id() {
+        let db = __create_temp_db(0);
+        let mut task_list = TaskList::new(db.clone());
+        task_list.db_order = DbOrder::Id;
+        let ids: Vec<String> = (1..=3)
+            .map(|priority| task_list.add_task(Task::builder().priority(priority).build()))
+            .collect();
+        let saved_ids = |path: &str| -> Vec<String> {
+            let tasks: Vec<Task> =
+                serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap();
+            tasks.into_iter().map(|task| task.id).collect()
+        };
+        task_list.save().unwrap();
+        let before = saved_ids(&db);
+        let mut sorted = ids.clone();
+        sorted.sort();
+        assert_eq!(before, sorted);
+
+        // Making the last task the most urgent reorders the listing, not the file
+        __update_field(&mut task_list, ids[2].clone(), "priority", "1").unwrap();
+        task_list.save().unwrap();
+        assert_eq!(saved_ids(&db), before);
+
+        task_list.db_order = DbOrder::Display;
+        task_list.save().unwrap();
+        let listed: Vec<String> = task_list
+            .filtered(&Filter::default())
+            .into_iter()
+            .map(|task| task.id)
+            .collect();
+        assert_eq!(saved_ids(&db), listed);
+        drop(task_list);
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_rename_category_and_tag() {
+        let db = __create_temp_db(0);
+        let mut task_list = TaskList::new(db.clone());
+        for (category, tag) in [("quick", "home"), ("Quick ", "work"), ("deep", "home")] {
+            let mut task = Task::builder().category(category).build();
+            task.tags.insert(tag.to_string());
+            task_list.add_task(task);
+        }
+
+        assert_eq!(task_list.rename_category("quick", "Misc"), 2);
+        let count = |category: &str| {
+            task_list
+                .tasks
+                .iter()
+                .filter(|task| task.category == category)
+                .count()
+        };
+        assert_eq!((count("misc"), count("deep")), (2, 1));
+        assert_eq!(task_list.rename_category("quick", "misc"), 0);
+
+        assert_eq!(task_list.rename_tag("HOME", "house"), 2);
+        assert!(task_list
+            .tasks
+            .iter()
+            .all(|task| !task.tags.contains("home")));
+        assert_eq!(task_list.rename_tag("garden", "yard"), 0);
+        drop(task_list);
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_db_lock() {
+        let db = __create_temp_db(0);
+        let lock = DbLock::acquire(&db).unwrap();
+        let err = DbLock::acquire(&db).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+        assert!(err.to_string().contains("another ztask instance"));
+
+        drop(lock);
+        let lock = DbLock::acquire(&db).unwrap();
+        drop(lock);
+        assert!(!std::path::Path::new(&format!("{}.lock", db)).exists());
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_exact_id_matching() {
+        let db = __create_temp_db(2);
+        let mut task_list = TaskList::new(db.clone());
+        let id = task_list.tasks.peek().unwrap().id.clone();
+
+        task_list.exact = true;
+        assert!(task_list.copy_task(id[..9].to_string()).is_none());
+        assert!(task_list.copy_task(id.clone()).is_some());
+        assert!(task_list.copy_task(format!("{}0", id)).is_none());
+
+        task_list.exact = false;
+        assert!(task_list.copy_task(id[..9].to_string()).is_some());
+        assert!(task_list.copy_task(format!("{}0", id)).is_none());
+
+        drop(task_list);
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_find_unique() {
+        let db = __create_temp_db(0);
+        let mut task_list = TaskList::new(db.clone());
+        for id in ["a1", "a2", "b1"] {
+            task_list.add_task(Task::builder().id(id).build());
+        }
+
+        assert_eq!(task_list.find_unique("b").unwrap().id, "b1");
+        assert_eq!(task_list.find_unique("a1").unwrap().id, "a1");
+        assert_eq!(
+            task_list.find_unique("c").err(),
+            Some(LookupError::NotFound("c".to_string()))
+        );
+        assert_eq!(
+            task_list.find_unique("a").err(),
+            Some(LookupError::Ambiguous("a".to_string(), 2))
+        );
+        // An id longer than any task id simply matches nothing
+        let long_id = "a1".repeat(50);
+        assert_eq!(
+            task_list.find_unique(&long_id).err(),
+            Some(LookupError::NotFound(long_id.clone()))
+        );
+        assert_eq!(task_list.remove_task(long_id), 0);
+        assert_eq!(
+            LookupError::Ambiguous("a".to_string(), 2).to_string(),
+            "Id 'a' does not uniquely match one task.  It matches 2"
+        );
+        drop(task_list);
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_event_log() {
+        let db = __create_temp_db(0);
+        let log = db.replace("-test.json", "-events.log");
+        let mut task_list = TaskList::new(db.clone());
+        task_list.event_log = Some(log.clone());
+
+        let id = task_list.add_task(Task::new("logged".to_string(), "quick".to_string(), false));
+        task_list.start_task_at(id.clone(), Local::now());
+        task_list.complete_task_at(id.clone(), Local::now());
+        task_list.remove_task(id.clone());
+
+        let events = eventlog::read(&log).unwrap();
+        let ops: Vec<&str> = events.iter().map(|event| event.op.as_str()).collect();
+        assert_eq!(ops, ["add", "start", "complete", "remove"]);
+        assert_eq!(events[1].from_status, Some(TaskStatus::Backlog));
+        assert_eq!(events[2].to_status, Some(TaskStatus::Completed));
+        assert!(events.iter().all(|event| event.task_id == id));
+
+        drop(task_list);
+        let _ = fs::remove_file(log);
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_event_log_uses_backdated_time() {
+        let db = __create_temp_db(0);
+        let log = db.replace("-test.json", "-events.log");
+        let mut task_list = TaskList::new(db.clone());
+        task_list.event_log = Some(log.clone());
+        let mut task = Task::new("backdated".to_string(), "quick".to_string(), false);
+        let created_at = Local::now() - Duration::hours(3);
+        task.created_at = created_at;
+        let id = task_list.add_task(task);
+
+        // Times before the task was created are rejected
+        let too_early = created_at - Duration::minutes(1);
+        assert_eq!(task_list.start_task_at(id.clone(), too_early), 0);
+        assert_eq!(task_list.complete_task_at(id.clone(), too_early), 0);
+
+        let started_at = created_at + Duration::hours(1);
+        let completed_at = created_at + Duration::hours(2);
+        assert_eq!(task_list.start_task_at(id.clone(), started_at), 1);
+        assert_eq!(task_list.complete_task_at(id.clone(), completed_at), 1);
+        let task = task_list.copy_task(id).unwrap();
+        let events = eventlog::read(&log).unwrap();
+        assert_eq!(events.len(), 3);
+        assert_eq!(Some(events[1].ts), task.last_started_at);
+        assert_eq!(Some(events[2].ts), task.completed_at);
+
+        drop(task_list);
+        let _ = fs::remove_file(log);
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_no_save_skips_event_log() {
+        let db = __create_temp_db(0);
+        let log = db.replace("-test.json", "-events.log");
+        let mut task_list = TaskList::new(db.clone());
+        task_list.event_log = Some(log.clone());
+        task_list.no_save = true;
+
+        let id = task_list.add_task(Task::new("unsaved".to_string(), "quick".to_string(), false));
+        task_list.start_task_at(id, Local::now());
+        assert!(eventlog::read(&log).unwrap().is_empty());
+
+        drop(task_list);
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_event_log_failure_is_not_fatal() {
+        let db = __create_temp_db(0);
+        let mut task_list = TaskList::new(db.clone());
+        task_list.event_log = Some("data/temp/no-such-dir/events.log".to_string());
+        task_list.add_task(Task::new(
+            "unlogged".to_string(),
+            "quick".to_string(),
+            false,
+        ));
+        assert_eq!(task_list.num_tasks(), 1);
+        drop(task_list);
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_remaining_estimate() {
+        let db = __create_temp_db(0);
+        let mut task_list = TaskList::new(db.clone());
+        for (estimate, status) in [
+            (Some("2h"), TaskStatus::Active),
+            (Some("30m"), TaskStatus::Backlog),
+            (None, TaskStatus::Backlog),
+            (Some("8h"), TaskStatus::Completed),
+        ] {
+            let mut task = Task::new("estimated".to_string(), "quick".to_string(), false);
+            task.estimate = estimate.map(|e| parse_estimate(e).unwrap());
+            task.status = status;
+            task_list.add_task(task);
+        }
+
+        let (total, unestimated) = task_list.remaining_estimate();
+        assert_eq!(total, Duration::minutes(150));
+        assert_eq!(unestimated, 1);
+
+        drop(task_list);
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_rebalance_priorities() {
+        let db = __create_temp_db(0);
+        let mut task_list = __create_backlog(&db, 5);
+        let before: Vec<String> = task_list
+            .tasks
+            .sorted()
+            .into_iter()
+            .map(|task| task.id)
+            .collect();
+
+        assert_eq!(task_list.rebalance_priorities(), 4);
+        let after = task_list.tasks.sorted();
+        let priorities: Vec<u8> = after.iter().map(|task| task.priority).collect();
+        assert_eq!(priorities, [1, 2, 3, 4, 5]);
+        let ids: Vec<String> = after.into_iter().map(|task| task.id).collect();
+        assert_eq!(ids, before);
+
+        // Already balanced, so nothing changes
+        assert_eq!(task_list.rebalance_priorities(), 0);
+
+        drop(task_list);
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_restore_from_backup() {
+        let db = __create_temp_db(2);
+        let mut task_list = TaskList::new(db.clone());
+        let backup = task_list.backup_path();
+        fs::copy(&db, &backup).unwrap();
+        task_list.add_task(Task::new("extra".to_string(), "quick".to_string(), false));
+
+        assert_eq!(task_list.restore_from(&backup).unwrap(), 2);
+        assert_eq!(task_list.num_tasks(), 2);
+        // The pre-restore state is kept so the restore can be reversed
+        assert_eq!(TaskList::load(backup.clone()).unwrap().len(), 3);
+
+        drop(task_list);
+        let _ = fs::remove_file(backup);
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_restore_rejects_invalid_backup() {
+        let db = __create_temp_db(2);
+        let mut task_list = TaskList::new(db.clone());
+        let backup = task_list.backup_path();
+        fs::write(&backup, "[ not json").unwrap();
+
+        assert!(task_list.restore_from(&backup).is_err());
+        assert_eq!(task_list.num_tasks(), 2);
+        assert_eq!(fs::read_to_string(&backup).unwrap(), "[ not json");
+
+        drop(task_list);
+        let _ = fs::remove_file(backup);
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_short_ids() {
+        let db = __create_temp_db(0);
+        let mut task_list = TaskList::new(db.clone());
+        task_list.id_generator = IdGenerator::Short;
+        for i in 0..11 {
+            let task = task_list
+                .task_builder()
+                .summary(format!("short {i}"))
+                .build();
+            task_list.add_task(task);
+        }
+        let ids: BTreeSet<String> = task_list.tasks.iter().map(|t| t.id.clone()).collect();
+        assert_eq!(ids.len(), 11);
+        assert!(ids.contains("a1") && ids.contains("a11"));
+
+        // "a1" names its own task rather than also matching a10 and a11
+        assert_eq!(
+            task_list.copy_task("a1".to_string()).unwrap().summary,
+            "short 0"
+        );
+        assert!(task_list.copy_task("a".to_string()).is_none());
+
+        drop(task_list);
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_quiet_suppresses_notes() {
+        let db = __create_temp_db(1);
+        let mut task_list = TaskList::new(db.clone());
+        let id = task_list.tasks.peek().unwrap().id.clone();
+        task_list.quiet = true;
+        task_list.suspend_task(id.clone(), Duration::zero(), None);
+        assert!(task_list.notes.is_empty());
+        drop(task_list);
+
+        let task_list = TaskList::open(db.clone(), true).unwrap();
+        assert!(task_list.notes.is_empty());
+        drop(task_list);
+
+        // The quiet load above saved the woken task, so sleep it again
+        let mut task_list = TaskList::new(db.clone());
+        task_list.suspend_task(id, Duration::zero(), None);
+        assert_eq!(task_list.notes, ["Sleeping for 0 seconds"]);
+        drop(task_list);
+        let task_list = TaskList::new(db.clone());
+        assert_eq!(task_list.notes, ["Awakened 1 task(s)"]);
+        drop(task_list);
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_merge_duplicates() {
+        let db = __create_temp_db(0);
+        let mut task_list = TaskList::new(db.clone());
+        let mut first = Task::new("Call  the bank".to_string(), "quick".to_string(), false);
+        first.details = "ask about fees".to_string();
+        let mut second = Task::new("call the bank".to_string(), "quick".to_string(), false);
+        second.details = "bring statement".to_string();
+        second.created_at = first.created_at + Duration::hours(1);
+        let (first, second) = (task_list.add_task(first), task_list.add_task(second));
+        let other = task_list.add_task(Task::new("other".to_string(), "quick".to_string(), false));
+        task_list.block_task_on(&other, &second);
+
+        let groups = task_list.find_duplicate_groups();
+        assert_eq!(groups, vec![vec![first.clone(), second.clone()]]);
+
+        assert_eq!(task_list.merge_tasks(&groups[0]), 1);
+        assert_eq!(task_list.num_tasks(), 2);
+        let merged = task_list.copy_task(first.clone()).unwrap();
+        assert_eq!(merged.details, "ask about fees\n\nbring statement");
+        let other = task_list.copy_task(other).unwrap();
+        assert_eq!(other.blocked_by, BTreeSet::from([first]));
+        assert!(task_list.find_duplicate_groups().is_empty());
+
+        drop(task_list);
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_merge_takes_on_blockers() {
+        let db = __create_temp_db(0);
+        let mut task_list = TaskList::new(db.clone());
+        let first = task_list.add_task(Task::builder().summary("File taxes").build());
+        let second = task_list.add_task(Task::builder().summary("file taxes").build());
+        let blocker = task_list.add_task(Task::builder().summary("Find receipts").build());
+        task_list.start_task_at(first.clone(), Local::now());
+        task_list.block_task_on(&second, &blocker);
+
+        assert_eq!(task_list.merge_tasks(&[first.clone(), second]), 1);
+        let merged = task_list.copy_task(first).unwrap();
+        assert_eq!(merged.status, TaskStatus::Blocked);
+        assert_eq!(merged.blocked_by, BTreeSet::from([blocker.clone()]));
+
+        // Completing the blocker unblocks the merged task as usual
+        task_list.complete_task_at(blocker, Local::now());
+        task_list.unblock_tasks();
+        assert_eq!(task_list.num_active(), 0);
+        assert!(task_list
+            .tasks
+            .iter()
+            .all(|task| task.status != TaskStatus::Blocked));
+
+        drop(task_list);
+        __destroy_temp_db(db);
+    }
+
     #[test]
     fn verify_edit_single() {
         let db = __create_temp_db(2);
@@ -428,4 +2381,71 @@ pub mod tests {
         drop(task_list);
         __destroy_temp_db(db);
     }
+
+    #[test]
+    fn verify_unchanged_details_edit() {
+        let db = __create_temp_db(1);
+        let mut task_list = TaskList::new(db.clone());
+        let mut task = task_list.tasks.peek().unwrap().clone();
+        task.details = "first line\nsecond line".to_string();
+        task_list.replace_task(task.clone());
+        task_list.save().unwrap();
+        drop(task_list);
+
+        // The editor isn't run under test, so the details come back as they were
+        let mut task_list = TaskList::new(db.clone());
+        assert_eq!(task_list.edit_task_details(task.id.clone()), 0);
+        assert!(!task_list.dirty);
+        let unchanged = task_list.copy_task(task.id).unwrap();
+        assert_eq!(unchanged.details, task.details);
+        drop(task_list);
+        __destroy_temp_db(db);
+    }
+
+    #[test]
+    fn verify_batch_edit() {
+        let db = __create_temp_db(3);
+        let mut task_list = TaskList::new(db.clone());
+        let originals: Vec<Task> = task_list.tasks.iter().take(2).cloned().collect();
+        let untouched = task_list
+            .tasks
+            .iter()
+            .find(|task| originals.iter().all(|o| o.id != task.id))
+            .unwrap()
+            .clone();
+
+        // An unchanged buffer changes nothing
+        let ids: Vec<String> = originals.iter().map(|task| task.id.clone()).collect();
+        assert_eq!(task_list.edit_tasks_batch(ids), Ok(0));
+
+        let mut edited = originals.clone();
+        edited[0].priority = 1;
+        edited[1].priority = 5;
+        let mut renamed = originals[0].clone();
+        renamed.id = "not-being-edited".to_string();
+        edited.push(renamed);
+        let buffer = serde_json::to_string_pretty(&edited).unwrap();
+        assert_eq!(task_list.apply_batch_edit(&originals, &buffer), Ok(2));
+
+        assert_eq!(
+            task_list
+                .copy_task(originals[0].id.clone())
+                .unwrap()
+                .priority,
+            1
+        );
+        assert_eq!(
+            task_list
+                .copy_task(originals[1].id.clone())
+                .unwrap()
+                .priority,
+            5
+        );
+        assert!(task_list.copy_task(untouched.id.clone()).unwrap() == untouched);
+        assert_eq!(task_list.tasks.len(), 3);
+
+        assert!(task_list.apply_batch_edit(&originals, "not json").is_err());
+        drop(task_list);
+        __destroy_temp_db(db);
+    }
 }
@@ -0,0 +1,287 @@
+//! Storage for the tasks of a task list.  By default the tasks are kept in
+//! a `BinaryHeap`, so every sorted query clones and drains the whole heap.
+//! Very large lists can instead keep them in a `Vec` with a sorted index
+//! that is built on the first query and only rebuilt after the tasks change,
+//! so a query clones just the tasks it returns.
+
+use crate::task::Task;
+use std::cell::OnceCell;
+use std::collections::BinaryHeap;
+
+/// How the tasks of a task list are stored
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Backend {
+    /// A binary heap, cloned and drained for every sorted query
+    #[default]
+    Heap,
+    /// A vector with a lazily kept sorted index, for very large lists
+    Vec,
+}
+
+/// Number of tasks above which the vec backend is used, unless another
+/// backend is asked for
+pub const VEC_BACKEND_THRESHOLD: usize = 10_000;
+
+impl Backend {
+    /// The backend for a list of the given number of tasks
+    pub fn for_size(num_tasks: usize) -> Self {
+        match num_tasks > VEC_BACKEND_THRESHOLD {
+            true => Backend::Vec,
+            false => Backend::Heap,
+        }
+    }
+}
+
+/// Parse a storage backend name, heap or vec.
+pub fn parse_backend(s: &str) -> Result<Backend, String> {
+    match s.trim().to_lowercase().as_str() {
+        "heap" => Ok(Backend::Heap),
+        "vec" => Ok(Backend::Vec),
+        _ => Err(format!("Invalid backend '{}', expected heap or vec", s)),
+    }
+}
+
+/// The tasks of a task list, in either backend.  Sorted order is the order
+/// the heap drains in, so both backends list tasks identically.
+#[derive(Clone)]
+pub enum TaskStore {
+    Heap(BinaryHeap<Task>),
+    Vec {
+        tasks: Vec<Task>,
+        /// Indexes of the tasks in sorted order, built when first needed
+        order: OnceCell<Vec<usize>>,
+    },
+}
+
+impl Default for TaskStore {
+    fn default() -> Self {
+        TaskStore::new(Backend::default())
+    }
+}
+
+impl TaskStore {
+    /// Create an empty store using the given backend
+    pub fn new(backend: Backend) -> Self {
+        TaskStore::from_tasks(backend, vec![])
+    }
+
+    /// Create a store holding the given tasks, using the given backend
+    pub fn from_tasks(backend: Backend, tasks: Vec<Task>) -> Self {
+        match backend {
+            Backend::Heap => TaskStore::Heap(tasks.into()),
+            Backend::Vec => TaskStore::Vec {
+                tasks,
+                order: OnceCell::new(),
+            },
+        }
+    }
+
+    /// The backend the tasks are stored in
+    pub fn backend(&self) -> Backend {
+        match self {
+            TaskStore::Heap(_) => Backend::Heap,
+            TaskStore::Vec { .. } => Backend::Vec,
+        }
+    }
+
+    /// Move the tasks into the given backend
+    pub fn with_backend(self, backend: Backend) -> Self {
+        if self.backend() == backend {
+            return self;
+        }
+        TaskStore::from_tasks(backend, self.into_vec())
+    }
+
+    /// The tasks, in no particular order
+    fn as_slice(&self) -> &[Task] {
+        match self {
+            TaskStore::Heap(heap) => heap.as_slice(),
+            TaskStore::Vec { tasks, .. } => tasks,
+        }
+    }
+
+    /// Indexes of the vector's tasks in sorted order, building them if the
+    /// tasks have changed since they were last needed
+    fn order<'a>(tasks: &[Task], order: &'a OnceCell<Vec<usize>>) -> &'a [usize] {
+        order.get_or_init(|| {
+            let mut order: Vec<usize> = (0..tasks.len()).collect();
+            // Compare as the heap does, which is the reverse of Task::cmp
+            order.sort_by(|&a, &b| tasks[a].partial_cmp(&tasks[b]).unwrap());
+            order
+        })
+    }
+
+    /// Iterate over the tasks, in no particular order
+    pub fn iter(&self) -> std::slice::Iter<'_, Task> {
+        self.as_slice().iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.as_slice().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.as_slice().is_empty()
+    }
+
+    /// The task the heap would pop next
+    #[cfg(test)]
+    pub fn peek(&self) -> Option<&Task> {
+        match self {
+            TaskStore::Heap(heap) => heap.peek(),
+            TaskStore::Vec { tasks, order } => {
+                TaskStore::order(tasks, order).last().map(|&i| &tasks[i])
+            }
+        }
+    }
+
+    /// Remove and return the task the heap would pop next
+    pub fn pop(&mut self) -> Option<Task> {
+        match self {
+            TaskStore::Heap(heap) => heap.pop(),
+            TaskStore::Vec { tasks, order } => {
+                let last = *TaskStore::order(tasks, order).last()?;
+                order.take();
+                Some(tasks.swap_remove(last))
+            }
+        }
+    }
+
+    pub fn push(&mut self, task: Task) {
+        match self {
+            TaskStore::Heap(heap) => heap.push(task),
+            TaskStore::Vec { tasks, order } => {
+                tasks.push(task);
+                order.take();
+            }
+        }
+    }
+
+    /// Keep only the tasks the predicate accepts
+    pub fn retain(&mut self, keep: impl FnMut(&Task) -> bool) {
+        match self {
+            TaskStore::Heap(heap) => heap.retain(keep),
+            TaskStore::Vec { tasks, order } => {
+                tasks.retain(keep);
+                order.take();
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.retain(|_| false);
+    }
+
+    /// Apply the change to every task, restoring the order afterwards
+    pub fn update(&mut self, change: impl FnMut(&mut Task)) {
+        match self {
+            TaskStore::Heap(heap) => {
+                let mut tasks = std::mem::take(heap).into_vec();
+                tasks.iter_mut().for_each(change);
+                *heap = tasks.into();
+            }
+            TaskStore::Vec { tasks, order } => {
+                tasks.iter_mut().for_each(change);
+                order.take();
+            }
+        }
+    }
+
+    /// Return copies of the tasks the predicate accepts, in sorted order.
+    /// The heap is cloned whole, but only the matching tasks in the vector.
+    pub fn sorted_matching(&self, mut matches: impl FnMut(&Task) -> bool) -> Vec<Task> {
+        match self {
+            TaskStore::Heap(heap) => {
+                let mut heap = heap.clone();
+                heap.retain(matches);
+                heap.into_sorted_vec()
+            }
+            TaskStore::Vec { tasks, order } => TaskStore::order(tasks, order)
+                .iter()
+                .map(|&i| &tasks[i])
+                .filter(|task| matches(task))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Return copies of all the tasks, in sorted order
+    pub fn sorted(&self) -> Vec<Task> {
+        self.sorted_matching(|_| true)
+    }
+
+    /// The tasks, in no particular order
+    pub fn into_vec(self) -> Vec<Task> {
+        match self {
+            TaskStore::Heap(heap) => heap.into_vec(),
+            TaskStore::Vec { tasks, .. } => tasks,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task::TaskStatus;
+
+    /// The same tasks in each backend
+    fn stores() -> [TaskStore; 2] {
+        let tasks: Vec<Task> = (0..8)
+            .map(|i| {
+                Task::builder()
+                    .summary(format!("task {i}"))
+                    .priority(i % 5 + 1)
+                    .status(match i % 3 {
+                        0 => TaskStatus::Active,
+                        1 => TaskStatus::Backlog,
+                        _ => TaskStatus::Completed,
+                    })
+                    .build()
+            })
+            .collect();
+        [Backend::Heap, Backend::Vec].map(|backend| TaskStore::from_tasks(backend, tasks.clone()))
+    }
+
+    /// Ids of the tasks in sorted order
+    fn sorted_ids(store: &TaskStore) -> Vec<String> {
+        store.sorted().into_iter().map(|task| task.id).collect()
+    }
+
+    #[test]
+    fn check_backends_agree() {
+        let [mut heap, mut vec] = stores();
+        assert_eq!(vec.backend(), Backend::Vec);
+        assert_eq!(sorted_ids(&heap), sorted_ids(&vec));
+        assert_eq!(heap.peek().map(|t| &t.id), vec.peek().map(|t| &t.id));
+        let is_backlog = |task: &Task| task.status == TaskStatus::Backlog;
+        assert!(heap
+            .sorted_matching(is_backlog)
+            .iter()
+            .map(|task| &task.id)
+            .eq(vec.sorted_matching(is_backlog).iter().map(|task| &task.id)));
+
+        // The index is rebuilt after every change
+        let late = Task::builder().summary("late").priority(1).build();
+        for store in [&mut heap, &mut vec] {
+            store.pop();
+            store.update(|task| task.priority = 6 - task.priority);
+            store.push(late.clone());
+            store.retain(|task| task.status != TaskStatus::Completed);
+        }
+        assert_eq!(heap.len(), vec.len());
+        assert_eq!(sorted_ids(&heap), sorted_ids(&vec));
+        assert_eq!(heap.peek().map(|t| &t.id), vec.peek().map(|t| &t.id));
+        let vec = vec.with_backend(Backend::Heap);
+        assert_eq!(vec.backend(), Backend::Heap);
+        assert_eq!(sorted_ids(&heap), sorted_ids(&vec));
+    }
+
+    #[test]
+    fn check_parse_backend() {
+        assert_eq!(parse_backend("Vec"), Ok(Backend::Vec));
+        assert_eq!(parse_backend(" heap"), Ok(Backend::Heap));
+        assert!(parse_backend("btree").is_err());
+        assert_eq!(Backend::for_size(VEC_BACKEND_THRESHOLD), Backend::Heap);
+        assert_eq!(Backend::for_size(VEC_BACKEND_THRESHOLD + 1), Backend::Vec);
+    }
+}
@@ -0,0 +1,153 @@
+//! Reusable task templates, each saved as `<name>.json` in a templates
+//! directory.  A template's fields are defaults for tasks added from it.
+
+use crate::task::{duration_seconds, normalize_label, parse_priority, Task};
+use chrono::Duration;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// Default templates directory
+pub const TEMPLATES_DIR: &str = "$HOME/.ztask/templates";
+
+/// Fields copied into tasks added from a template.  Every field is optional.
+#[derive(Serialize, Deserialize, Default, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Template {
+    /// Summary used when no task names are given
+    pub summary: Option<String>,
+    pub details: Option<String>,
+    pub category: Option<String>,
+    pub priority: Option<u8>,
+    pub owner: Option<String>,
+    /// Estimated effort, stored in seconds
+    #[serde(with = "duration_seconds")]
+    pub estimate: Option<Duration>,
+    pub tags: BTreeSet<String>,
+}
+
+impl Template {
+    /// Make a template from the fields of an existing task
+    pub fn from_task(task: &Task) -> Self {
+        Template {
+            summary: Some(task.summary.clone()),
+            details: Some(task.details.clone()).filter(|details| !details.is_empty()),
+            category: Some(task.category.clone()),
+            priority: Some(task.priority),
+            owner: task.owner.clone(),
+            estimate: task.estimate,
+            tags: task.tags.clone(),
+        }
+    }
+
+    /// Load the template with the given name from the templates directory.
+    /// Templates can be edited by hand, so the priority is checked and the
+    /// category and tags are normalized as they would be for a task.
+    pub fn load(dir: &str, name: &str) -> Result<Template, io::Error> {
+        let path = template_path(dir, name)?;
+        match fs::read_to_string(&path) {
+            Ok(contents) => {
+                let mut template: Template = serde_json::from_str(&contents)?;
+                if let Some(priority) = template.priority {
+                    parse_priority(&priority.to_string()).map_err(|e| {
+                        io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("Template '{}': {}", name, e),
+                        )
+                    })?;
+                }
+                template.category = template.category.as_deref().map(normalize_label);
+                template.tags = template
+                    .tags
+                    .iter()
+                    .map(|tag| normalize_label(tag))
+                    .collect();
+                Ok(template)
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No template named '{}'", name),
+            )),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Save the template under the given name, replacing any template that
+    /// already has that name
+    pub fn save(&self, dir: &str, name: &str) -> Result<(), io::Error> {
+        let path = template_path(dir, name)?;
+        fs::create_dir_all(dir)?;
+        fs::write(path, serde_json::to_string_pretty(self)?)
+    }
+}
+
+/// Return the names of the saved templates, in alphabetical order
+pub fn list_templates(dir: &str) -> Result<Vec<String>, io::Error> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e),
+    };
+    let mut names = vec![];
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "json") {
+            if let Some(name) = path.file_stem() {
+                names.push(name.to_string_lossy().to_string());
+            }
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+/// Path of the file for the named template.  Names are plain file names, so
+/// a template can't be read or written outside the templates directory.
+fn template_path(dir: &str, name: &str) -> Result<PathBuf, io::Error> {
+    if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Invalid template name '{}'", name),
+        ));
+    }
+    Ok(PathBuf::from(dir).join(format!("{}.json", name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verify_template_round_trip() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path().to_str().unwrap();
+        let template = Template {
+            summary: Some("Deploy".to_string()),
+            priority: Some(2),
+            estimate: Some(Duration::minutes(90)),
+            ..Default::default()
+        };
+        template.save(dir, "deploy").unwrap();
+        assert_eq!(Template::load(dir, "deploy").unwrap(), template);
+        assert_eq!(list_templates(dir).unwrap(), ["deploy"]);
+        assert!(Template::load(dir, "missing").is_err());
+        assert!(template.save(dir, "../escape").is_err());
+    }
+
+    #[test]
+    fn verify_template_is_checked_on_load() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path().to_str().unwrap();
+        let path = temp_dir.path().join("edited.json");
+        fs::write(&path, r#"{ "category": " Work ", "tags": ["Urgent"] }"#).unwrap();
+        let template = Template::load(dir, "edited").unwrap();
+        assert_eq!(template.category.as_deref(), Some("work"));
+        assert_eq!(template.tags, BTreeSet::from(["urgent".to_string()]));
+
+        fs::write(&path, r#"{ "priority": 9 }"#).unwrap();
+        let e = Template::load(dir, "edited").unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+    }
+}